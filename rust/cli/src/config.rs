@@ -7,13 +7,29 @@ pub struct Config {
     pub save: Save,
     pub n_iterations: usize,
     pub time_step: f64,
+    pub adaptive: bool,
     pub vorton_to_velocity_algorithm: VortonToVelocityAlgorithm,
+    pub time_integrator: Option<TimeIntegrator>,
+    pub remesh: Option<usize>,
+    pub diagnostics: Option<String>,
+    /// Number of iterations between accuracy reports of the tree algorithm, and number of samples
+    pub check_accuracy: Option<(usize, usize)>,
+}
+
+#[derive(Debug)]
+pub enum TimeIntegrator {
+  Euler,
+  Midpoint,
+  RungeKutta4,
+  AdamsBashforth2,
 }
 
 #[derive(Debug)]
 pub enum VortonToVelocityAlgorithm {
   Simple,
-  Tree,
+  Tree { theta: Option<f64>, max_leaf: Option<usize>, max_depth: Option<usize> },
+  Fmm(usize),
+  Vic,
 }
 
 pub enum Initial {
@@ -33,6 +49,7 @@ pub enum Action {
 }
 
 pub enum Output {
+    #[allow(clippy::upper_case_acronyms)]
     CSV(String),
     Velocity(String),
     Nothing,
@@ -66,7 +83,7 @@ impl Config {
                  .action(clap::ArgAction::Count))
             .arg(Arg::new("csv")
                  .long("csv")
-                 .help("Output vortex particle positions and vorticity to CSV file format")
+                 .help("Output vortex particle positions and vorticity, and tracer positions if any, to CSV file format")
                  .value_name("DIRECTORY")
                  .action(clap::ArgAction::Set))
             .arg(Arg::new("ovel")
@@ -86,14 +103,55 @@ impl Config {
                  .action(clap::ArgAction::Set)
                  .value_name("DURATION")
                  .default_value("0.03"))
+            .arg(Arg::new("adaptive")
+                 .long("adaptive")
+                 .help("Use adaptive time stepping. Each iteration then advances the simulation by the nominated time step using as many sub-steps as required")
+                 .action(clap::ArgAction::Count))
             .arg(Arg::new("alg_simple")
                  .long("alg_simple")
                  .help("Use the simple algorithm for calculating velocity from vortons")
                  .action(clap::ArgAction::Count))
             .arg(Arg::new("alg_tree")
                  .long("alg_tree")
-                 .help("Use the tree algorithm for calculating velocity from vortons")
+                 .help("Use the tree algorithm for calculating velocity from vortons, with an optional opening angle, maximum number of vortons per leaf and maximum depth")
+                 .value_names(["THETA", "MAX_LEAF", "MAX_DEPTH"])
+                 .num_args(0..=3)
+                 .action(clap::ArgAction::Set))
+            .arg(Arg::new("alg_fmm")
+                 .long("alg_fmm")
+                 .help("Use the fast multipole method for calculating velocity from vortons, with an optional expansion order (default 6)")
+                 .value_name("ORDER")
+                 .num_args(0..=1)
+                 .default_missing_value("6")
+                 .action(clap::ArgAction::Set))
+            .arg(Arg::new("alg_vic")
+                 .long("alg_vic")
+                 .help("Use the vortex-in-cell method for calculating velocity from vortons")
                  .action(clap::ArgAction::Count))
+            .arg(Arg::new("integrator")
+                 .long("integrator")
+                 .help("Nominate the time integration scheme. Defaults to the scheme of the simulation")
+                 .value_name("euler|midpoint|rk4|ab2")
+                 .value_parser(["euler", "midpoint", "rk4", "ab2"])
+                 .action(clap::ArgAction::Set))
+            .arg(Arg::new("remesh")
+                 .long("remesh")
+                 .help("Remesh the vortons onto a regular grid every nominated number of iterations (default 10)")
+                 .value_name("INTEGER")
+                 .num_args(0..=1)
+                 .default_missing_value("10")
+                 .action(clap::ArgAction::Set))
+            .arg(Arg::new("diagnostics")
+                 .long("diagnostics")
+                 .help("Output the time history of the conservation diagnostics to a CSV file")
+                 .value_name("PATH/FILE")
+                 .action(clap::ArgAction::Set))
+            .arg(Arg::new("check_accuracy")
+                 .long("check-accuracy")
+                 .help("Print the accuracy of the tree algorithm compared to the direct sum every nominated number of iterations (default 10), using the nominated number of samples (default 100)")
+                 .value_names(["ITERATIONS", "SAMPLES"])
+                 .num_args(0..=2)
+                 .action(clap::ArgAction::Set))
             .get_matches();
         
         let mut action = Action::Nothing;
@@ -116,11 +174,44 @@ impl Config {
         let mut time_step = 0.03;
         if let Some(v) = matches.get_one::<String>("time_step") { time_step = v.parse::<f64>().unwrap(); }
 
+        let adaptive = matches.get_count("adaptive") > 0;
+
         let mut vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Simple;
         if matches.get_count("alg_simple") > 0 { vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Simple; }
-        if matches.get_count("alg_tree") > 0 { vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Tree; }
+        if let Some(v) = matches.get_many::<String>("alg_tree") {
+          let v = v.collect::<Vec<_>>();
+          vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Tree {
+            theta: match v.first() { Some(v) => Some(v.parse::<f64>()?), None => None },
+            max_leaf: match v.get(1) { Some(v) => Some(v.parse::<usize>()?), None => None },
+            max_depth: match v.get(2) { Some(v) => Some(v.parse::<usize>()?), None => None },
+          };
+        }
+        if let Some(v) = matches.get_one::<String>("alg_fmm") { vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Fmm(v.parse::<usize>()?); }
+        if matches.get_count("alg_vic") > 0 { vorton_to_velocity_algorithm = VortonToVelocityAlgorithm::Vic; }
+
+        let time_integrator = match matches.get_one::<String>("integrator").map(|v| v.as_str()) {
+          Some("euler")    => Some(TimeIntegrator::Euler),
+          Some("midpoint") => Some(TimeIntegrator::Midpoint),
+          Some("rk4")      => Some(TimeIntegrator::RungeKutta4),
+          Some("ab2")      => Some(TimeIntegrator::AdamsBashforth2),
+          _                => None,
+        };
+
+        let remesh = match matches.get_one::<String>("remesh") { Some(v) => Some(v.parse::<usize>()?), None => None };
+
+        let diagnostics = matches.get_one::<String>("diagnostics").cloned();
+
+        let mut check_accuracy = None;
+        if let Some(v) = matches.get_many::<String>("check_accuracy") {
+          let v = v.collect::<Vec<_>>();
+          check_accuracy = Some((match v.first() { Some(v) => v.parse::<usize>()?, None => 10 },
+                                 match v.get(1) { Some(v) => v.parse::<usize>()?, None => 100 }));
+          if !matches!(vorton_to_velocity_algorithm, VortonToVelocityAlgorithm::Tree { .. }) {
+            return Err("--check-accuracy reports on the tree algorithm and requires --alg_tree".into());
+          }
+        }
 
-        Ok(Config { action, output, initial, save, n_iterations, time_step, vorton_to_velocity_algorithm, })
+        Ok(Config { action, output, initial, save, n_iterations, time_step, adaptive, vorton_to_velocity_algorithm, time_integrator, remesh, diagnostics, check_accuracy, })
     }
 }
 
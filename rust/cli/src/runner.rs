@@ -1,9 +1,10 @@
 use std::time::SystemTime;
 use std::fs::{self, File};
+use std::io::Write;
 use std::path::Path;
 
 use crate::{config};
-use vortex_particle_simulation::{Simulation, Profiler, VortonToVelocityAlgorithm, Geometry};
+use vortex_particle_simulation::{Simulation, Profiler, VortonToVelocityAlgorithm, Geometry, TimeIntegrator, TimeStepController, Remeshing, RemeshingTrigger, PopulationReport, Diagnostics};
 
 pub fn run(config: config::Config) -> Result<(), Box<dyn std::error::Error>> {
   use std::convert::TryFrom;
@@ -25,11 +26,36 @@ pub fn run(config: config::Config) -> Result<(), Box<dyn std::error::Error>> {
             config::VortonToVelocityAlgorithm::Simple => {
               sim.use_vorton_to_velocity(VortonToVelocityAlgorithm::Simple);
             },
-            config::VortonToVelocityAlgorithm::Tree => {
-              sim.use_vorton_to_velocity(VortonToVelocityAlgorithm::Tree(6));
+            config::VortonToVelocityAlgorithm::Tree { theta, max_leaf, max_depth } => {
+              let mut algorithm = VortonToVelocityAlgorithm::tree();
+              if let VortonToVelocityAlgorithm::Tree { theta: t, max_leaf: l, max_depth: d, .. } = &mut algorithm {
+                if let Some(theta) = theta { *t = *theta; }
+                if let Some(max_leaf) = max_leaf { *l = *max_leaf; }
+                if let Some(max_depth) = max_depth { *d = *max_depth; }
+              }
+              sim.use_vorton_to_velocity(algorithm);
+            },
+            config::VortonToVelocityAlgorithm::Fmm(order) => {
+              sim.use_vorton_to_velocity(VortonToVelocityAlgorithm::Fmm(*order));
+            },
+            config::VortonToVelocityAlgorithm::Vic => {
+              sim.use_vorton_to_velocity(VortonToVelocityAlgorithm::Vic);
             },
           };
 
+          if let Some(time_integrator) = &config.time_integrator {
+            sim.use_time_integrator(match time_integrator {
+              config::TimeIntegrator::Euler           => TimeIntegrator::Euler,
+              config::TimeIntegrator::Midpoint        => TimeIntegrator::Midpoint,
+              config::TimeIntegrator::RungeKutta4     => TimeIntegrator::RungeKutta4,
+              config::TimeIntegrator::AdamsBashforth2 => TimeIntegrator::AdamsBashforth2,
+            });
+          }
+
+          if let Some(n) = config.remesh {
+            sim.use_remeshing(Some(Remeshing { trigger: RemeshingTrigger::Every(n), ..Remeshing::default() }));
+          }
+
           /* Run the simulation */
           match &config.action {
               config::Action::Run     => run_simulation(&config, sim)?,
@@ -48,14 +74,36 @@ pub fn run(config: config::Config) -> Result<(), Box<dyn std::error::Error>> {
 
 fn run_simulation(config: &config::Config, simulation: &mut Simulation) -> Result<(), Box<dyn std::error::Error>> {
     output(config, simulation)?;
+    let mut diagnostics = match &config.diagnostics {
+      Some(f) => { let mut file = File::create(f)?; file.write_all(Diagnostics::csv_header().as_bytes())?; Some(file) },
+      None    => None,
+    };
+    write_diagnostics(&mut diagnostics, simulation)?;
+    check_accuracy(config, simulation)?;
     let system_time = SystemTime::now();
     let time_step = config.time_step;
-    let start_iteration = simulation.iteration();
     let mut profiler = Profiler::new(|| {system_time.elapsed().unwrap().as_millis() as f64})?;
-    while simulation.iteration() < start_iteration + config.n_iterations {
-        simulation.step(time_step, &mut profiler)?;
+    if config.adaptive {
+        simulation.use_time_step_controller(TimeStepController { max_time_step: time_step, ..TimeStepController::default() });
+    }
+    for _ in 0..config.n_iterations {
+        let iteration = simulation.iteration();
+        let sub_steps = if config.adaptive {
+            simulation.step_adaptive(simulation.time() + time_step, &mut profiler)?.len()
+        } else {
+            simulation.step(time_step, &mut profiler)?; 1
+        };
         output(config, simulation)?;
-        println!("Iteration {}: {:.2}s [{}]", simulation.iteration(), simulation.time(), 
+        write_diagnostics(&mut diagnostics, simulation)?;
+        check_accuracy(config, simulation)?;
+        if simulation.last_remesh().is_some_and(|i| i > iteration) {
+            println!("Remesh: {} vortons", simulation.vortons().len());
+        }
+        if *simulation.population_report() != PopulationReport::default() {
+            println!("Population: {:?}", simulation.population_report());
+        }
+        println!("Iteration {}: {:.2}s{} [{}]", simulation.iteration(), simulation.time(), 
+                 if config.adaptive { format!(" ({} sub-steps)", sub_steps) } else { "".to_string() },
                  profiler.as_magnitude()
                  .iter().fold("".to_string(), |r, v| format!("{}{}{}: {}ms", r, if r.is_empty() {""} else {"; "}, v.0, v.1))
                  ); 
@@ -70,6 +118,10 @@ fn output(config: &config::Config, simulation: &Simulation) -> Result<(), Box<dy
           let file = open_file(dir, format!("vortex_particles_{}.csv", simulation.iteration()))?;
           vortex_particle_simulation::VortonCollection::from(simulation)
           .to_writer_csv(file, "vorticity", |v| Ok(v.vorticity().norm()))?;
+          let tracers = vortex_particle_simulation::TracerCollection::from(simulation);
+          if ! tracers.is_empty() {
+            tracers.to_writer_csv(open_file(dir, format!("tracers_{}.csv", simulation.iteration()))?)?;
+          }
           Ok(())
         },
 
@@ -85,6 +137,24 @@ fn output(config: &config::Config, simulation: &Simulation) -> Result<(), Box<dy
     }
 }
 
+/// Append the diagnostics of the current iteration to the time history file, if any
+fn write_diagnostics(file: &mut Option<File>, simulation: &Simulation) -> Result<(), Box<dyn std::error::Error>> {
+  if let Some(file) = file {
+    file.write_all(Diagnostics::new(simulation)?.to_csv().as_bytes())?;
+  }
+  Ok(())
+}
+
+/// Print the accuracy report of the tree algorithm when due
+fn check_accuracy(config: &config::Config, simulation: &Simulation) -> Result<(), Box<dyn std::error::Error>> {
+  if let Some((n, n_samples)) = config.check_accuracy {
+    if n > 0 && simulation.iteration().is_multiple_of(n) {
+      print!("{}", simulation.accuracy_report(n_samples)?);
+    }
+  }
+  Ok(())
+}
+
 fn open_file(dir: &String, fname: String) -> Result<std::fs::File, Box<dyn std::error::Error>> {
   let path = Path::new(".").join(dir);
   if ! path.exists() { fs::create_dir_all(path.clone())?;}
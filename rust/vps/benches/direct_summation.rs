@@ -0,0 +1,38 @@
+//! Direct summation of the velocity induced by 50k vortons, vorton by vorton and in batches
+//! using the structure of arrays.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use vortex_particle_simulation::{Kernel, Point3, Vector3, Vorton, VortonArrays};
+use vortex_particle_simulation::random::random;
+
+const N_VORTONS: usize = 50_000;
+const N_POSITIONS: usize = 64;
+
+fn direct_summation(c: &mut Criterion) {
+  let mut seed = 7;
+  let vortons = (0..N_VORTONS)
+    .map(|_| Vorton::new(Point3::new(random(&mut seed), random(&mut seed), random(&mut seed)),
+                         Vector3::new(random(&mut seed) - 0.5, random(&mut seed) - 0.5, random(&mut seed) - 0.5),
+                         1e-6))
+    .collect::<Vec<Vorton>>();
+  let positions = vortons.iter().step_by(N_VORTONS / N_POSITIONS).map(|v| v.position().clone()).collect::<Vec<_>>();
+  let arrays = VortonArrays::from(vortons.as_slice());
+
+  for kernel in [Kernel::SolidSphere, Kernel::WinckelmansLeonard] {
+    let mut group = c.benchmark_group(format!("direct_summation_{kernel:?}"));
+    group.sample_size(10);
+    group.bench_function("scalar", |b| b.iter(|| {
+      positions.iter()
+        .map(|p| vortons.iter().fold(Vector3::default(), |r, v| r + v.velocity_contribution(&kernel, p)))
+        .fold(Vector3::default(), |r, u| r + black_box(u))
+    }));
+    group.bench_function("batched", |b| b.iter(|| {
+      positions.iter()
+        .map(|p| arrays.velocity_at(&kernel, 0..arrays.len(), p))
+        .fold(Vector3::default(), |r, u| r + black_box(u))
+    }));
+    group.finish();
+  }
+}
+
+criterion_group!(benches, direct_summation);
+criterion_main!(benches);
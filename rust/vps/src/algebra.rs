@@ -1,2 +1,4 @@
 mod point3; pub use point3::Point3;
 mod vector3; pub use vector3::Vector3;
+mod matrix3; pub use matrix3::Matrix3;
+pub(crate) mod dense;
@@ -0,0 +1,128 @@
+/// Solve the least squares problem `min |A x - b|` for the `m x n` matrix `A`, given by rows,
+/// using the normal equations `(AᵀA + λI) x = Aᵀb`. The regularisation `λ`, relative to the
+/// largest diagonal entry of `AᵀA`, keeps the system solvable when `A` is rank deficient.
+pub fn least_squares(a: &[Vec<f64>], b: &[f64], lambda: f64) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+  let n = a.first().map(|row| row.len()).unwrap_or(0);
+  if a.len() != b.len() || a.iter().any(|row| row.len() != n) { return Err("Inconsistent least squares dimensions".into()); }
+  let mut ata = vec![vec![0.0; n]; n];
+  let mut atb = vec![0.0; n];
+  for (row, b) in a.iter().zip(b) {
+    for i in 0..n {
+      if row[i] == 0.0 { continue; }
+      atb[i] += row[i] * b;
+      for (m, r) in ata[i].iter_mut().zip(row) { *m += row[i] * r; }
+    }
+  }
+  let scale = (0..n).map(|i| ata[i][i]).fold(0.0, f64::max);
+  for (i, row) in ata.iter_mut().enumerate() { row[i] += lambda * scale; }
+  solve(ata, atb)
+}
+
+/// Solve the square linear system `A x = b`, `A` being given by rows, using gaussian
+/// elimination with partial pivoting
+pub fn solve(a: Vec<Vec<f64>>, b: Vec<f64>) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+  Lu::new(a)?.solve(&b)
+}
+
+/// Solution of smallest norm of the underdetermined systems `A x = b` for the `m x n` matrix
+/// `A`, given by rows, as `x = Aᵀ y` with `(AAᵀ + λI) y = b`. The regularisation `λ` is
+/// relative to the largest diagonal entry of `AAᵀ`. The factorization of `AAᵀ + λI` is kept
+/// to solve for several right hand sides `b`.
+#[derive(Debug)]
+pub struct MinimumNorm {
+  a: Vec<Vec<f64>>,
+  aat: Lu,
+}
+
+impl MinimumNorm {
+  pub fn new(a: Vec<Vec<f64>>, lambda: f64) -> Result<MinimumNorm, Box<dyn std::error::Error>> {
+    let n = a.first().map(|row| row.len()).unwrap_or(0);
+    if a.iter().any(|row| row.len() != n) { return Err("Inconsistent minimum norm dimensions".into()); }
+    let mut aat = a.iter()
+      .map(|r| a.iter().map(|s| r.iter().zip(s).map(|(r, s)| r * s).sum::<f64>()).collect::<Vec<f64>>())
+      .collect::<Vec<Vec<f64>>>();
+    let scale = (0..a.len()).map(|i| aat[i][i]).fold(0.0, f64::max);
+    for (i, row) in aat.iter_mut().enumerate() { row[i] += lambda * scale; }
+    Ok(MinimumNorm { aat: Lu::new(aat)?, a })
+  }
+
+  pub fn solve(&self, b: &[f64]) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    if self.a.len() != b.len() { return Err("Inconsistent minimum norm dimensions".into()); }
+    let y = self.aat.solve(b)?;
+    let n = self.a.first().map(|row| row.len()).unwrap_or(0);
+    Ok((0..n).map(|j| self.a.iter().zip(&y).map(|(row, y)| row[j] * y).sum()).collect())
+  }
+}
+
+/// LU factorization with partial pivoting of a square matrix, given by rows, solving the
+/// linear system for several right hand sides
+#[derive(Debug)]
+pub struct Lu {
+  /// Unit lower triangular factor below the diagonal, upper triangular factor above
+  lu: Vec<Vec<f64>>,
+  /// Row of the matrix moved to each row of the factors
+  rows: Vec<usize>,
+}
+
+impl Lu {
+  pub fn new(mut a: Vec<Vec<f64>>) -> Result<Lu, Box<dyn std::error::Error>> {
+    let n = a.len();
+    if a.iter().any(|row| row.len() != n) { return Err("The linear system is not square".into()); }
+    let mut rows = (0..n).collect::<Vec<usize>>();
+    for k in 0..n {
+      let pivot = (k..n).max_by(|i, j| a[*i][k].abs().total_cmp(&a[*j][k].abs())).ok_or("Empty linear system")?;
+      if a[pivot][k].abs() < 1e-300 { return Err("The linear system is singular".into()); }
+      a.swap(k, pivot); rows.swap(k, pivot);
+      let (top, bottom) = a.split_at_mut(k + 1);
+      let row_k = &top[k];
+      for row in bottom.iter_mut() {
+        let f = row[k] / row_k[k];
+        row[k] = f;
+        if f == 0.0 { continue; }
+        for (r, p) in row[k + 1..].iter_mut().zip(&row_k[k + 1..]) { *r -= f * p; }
+      }
+    }
+    Ok(Lu { lu: a, rows })
+  }
+
+  pub fn solve(&self, b: &[f64]) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let n = self.rows.len();
+    if b.len() != n { return Err("The linear system is not square".into()); }
+    let mut x = self.rows.iter().map(|i| b[*i]).collect::<Vec<f64>>();
+    for k in 0..n {
+      let s = self.lu[k][..k].iter().zip(&x[..k]).map(|(l, x)| l * x).sum::<f64>();
+      x[k] -= s;
+    }
+    for k in (0..n).rev() {
+      let s = self.lu[k][k + 1..].iter().zip(&x[k + 1..]).map(|(u, x)| u * x).sum::<f64>();
+      x[k] = (x[k] - s) / self.lu[k][k];
+    }
+    Ok(x)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_solves_linear_systems() -> Result<(), Box<dyn std::error::Error>> {
+    let a = vec![vec![0.0, 2.0, 1.0], vec![1.0, 1.0, 0.0], vec![3.0, 0.0, 1.0]];
+    let x = solve(a.clone(), vec![7.0, 3.0, 6.0])?;
+    for (xi, e) in x.iter().zip([1.0, 2.0, 3.0]) { assert!((xi - e).abs() < 1e-12); }
+    // Overdetermined consistent system
+    let mut rows = a.clone(); rows.push(vec![1.0, 1.0, 1.0]);
+    let x = least_squares(&rows, &[7.0, 3.0, 6.0, 6.0], 0.0)?;
+    for (xi, e) in x.iter().zip([1.0, 2.0, 3.0]) { assert!((xi - e).abs() < 1e-10); }
+    // Underdetermined system: the closest point of the plane `x + y + z = 3` to the origin
+    let plane = MinimumNorm::new(vec![vec![1.0, 1.0, 1.0]], 0.0)?;
+    for xi in plane.solve(&[3.0])?.iter() { assert!((xi - 1.0).abs() < 1e-12); }
+    // Factorizations reused for several right hand sides
+    let lu = Lu::new(a.clone())?;
+    for (b, e) in [([7.0, 3.0, 6.0], [1.0, 2.0, 3.0]), ([0.0, 1.0, 3.0], [1.0, 0.0, 0.0])] {
+      for (xi, e) in lu.solve(&b)?.iter().zip(e) { assert!((xi - e).abs() < 1e-12); }
+    }
+    for xi in plane.solve(&[-6.0])?.iter() { assert!((xi + 2.0).abs() < 1e-12); }
+    Ok(())
+  }
+}
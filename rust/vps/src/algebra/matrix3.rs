@@ -0,0 +1,126 @@
+/*
+ * Matrix class
+ */
+use std::ops::{Add, Sub};
+use num::Float;
+
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::Vector3;
+
+/// `Matrix3` represents a 3x3 matrix stored row by row. It is primarily used to
+/// hold velocity gradient tensors, with `m[i][j]` being `du_i/dx_j`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Matrix3<T>
+{
+    pub m: [[T; 3]; 3],
+}
+
+impl<T> Default for Matrix3<T>
+where T: Float
+{
+    /// Defines a default `Matrix3` filled with zeros
+    fn default() -> Matrix3<T> {
+        Matrix3 { m: [[T::zero(); 3]; 3] }
+    }
+}
+
+impl<T> Matrix3<T>
+where T: Float
+{
+    pub fn new(m: [[T; 3]; 3]) -> Matrix3<T> {
+        Matrix3 { m }
+    }
+
+    pub fn identity() -> Matrix3<T> {
+        let mut r = Matrix3::default();
+        for i in 0..3 { r.m[i][i] = T::one(); }
+        r
+    }
+
+    /// Outer product `a b^T`
+    pub fn outer(a: &Vector3<T>, b: &Vector3<T>) -> Matrix3<T> {
+        let a = [a.x, a.y, a.z]; let b = [b.x, b.y, b.z];
+        let mut r = Matrix3::default();
+        for (i, ai) in a.iter().enumerate() {
+            for (j, bj) in b.iter().enumerate() {
+                r.m[i][j] = *ai * *bj;
+            }
+        }
+        r
+    }
+
+    /// Matrix `M` such that `M v = a x v`
+    pub fn cross_product(a: &Vector3<T>) -> Matrix3<T> {
+        let z = T::zero();
+        Matrix3::new([[ z,   -a.z,  a.y],
+                      [ a.z,  z,   -a.x],
+                      [-a.y,  a.x,  z  ]])
+    }
+
+    pub fn transpose(&self) -> Matrix3<T> {
+        let mut r = Matrix3::default();
+        for i in 0..3 { for j in 0..3 { r.m[i][j] = self.m[j][i]; } }
+        r
+    }
+
+    pub fn scale(&self, l: T) -> Matrix3<T> {
+        let mut r = self.clone();
+        for row in r.m.iter_mut() { for v in row.iter_mut() { *v = *v * l; } }
+        r
+    }
+
+    /// Matrix vector product `M v`
+    pub fn dot(&self, v: &Vector3<T>) -> Vector3<T> {
+        let row = |i: usize| self.m[i][0]*v.x + self.m[i][1]*v.y + self.m[i][2]*v.z;
+        Vector3::new(row(0), row(1), row(2))
+    }
+
+    /// Matrix product `M N`
+    pub fn product(&self, o: &Matrix3<T>) -> Matrix3<T> {
+        let mut r = Matrix3::default();
+        for i in 0..3 {
+            for j in 0..3 {
+                r.m[i][j] = self.m[i][0]*o.m[0][j] + self.m[i][1]*o.m[1][j] + self.m[i][2]*o.m[2][j];
+            }
+        }
+        r
+    }
+
+    pub fn trace(&self) -> T {
+        self.m[0][0] + self.m[1][1] + self.m[2][2]
+    }
+
+    /// Frobenius norm
+    pub fn norm(&self) -> T {
+        self.m.iter().flatten().fold(T::zero(), |r, v| r + *v * *v).sqrt()
+    }
+
+    /// Returns the curl associated with a gradient tensor, ie the vorticity when the
+    /// matrix is a velocity gradient
+    pub fn curl(&self) -> Vector3<T> {
+        Vector3::new(self.m[2][1] - self.m[1][2],
+                     self.m[0][2] - self.m[2][0],
+                     self.m[1][0] - self.m[0][1])
+    }
+}
+
+impl<T> Add for Matrix3<T>
+where T: Float
+{
+    type Output = Self;
+    fn add(mut self, other: Self) -> Self::Output {
+        for i in 0..3 { for j in 0..3 { self.m[i][j] = self.m[i][j] + other.m[i][j]; } }
+        self
+    }
+}
+
+impl<T> Sub for Matrix3<T>
+where T: Float
+{
+    type Output = Self;
+    fn sub(mut self, other: Self) -> Self::Output {
+        for i in 0..3 { for j in 0..3 { self.m[i][j] = self.m[i][j] - other.m[i][j]; } }
+        self
+    }
+}
@@ -1,3 +1,7 @@
+mod ewald;
 mod vorton_to_velocity;        pub use vorton_to_velocity::VortonToVelocity;
 mod vorton_to_velocity_simple; pub use vorton_to_velocity_simple::{VortonToVelocitySimple, VortonToVelocitySimpleBuilder};
-mod vorton_to_velocity_tree;   pub use vorton_to_velocity_tree::{VortonToVelocityTree, VortonToVelocityTreeBuilder};
+mod vorton_to_velocity_tree;   pub use vorton_to_velocity_tree::{VortonToVelocityTree, VortonToVelocityTreeBuilder, Octree, AccuracyReport, LevelStatistics};
+pub(crate) use vorton_to_velocity_tree::{DEFAULT_THETA, DEFAULT_REBUILD_THRESHOLD};
+mod vorton_to_velocity_fmm;    pub use vorton_to_velocity_fmm::{VortonToVelocityFmm, VortonToVelocityFmmBuilder};
+mod vorton_to_velocity_vic;    pub use vorton_to_velocity_vic::{VortonToVelocityVic, VortonToVelocityVicBuilder, GreenFunction};
@@ -0,0 +1,160 @@
+use num::complex::Complex;
+
+use crate::{parallel, Point3, Vorton, Kernel, Vector3, Matrix3, Periodicity};
+
+/// Relative accuracy targeted by the Ewald summation
+const ACCURACY: f64 = 1e-6;
+
+/// Ratio between the box length and the period used along non periodic axes
+const PADDING: f64 = 3.0;
+
+/// Ewald summation of the velocity induced by a set of vortons replicated periodically. The
+/// singular kernel `1/(4π|r|³)` is split into a long range part, the kernel of a gaussian
+/// vorton of core radius `1/(√2 β)`, and a short range part that vanishes beyond the cutoff
+/// distance. The short range part, evaluated with the smoothing kernel of the vortons, is
+/// summed over the nearest image of each vorton. The long range part is summed over all
+/// images in Fourier space using the structure factors `S(k) = Σ α e^{-ik·y}`.
+///
+/// Non periodic axes are treated as periodic with a period of `PADDING` times the length of
+/// the box, extended to contain the vortons, leaving empty space between the images
+/// (Yeh–Berkowitz).
+pub struct Ewald {
+  periodicity: Periodicity,
+  kernel: Kernel,
+  /// Core radius of the gaussian vorton representing the long range part
+  core_radius: f64,
+  cutoff: f64,
+  /// Displacements to the neighbouring images along the periodic axes
+  shifts: Vec<Vector3<f64>>,
+  /// Wave vectors, weight `e^{-k²/4β²} / (k² V)` and structure factor
+  waves: Vec<(Vector3<f64>, f64, [Complex<f64>; 3])>,
+  /// Mean velocity over the box, which is not captured by the wave vectors
+  mean_velocity: Vector3<f64>,
+}
+
+impl Ewald {
+  pub fn new(vortons: &[Vorton], periodicity: &Periodicity, kernel: Kernel) -> Result<Ewald, Box<dyn std::error::Error>> {
+    let lengths = periodicity.lengths();
+    if lengths.iter().any(|l| *l <= 0.0) { return Err("Periodic domain must have a positive size".into()); }
+    // Along non periodic axes, vortons may have left the box: the padded box extends from the
+    // lowest of the box and the vortons over `PADDING` times their combined length
+    let (min, max) = vortons.iter()
+      .fold((periodicity.min.clone(), periodicity.max.clone()), |(min, max), v| (min.min(v.position()), max.max(v.position())));
+    let (min, max) = ([min.x, min.y, min.z], [max.x, max.y, max.z]);
+    let box_min = [periodicity.min.x, periodicity.min.y, periodicity.min.z];
+    let start = [0, 1, 2].map(|a| if periodicity.periodic[a] { box_min[a] } else { min[a] });
+    let periods = [0, 1, 2].map(|a| if periodicity.periodic[a] { lengths[a] } else { PADDING * (max[a] - min[a]) });
+    let shifts = (0..27)
+      .map(|c| [c % 3, (c / 3) % 3, c / 9].map(|i| i as f64 - 1.0))
+      .filter(|n| (0..3).all(|a| n[a] == 0.0 || periodicity.periodic[a]))
+      .map(|n| Vector3::new(n[0] * periods[0], n[1] * periods[1], n[2] * periods[2]))
+      .collect();
+    let start = Point3::new(start[0], start[1], start[2]);
+    let padded = Periodicity { periodic: [true; 3], max: &start + &Vector3::new(periods[0], periods[1], periods[2]), min: start };
+
+    // The cutoff is such that the nearest image is the only image within the cutoff
+    let s = (-ACCURACY.ln()).sqrt();
+    let cutoff = 0.5 * periods.iter().cloned().fold(f64::INFINITY, f64::min);
+    let beta = s / cutoff;
+    let k_max = 2.0 * beta * s;
+    let n = periods.map(|l| (k_max * l / (2.0 * std::f64::consts::PI)).ceil() as i64);
+    let volume = periods[0] * periods[1] * periods[2];
+
+    let mut wave_vectors = Vec::new();
+    for i in -n[0]..=n[0] {
+      for j in -n[1]..=n[1] {
+        for k in -n[2]..=n[2] {
+          let k = Vector3::new(2.0 * std::f64::consts::PI * i as f64 / periods[0],
+                               2.0 * std::f64::consts::PI * j as f64 / periods[1],
+                               2.0 * std::f64::consts::PI * k as f64 / periods[2]);
+          let k2 = k.dot(&k);
+          if k2 == 0.0 || k2 > k_max * k_max { continue; }
+          wave_vectors.push((k, (-0.25 * k2 / (beta * beta)).exp() / (k2 * volume)));
+        }
+      }
+    }
+    // Along a periodic axis `a`, the velocity averaged over the periodic axes is the integral
+    // of the vorticity along the non periodic axes. Its mean over the box is then
+    // `1/V Σ ε_abc x_b α_c`, averaged over the non periodic axes `b`, so that the velocity
+    // vanishes far from the vortons.
+    let mean_velocity = vortons.iter().fold([0.0; 3], |mut u, v| {
+      let (x, alpha) = (v.position() - &padded.min, v.vorticity().scale(v.volume() / volume));
+      let (x, alpha) = ([x.x, x.y, x.z], [alpha.x, alpha.y, alpha.z]);
+      for a in (0..3).filter(|a| periodicity.periodic[*a]) {
+        let (b, c) = ((a + 1) % 3, (a + 2) % 3);
+        let n = [b, c].iter().filter(|b| !periodicity.periodic[**b]).count() as f64;
+        if !periodicity.periodic[b] { u[a] += x[b] * alpha[c] / n; }
+        if !periodicity.periodic[c] { u[a] -= x[c] * alpha[b] / n; }
+      }
+      u
+    });
+    let mean_velocity = Vector3::new(mean_velocity[0], mean_velocity[1], mean_velocity[2]);
+
+    let waves = parallel::map(&wave_vectors, |(k, w)| {
+      let s = vortons.iter().fold([Complex::new(0.0, 0.0); 3], |s, v| {
+        let phase = Complex::from_polar(1.0, -k.dot(&(v.position() - &Point3::origin())));
+        let alpha = v.vorticity().scale(v.volume());
+        [s[0] + phase * alpha.x, s[1] + phase * alpha.y, s[2] + phase * alpha.z]
+      });
+      (k.clone(), *w, s)
+    });
+    Ok(Ewald { periodicity: padded, kernel, core_radius: 1.0 / (std::f64::consts::SQRT_2 * beta), cutoff, shifts, waves, mean_velocity })
+  }
+
+  /// Returns the gaussian vorton of core radius `core_radius` with the same strength as `vorton`
+  fn long_range(&self, vorton: &Vorton) -> Vorton {
+    let volume = std::f64::consts::PI * self.core_radius.powi(3) / 6.0;
+    Vorton::new(vorton.position().clone(), vorton.vorticity().scale(vorton.volume() / volume), volume)
+  }
+
+  /// Returns the image of `position` nearest to `vorton`
+  pub fn nearest_image(&self, vorton: &Vorton, position: &Point3<f64>) -> Point3<f64> {
+    vorton.position() + &self.periodicity.minimum_image(&(position - vorton.position()))
+  }
+
+  /// Short range contribution of `vorton` to the velocity at `position`, which is zero
+  /// beyond the cutoff distance
+  pub fn short_range_velocity(&self, vorton: &Vorton, position: &Point3<f64>) -> Vector3<f64> {
+    if (position - vorton.position()).norm() > self.cutoff { return Vector3::default(); }
+    vorton.velocity_contribution(&self.kernel, position)
+      - self.long_range(vorton).velocity_contribution(&Kernel::Gaussian, position)
+  }
+
+  /// Short range contribution of `vorton` to the velocity gradient at `position`, which is
+  /// zero beyond the cutoff distance
+  pub fn short_range_velocity_gradient(&self, vorton: &Vorton, position: &Point3<f64>) -> Matrix3<f64> {
+    if (position - vorton.position()).norm() > self.cutoff { return Matrix3::default(); }
+    vorton.velocity_gradient_contribution(&self.kernel, position)
+      - self.long_range(vorton).velocity_gradient_contribution(&Kernel::Gaussian, position)
+  }
+
+  /// Long range velocity `u = Σ_k w_k Re(i e^{ik·x} k × S(k))` at `position`
+  pub fn long_range_velocity(&self, position: &Point3<f64>) -> Vector3<f64> {
+    let x = position - &Point3::origin();
+    self.waves.iter().fold(self.mean_velocity.clone(), |u, (k, w, s)| {
+      let phase = Complex::from_polar(*w, k.dot(&x)) * Complex::i();
+      u + Vector3::new((phase * (s[2] * k.y - s[1] * k.z)).re,
+                       (phase * (s[0] * k.z - s[2] * k.x)).re,
+                       (phase * (s[1] * k.x - s[0] * k.y)).re)
+    })
+  }
+
+  /// Long range velocity gradient `du_i/dx_j = Σ_k w_k Re(-e^{ik·x} (k × S(k))_i k_j)`
+  pub fn long_range_velocity_gradient(&self, position: &Point3<f64>) -> Matrix3<f64> {
+    let x = position - &Point3::origin();
+    self.waves.iter().fold(Matrix3::default(), |g, (k, w, s)| {
+      let phase = -Complex::from_polar(*w, k.dot(&x));
+      let c = [(phase * (s[2] * k.y - s[1] * k.z)).re,
+               (phase * (s[0] * k.z - s[2] * k.x)).re,
+               (phase * (s[1] * k.x - s[0] * k.y)).re];
+      g + Matrix3::new([0, 1, 2].map(|i| [c[i] * k.x, c[i] * k.y, c[i] * k.z]))
+    })
+  }
+
+  /// Returns the displacements to the neighbouring images of the box along the periodic
+  /// axes, including the box itself. The image of a position nearest to a vorton located in
+  /// the box is one of the position displaced by these shifts.
+  pub fn shifts(&self) -> &[Vector3<f64>] {
+    &self.shifts
+  }
+}
@@ -1,5 +1,20 @@
-use crate::{Point3, Vector3};
+use crate::{Point3, Vector3, Matrix3};
 
-pub trait VortonToVelocity {
+/// Evaluation of the velocity induced by a set of vortons. Implementations are `Sync` so that
+/// evaluations can be shared between threads.
+pub trait VortonToVelocity: Sync {
   fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>;
+
+  /// Returns the velocity gradient tensor at `position`, with entry `[i][j]` being `du_i/dx_j`.
+  /// The default implementation uses a central finite difference of `velocity_at`.
+  fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+    let h = 1e-4;
+    let mut r = Matrix3::default();
+    for (j, d) in [Vector3::x(), Vector3::y(), Vector3::z()].iter().enumerate() {
+      let d = d.scale(h);
+      let dv = self.velocity_at(&(position + &d))? - self.velocity_at(&(position - &d))?;
+      r.m[0][j] = dv.x / (2.0*h); r.m[1][j] = dv.y / (2.0*h); r.m[2][j] = dv.z / (2.0*h);
+    }
+    Ok(r)
+  }
 }
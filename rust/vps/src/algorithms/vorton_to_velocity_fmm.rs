@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use crate::{parallel, VortonToVelocity, Point3, Vorton, Kernel, Vector3, Matrix3};
+use super::vorton_to_velocity_tree::Grid;
+
+mod expansion; use expansion::{Expansion, Coefficients, Derivatives, Potential};
+
+/// Algorithm to calculate the velocity from a field of vortons using the Fast Multipole
+/// Method. The vortons are sorted in a uniform octree. The vector potential of the vortons
+/// in each cell is represented by a multipole expansion of order `order`, converted into
+/// local expansions about the center of well separated cells. The velocity at a position
+/// is the sum of the local expansion of the leaf cell containing it and of the direct
+/// contribution of the vortons located in the neighbouring leaf cells. The expansions use
+/// the singular kernel: the smoothing kernel only applies to the near field.
+#[derive(derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct VortonToVelocityFmm<'a> {
+  vortons: &'a [Vorton],
+  velocity: &'a Vector3<f64>,
+  /// Smoothing kernel of the vortons
+  #[builder(default)]
+  kernel: Kernel,
+  /// Order of the multipole and local expansions
+  order: usize,
+  /// Target number of vortons per leaf cell used to select the depth of the octree
+  #[builder(default = "32")]
+  max_leaf: usize,
+  #[builder(setter(skip))]
+  grids: Vec<Grid>,
+  #[builder(setter(skip))]
+  cells: Vec<HashMap<(usize, usize, usize), Cell>>,
+  #[builder(setter(skip))]
+  expansion: Option<Expansion>,
+}
+
+#[derive(Default)]
+struct Cell {
+  vortons: Vec<usize>,
+  multipole: Coefficients,
+  local: Option<Coefficients>,
+}
+
+impl<'a> VortonToVelocity for VortonToVelocityFmm<'a> {
+  fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
+    let (potential, near) = self.evaluate(position, false, &|v: &Vorton| v.velocity_contribution(&self.kernel, position))?;
+    Ok(self.velocity + potential.velocity() + near)
+  }
+
+  fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+    let (potential, near) = self.evaluate(position, true, &|v: &Vorton| v.velocity_gradient_contribution(&self.kernel, position))?;
+    Ok(potential.velocity_gradient() + near)
+  }
+}
+
+impl<'a> VortonToVelocityFmm<'a> {
+  pub fn initialize(mut self) -> Result<VortonToVelocityFmm<'a>, Box<dyn std::error::Error>> {
+    if self.order < 2 { return Err("FMM expansion order must be at least 2".into()); }
+    self.expansion = Some(Expansion::new(self.order));
+    self.make_grids()?; self.make_multipoles()?; self.make_locals()?;
+    Ok(self)
+  }
+
+  /// Sum the contributions to `position` of the far field, returned as the derivatives of
+  /// the vector potential, and of the near field vortons, accumulated using `f`.
+  fn evaluate<R, F>(&self, position: &Point3<f64>, gradient: bool, f: &F) -> Result<(Potential, R), Box<dyn std::error::Error>>
+  where R: std::ops::Add<Output = R> + Default,
+        F: Fn(&Vorton) -> R
+  {
+    let expansion = self.expansion.as_ref().ok_or("FMM not initialised")?;
+    let leaf = self.grids.len() - 1;
+    let mut potential = Potential::default();
+    if let Some((i, j, k)) = self.grids[leaf].cell_ijk(position) {
+      if let Some(local) = self.cells[leaf].get(&(i, j, k)).and_then(|c| c.local.as_ref()) {
+        expansion.l2p(local, &(position - &self.grids[leaf].cell_center(i, j, k)), gradient, &mut potential);
+        let near = self.neighbours(leaf, (i, j, k))
+          .flat_map(|(_, cell)| cell.vortons.iter())
+          .fold(R::default(), |r, n| r + f(&self.vortons[*n]));
+        return Ok((potential, near));
+      }
+    }
+    // Positions without a local expansion, ie outside of the octree or in an empty leaf cell,
+    // are evaluated by traversing the tree and using multipole expansions directly.
+    let near = self.traverse(expansion, position, 0, (0, 0, 0), gradient, &mut potential, f);
+    Ok((potential, near))
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn traverse<R, F>(&self, expansion: &Expansion,
+                    position: &Point3<f64>,
+                    level: usize, (i, j, k): (usize, usize, usize),
+                    gradient: bool, potential: &mut Potential,
+                    f: &F) -> R
+  where R: std::ops::Add<Output = R> + Default,
+        F: Fn(&Vorton) -> R
+  {
+    let Some(cell) = self.cells[level].get(&(i, j, k)) else { return R::default() };
+    let grid = &self.grids[level];
+    let r = position - &grid.cell_center(i, j, k);
+    if r.x.abs().max(r.y.abs()).max(r.z.abs()) > 1.5 * grid.cell_length() {
+      expansion.m2p(&cell.multipole, &r, gradient, potential);
+      R::default()
+    } else if level + 1 == self.grids.len() {
+      cell.vortons.iter().fold(R::default(), |r, n| r + f(&self.vortons[*n]))
+    } else {
+      let mut r = R::default();
+      for c in 0..8 {
+        r = r + self.traverse(expansion, position, level + 1, (2*i + (c & 1), 2*j + ((c >> 1) & 1), 2*k + ((c >> 2) & 1)), gradient, potential, f);
+      }
+      r
+    }
+  }
+
+  /// Returns the non-empty cells adjacent to cell `i, j, k`, including itself
+  fn neighbours(&self, level: usize, (i, j, k): (usize, usize, usize)) -> impl Iterator<Item = ((usize, usize, usize), &Cell)> + '_ {
+    (0..27)
+      .filter_map(move |c| Some(((i + c % 3).checked_sub(1)?, (j + (c / 3) % 3).checked_sub(1)?, (k + c / 9).checked_sub(1)?)))
+      .filter_map(move |ijk| self.cells[level].get(&ijk).map(|cell| (ijk, cell)))
+  }
+
+  fn make_grids(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    use std::convert::TryInto;
+    // The leaf level is at least 2 so that the interaction lists are not empty
+    let n_leaves = self.vortons.len() as f64 / self.max_leaf.max(1) as f64;
+    let n_grids = (n_leaves.log(8.0).ceil() as usize).max(2) + 1;
+    self.grids.push(self.vortons.try_into()?);
+    while self.grids.len() < n_grids { self.grids.push(self.grids.last().ok_or("Grid unavailable")?.into()); }
+    Ok(())
+  }
+
+  /// Sort the vortons in the leaf cells, calculate their multipole expansions and
+  /// aggregate them up the tree
+  fn make_multipoles(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    let expansion = self.expansion.as_ref().ok_or("FMM not initialised")?;
+    let leaf = self.grids.len() - 1;
+    let mut cells = (0..self.grids.len()).map(|_| HashMap::<(usize, usize, usize), Cell>::new()).collect::<Vec<_>>();
+    for (n, v) in self.vortons.iter().enumerate() {
+      let ijk = self.grids[leaf].cell_ijk(v.position()).ok_or("Vorton outside of grid")?;
+      cells[leaf].entry(ijk).or_default().vortons.push(n);
+    }
+    let leaves = cells[leaf].iter().collect::<Vec<_>>();
+    let multipoles = parallel::map(&leaves, |((i, j, k), cell)|
+      expansion.p2m(&self.grids[leaf].cell_center(*i, *j, *k), cell.vortons.iter().map(|n| &self.vortons[*n])));
+    let keys = leaves.into_iter().map(|(ijk, _)| *ijk).collect::<Vec<_>>();
+    for (ijk, multipole) in keys.into_iter().zip(multipoles) {
+      if let Some(cell) = cells[leaf].get_mut(&ijk) { cell.multipole = multipole; }
+    }
+    for level in (0..leaf).rev() {
+      let (parents, children) = cells.split_at_mut(level + 1);
+      // Children are aggregated in a fixed order so that the results are reproducible
+      let mut children = children[0].iter().collect::<Vec<_>>();
+      children.sort_by_key(|(key, _)| **key);
+      for ((i, j, k), child) in children {
+        let parent = (i / 2, j / 2, k / 2);
+        let shift = &self.grids[level + 1].cell_center(*i, *j, *k) - &self.grids[level].cell_center(parent.0, parent.1, parent.2);
+        let cell = parents[level].entry(parent).or_insert_with(|| Cell { multipole: expansion.zeros(), ..Cell::default() });
+        expansion.m2m(&child.multipole, &shift, &mut cell.multipole);
+      }
+    }
+    self.cells = cells;
+    Ok(())
+  }
+
+  /// Calculate the local expansions, from level 2 down to the leaves, by translating the
+  /// local expansion of the parent cell and converting the multipole expansions of the cells
+  /// in the interaction list, ie the children of the neighbours of the parent cell that are
+  /// not adjacent to the cell.
+  fn make_locals(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    let expansion = self.expansion.as_ref().ok_or("FMM not initialised")?;
+    for level in 2..self.grids.len() {
+      let grid = &self.grids[level];
+      let derivatives = (0..343)
+        .map(|c| (c % 7 - 3, (c / 7) % 7 - 3, c / 49 - 3))
+        .filter(|(a, b, c): &(i64, i64, i64)| a.abs().max(b.abs()).max(c.abs()) > 1)
+        .map(|(a, b, c)| ((a, b, c), Derivatives::new(&Vector3::new(a as f64, b as f64, c as f64).scale(grid.cell_length()), expansion.m2l_order())))
+        .collect::<HashMap<(i64, i64, i64), Derivatives>>();
+
+      let keys = self.cells[level].keys().cloned().collect::<Vec<_>>();
+      let locals = parallel::map(&keys, |&(i, j, k)| {
+          let mut local = expansion.zeros();
+          let (pi, pj, pk) = (i / 2, j / 2, k / 2);
+          if let Some(parent) = self.cells[level - 1].get(&(pi, pj, pk)).and_then(|c| c.local.as_ref()) {
+            let shift = &grid.cell_center(i, j, k) - &self.grids[level - 1].cell_center(pi, pj, pk);
+            expansion.l2l(parent, &shift, &mut local);
+          }
+          for ((ni, nj, nk), _) in self.neighbours(level - 1, (pi, pj, pk)) {
+            for c in 0..8 {
+              let source = (2*ni + (c & 1), 2*nj + ((c >> 1) & 1), 2*nk + ((c >> 2) & 1));
+              let offset = (i as i64 - source.0 as i64, j as i64 - source.1 as i64, k as i64 - source.2 as i64);
+              if let (Some(b), Some(cell)) = (derivatives.get(&offset), self.cells[level].get(&source)) {
+                expansion.m2l(&cell.multipole, b, &mut local);
+              }
+            }
+          }
+          local
+        });
+
+      for (ijk, local) in keys.into_iter().zip(locals) {
+        if let Some(cell) = self.cells[level].get_mut(&ijk) { cell.local = Some(local); }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::VortonToVelocitySimpleBuilder;
+  use crate::random::random;
+
+  fn random_vortons(n: usize, seed: &mut u64) -> Vec<Vorton> {
+    (0..n)
+      .map(|_| Vorton::new(Point3::new(random(seed), random(seed), 0.5 * random(seed)),
+                           Vector3::new(random(seed) - 0.5, random(seed) - 0.5, random(seed) - 0.5),
+                           1e-6))
+      .collect()
+  }
+
+  #[test]
+  fn it_matches_simple() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed = 7;
+    let vortons = random_vortons(1000, &mut seed);
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    // Vorton positions and positions outside of the vorton cloud
+    let positions = vortons.iter().map(|v| v.position().clone())
+      .chain((0..20).map(|_| Point3::new(2.0 * random(&mut seed) - 0.5, random(&mut seed), 0.6 + random(&mut seed))))
+      .collect::<Vec<Point3<f64>>>();
+    let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+    let expected = positions.iter()
+      .map(|p| Ok((simple.velocity_at(p)?, simple.velocity_gradient_at(p)?)))
+      .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+    // Relative RMS error on the velocity and velocity gradient for increasing orders
+    let mut errors = Vec::new();
+    for order in [2, 4, 6] {
+      let fmm = VortonToVelocityFmmBuilder::default().vortons(&vortons).velocity(&velocity).order(order).max_leaf(8).build()?.initialize()?;
+      let (mut e, mut n, mut eg, mut ng) = (0.0, 0.0, 0.0, 0.0);
+      for (p, (u, g)) in positions.iter().zip(expected.iter()) {
+        e  += (fmm.velocity_at(p)? - u.clone()).norm().powi(2);           n  += u.norm().powi(2);
+        eg += (fmm.velocity_gradient_at(p)? - g.clone()).norm().powi(2);  ng += g.norm().powi(2);
+      }
+      errors.push(((e / n).sqrt(), (eg / ng).sqrt()));
+    }
+    println!("Errors: {errors:?}");
+    assert!(errors.windows(2).all(|e| e[1].0 < e[0].0 && e[1].1 < e[0].1));
+    assert!(errors[2].0 < 1e-3 && errors[2].1 < 1e-3);
+    Ok(())
+  }
+
+  /// A single vorton, or coincident vortons, give a grid which is the size of their core
+  #[test]
+  fn it_handles_coincident_vortons() -> Result<(), Box<dyn std::error::Error>> {
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    let position = Point3::new(0.3, 0.2, 0.1);
+    let p = Point3::new(0.5, 0.2, 0.1);
+    for n in [1, 3] {
+      let vortons = vec![Vorton::new(position.clone(), Vector3::new(0.0, 0.0, 1.0), 1e-3); n];
+      let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+      let fmm = VortonToVelocityFmmBuilder::default().vortons(&vortons).velocity(&velocity).order(4).max_leaf(8).build()?.initialize()?;
+      let (expected, u) = (simple.velocity_at(&p)?, fmm.velocity_at(&p)?);
+      assert!(expected.norm() > 0.0 && (u - expected.clone()).norm() < 1e-6 * expected.norm());
+    }
+    Ok(())
+  }
+}
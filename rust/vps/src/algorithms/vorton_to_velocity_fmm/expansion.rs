@@ -0,0 +1,280 @@
+use crate::{Vorton, Point3, Vector3, Matrix3};
+
+/// Cartesian Taylor expansions of the Laplace kernel `1/|r|` used by the fast multipole
+/// method. The vector potential `ψ(x) = 1/4π Σ_q α_q / |x - y_q|`, with `α_q = ω_q V_q`,
+/// is expanded as:
+///  - a multipole expansion about the center `c` of a cell containing the sources,
+///    `ψ(x) = 1/4π Σ_k M_k b_k(x - c)` with `M_k = Σ_q α_q (c - y_q)^k`;
+///  - a local expansion about the center `c` of a target cell,
+///    `ψ(x) = 1/4π Σ_k L_k (x - c)^k`;
+///
+/// where `k = (k_x, k_y, k_z)` is a multi-index with `|k| = k_x + k_y + k_z <= order` and
+/// `b_k(r) = ∂^k (1/|r|) / k!`. Coefficients are stored as `[f64; 3]` holding the three
+/// components of the vector potential.
+pub struct Expansion {
+  order: usize,
+  indices: Vec<(usize, usize, usize)>,
+  lookup: Vec<usize>,
+  binomials: Vec<Vec<f64>>,
+  /// Position in `Derivatives` of order `2 order` and weight of the terms of the M2L
+  /// translation, for each coefficient of the local expansion
+  m2l_terms: Vec<Vec<(usize, f64)>>,
+}
+
+pub type Coefficients = Vec<[f64; 3]>;
+
+impl Expansion {
+  pub fn new(order: usize) -> Expansion {
+    let dim = order + 1;
+    let mut indices = Vec::new();
+    let mut lookup = vec![usize::MAX; dim * dim * dim];
+    for n in 0..=order {
+      for i in (0..=n).rev() {
+        for j in (0..=(n - i)).rev() {
+          let k = n - i - j;
+          lookup[(i * dim + j) * dim + k] = indices.len();
+          indices.push((i, j, k));
+        }
+      }
+    }
+    let binomials = (0..=(2 * order + 2))
+      .map(|n| (0..=n).scan(1.0, |c, k| { let r = *c; *c = *c * (n - k) as f64 / (k + 1) as f64; Some(r) }).collect())
+      .collect();
+    let mut expansion = Expansion { order, indices, lookup, binomials, m2l_terms: Vec::new() };
+    let dim = 2 * order + 1;
+    expansion.m2l_terms = expansion.indices.iter()
+      .map(|&n| expansion.indices.iter()
+        .map(|&k| {
+          let s = (n.0 + k.0, n.1 + k.1, n.2 + k.2);
+          ((s.0 * dim + s.1) * dim + s.2, expansion.binomial(s, n))
+        })
+        .collect())
+      .collect();
+    expansion
+  }
+
+  /// Returns an expansion with all coefficients set to zero
+  pub fn zeros(&self) -> Coefficients {
+    vec![[0.0; 3]; self.indices.len()]
+  }
+
+  /// Returns the maximum order of the derivatives `b_k` required by `m2l`
+  pub fn m2l_order(&self) -> usize {
+    2 * self.order
+  }
+
+  fn position(&self, (i, j, k): (usize, usize, usize)) -> usize {
+    let dim = self.order + 1;
+    self.lookup[(i * dim + j) * dim + k]
+  }
+
+  /// Product of binomial coefficients `C(n, k) = Π_a C(n_a, k_a)`
+  fn binomial(&self, n: (usize, usize, usize), k: (usize, usize, usize)) -> f64 {
+    self.binomials[n.0][k.0] * self.binomials[n.1][k.1] * self.binomials[n.2][k.2]
+  }
+
+  /// Multipole expansion about `center` of a set of vortons
+  pub fn p2m<'a, I: Iterator<Item = &'a Vorton>>(&self, center: &Point3<f64>, vortons: I) -> Coefficients {
+    let mut multipole = self.zeros();
+    for vorton in vortons {
+      let d = center - vorton.position();
+      let p = Powers::new(&d, self.order);
+      let alpha = vorton.vorticity().scale(vorton.volume());
+      for (m, &n) in multipole.iter_mut().zip(self.indices.iter()) {
+        add(m, p.get(n), &[alpha.x, alpha.y, alpha.z]);
+      }
+    }
+    multipole
+  }
+
+  /// Translate a multipole expansion by `shift`, the vector from the new center to the
+  /// original center, and add it to `target`
+  pub fn m2m(&self, multipole: &[[f64; 3]], shift: &Vector3<f64>, target: &mut Coefficients) {
+    let p = Powers::new(&shift.scale(-1.0), self.order);
+    for (t, &n) in target.iter_mut().zip(self.indices.iter()) {
+      for i in 0..=n.0 {
+        for j in 0..=n.1 {
+          for k in 0..=n.2 {
+            let w = self.binomial(n, (i, j, k)) * p.get((n.0 - i, n.1 - j, n.2 - k));
+            add(t, w, &multipole[self.position((i, j, k))]);
+          }
+        }
+      }
+    }
+  }
+
+  /// Convert a multipole expansion into a local expansion and add it to `target`. `b`
+  /// holds the derivatives evaluated at the vector from the multipole center to the local
+  /// center, up to order `m2l_order`.
+  pub fn m2l(&self, multipole: &[[f64; 3]], b: &Derivatives, target: &mut Coefficients) {
+    for (t, terms) in target.iter_mut().zip(self.m2l_terms.iter()) {
+      for (m, (index, w)) in multipole.iter().zip(terms.iter()) {
+        add(t, w * b.values[*index], m);
+      }
+    }
+  }
+
+  /// Translate a local expansion by `shift`, the vector from the original center to the new
+  /// center, and add it to `target`
+  pub fn l2l(&self, local: &[[f64; 3]], shift: &Vector3<f64>, target: &mut Coefficients) {
+    let p = Powers::new(shift, self.order);
+    for (l, &n) in local.iter().zip(self.indices.iter()) {
+      for i in 0..=n.0 {
+        for j in 0..=n.1 {
+          for k in 0..=n.2 {
+            let w = self.binomial(n, (i, j, k)) * p.get((n.0 - i, n.1 - j, n.2 - k));
+            add(&mut target[self.position((i, j, k))], w, l);
+          }
+        }
+      }
+    }
+  }
+
+  /// Evaluate a local expansion at `h`, the vector from the expansion center
+  pub fn l2p(&self, local: &[[f64; 3]], h: &Vector3<f64>, gradient: bool, potential: &mut Potential) {
+    let p = Powers::new(h, self.order);
+    for (l, &n) in local.iter().zip(self.indices.iter()) {
+      for a in 0..3 {
+        let Some(na) = n.minus(a) else { continue };
+        let w = n.get(a) as f64 * p.get(na);
+        add(&mut potential.first[a], w, l);
+        if gradient {
+          for e in 0..3 {
+            let Some(nae) = na.minus(e) else { continue };
+            let w = (n.get(a) * na.get(e)) as f64 * p.get(nae);
+            add(&mut potential.second[a][e], w, l);
+          }
+        }
+      }
+    }
+  }
+
+  /// Evaluate a multipole expansion at `r`, the vector from the expansion center
+  pub fn m2p(&self, multipole: &[[f64; 3]], r: &Vector3<f64>, gradient: bool, potential: &mut Potential) {
+    let b = Derivatives::new(r, self.order + if gradient { 2 } else { 1 });
+    for (m, &k) in multipole.iter().zip(self.indices.iter()) {
+      for a in 0..3 {
+        let ka = k.plus(a);
+        let w = ka.get(a) as f64 * b.get(ka);
+        add(&mut potential.first[a], w, m);
+        if gradient {
+          for e in 0..3 {
+            let kae = ka.plus(e);
+            let w = (ka.get(a) * kae.get(e)) as f64 * b.get(kae);
+            add(&mut potential.second[a][e], w, m);
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Add `w m` to `target`
+fn add(target: &mut [f64; 3], w: f64, m: &[f64; 3]) {
+  target[0] += w * m[0]; target[1] += w * m[1]; target[2] += w * m[2];
+}
+
+/// Multi-index arithmetic
+trait MultiIndex: Sized {
+  fn get(&self, a: usize) -> usize;
+  fn plus(&self, a: usize) -> Self;
+  fn minus(&self, a: usize) -> Option<Self>;
+}
+
+impl MultiIndex for (usize, usize, usize) {
+  fn get(&self, a: usize) -> usize {
+    match a { 0 => self.0, 1 => self.1, _ => self.2 }
+  }
+
+  fn plus(&self, a: usize) -> Self {
+    match a { 0 => (self.0 + 1, self.1, self.2), 1 => (self.0, self.1 + 1, self.2), _ => (self.0, self.1, self.2 + 1) }
+  }
+
+  fn minus(&self, a: usize) -> Option<Self> {
+    match a {
+      0 => self.0.checked_sub(1).map(|i| (i, self.1, self.2)),
+      1 => self.1.checked_sub(1).map(|j| (self.0, j, self.2)),
+      _ => self.2.checked_sub(1).map(|k| (self.0, self.1, k)),
+    }
+  }
+}
+
+/// Powers `v^k = v_x^k_x v_y^k_y v_z^k_z` of a vector
+struct Powers {
+  values: [Vec<f64>; 3],
+}
+
+impl Powers {
+  fn new(v: &Vector3<f64>, order: usize) -> Powers {
+    let powers = |x: f64| (0..=order).scan(1.0, |p, _| { let r = *p; *p *= x; Some(r) }).collect::<Vec<f64>>();
+    Powers { values: [powers(v.x), powers(v.y), powers(v.z)] }
+  }
+
+  fn get(&self, (i, j, k): (usize, usize, usize)) -> f64 {
+    self.values[0][i] * self.values[1][j] * self.values[2][k]
+  }
+}
+
+/// Taylor coefficients `b_k(r) = ∂^k (1/|r|) / k!` for `|k| <= order` calculated with
+/// the recurrence relation
+/// `|k| r² b_k = -(2|k| - 1) Σ_a r_a b_{k - e_a} - (|k| - 1) Σ_a b_{k - 2e_a}`
+pub struct Derivatives {
+  dim: usize,
+  values: Vec<f64>,
+}
+
+impl Derivatives {
+  pub fn new(r: &Vector3<f64>, order: usize) -> Derivatives {
+    let dim = order + 1;
+    let mut b = Derivatives { dim, values: vec![0.0; dim * dim * dim] };
+    let r2 = r.dot(r);
+    let r = [r.x, r.y, r.z];
+    b.values[0] = 1.0 / r2.sqrt();
+    for n in 1..=order {
+      for i in 0..=n {
+        for j in 0..=(n - i) {
+          let k = (i, j, n - i - j);
+          let s = (0..3).fold(0.0, |s, a| {
+            let s = match k.minus(a) { Some(ka) => s + (2 * n - 1) as f64 * r[a] * b.get(ka), None => s };
+            match k.minus(a).and_then(|ka| ka.minus(a)) { Some(kaa) => s + (n - 1) as f64 * b.get(kaa), None => s }
+          });
+          let index = b.index(k);
+          b.values[index] = -s / (n as f64 * r2);
+        }
+      }
+    }
+    b
+  }
+
+  fn index(&self, (i, j, k): (usize, usize, usize)) -> usize {
+    (i * self.dim + j) * self.dim + k
+  }
+
+  pub fn get(&self, k: (usize, usize, usize)) -> f64 {
+    self.values[self.index(k)]
+  }
+}
+
+/// First and second derivatives of the vector potential, `first[a][c] = 4π ∂ψ_c/∂x_a` and
+/// `second[a][e][c] = 4π ∂²ψ_c/∂x_a∂x_e`, accumulated from expansions
+#[derive(Default)]
+pub struct Potential {
+  first: [[f64; 3]; 3],
+  second: [[[f64; 3]; 3]; 3],
+}
+
+impl Potential {
+  /// Returns the velocity `u = ∇ × ψ`
+  pub fn velocity(&self) -> Vector3<f64> {
+    let d = &self.first;
+    Vector3::new(d[1][2] - d[2][1], d[2][0] - d[0][2], d[0][1] - d[1][0])
+      .scale(0.25 / std::f64::consts::PI)
+  }
+
+  /// Returns the velocity gradient `du_i/dx_j`
+  pub fn velocity_gradient(&self) -> Matrix3<f64> {
+    let h = &self.second;
+    let curl = |a: usize, b: usize| [0, 1, 2].map(|j| h[a][j][b] - h[b][j][a]);
+    Matrix3::new([curl(1, 2), curl(2, 0), curl(0, 1)]).scale(0.25 / std::f64::consts::PI)
+  }
+}
@@ -1,31 +1,64 @@
-use crate::{VortonToVelocity, Point3, SuperVorton, Vorton, Vector3};
+use std::ops::Range;
+use std::sync::Arc;
+
+use crate::{parallel, VortonToVelocity, VortonToVelocitySimpleBuilder, Point3, SuperVorton, Vorton, VortonArrays, Kernel, Periodicity, Vector3, Matrix3};
+use super::ewald::Ewald;
 
 mod grid; pub use grid::Grid;
+mod octree; pub use octree::Octree; use octree::{Info, Node};
+mod accuracy; pub use accuracy::{AccuracyReport, LevelStatistics}; use accuracy::sample;
 
 /// Algorithm to calculate veloctiy from a field of vorton
+///
+/// The vortons are sorted in an octree whose cells are subdivided while they contain more
+/// than `max_leaf` vortons, up to `max_depth` levels. The vortons of a cell are represented
+/// by a super vorton when the cell is seen from the position under an angle less than the
+/// Barnes–Hut parameter `theta`, ie when `cell length < theta * distance`. The super vorton
+/// contributes through its dipole and quadrupole moments as well, except for the short
+/// range part of the Ewald summation in a periodic domain.
+///
+/// The octree of a previous evaluation can be provided to be refitted to the vortons, which
+/// avoids rebuilding the octree at every time step.
+///
+/// The vortons are copied into a structure of arrays in the order of the leaves of the
+/// octree, so that the vortons of a leaf are summed in a batch.
 #[derive(derive_builder::Builder)]
 #[builder(pattern = "owned")]
 pub struct VortonToVelocityTree<'a> {
-  vortons: &'a Vec<Vorton>,
+  vortons: &'a [Vorton],
   velocity: &'a Vector3<f64>,
-  n_grids: usize,
+  /// Smoothing kernel of the vortons
+  #[builder(default)]
+  kernel: Kernel,
+  /// Opening angle below which a cell is represented by its super vorton
+  #[builder(default = "DEFAULT_THETA")]
+  theta: f64,
+  /// Maximum number of vortons in a leaf cell. With 0, all leaves are at `max_depth`.
+  #[builder(default)]
+  max_leaf: usize,
+  /// Maximum number of levels of the tree
+  max_depth: usize,
+  /// Relative growth of the cells beyond which the octree is rebuilt rather than refitted
+  #[builder(default = "DEFAULT_REBUILD_THRESHOLD")]
+  rebuild_threshold: f64,
+  /// Octree of a previous evaluation, refitted to the vortons when possible
+  #[builder(default)]
+  octree: Option<Arc<Octree>>,
+  /// Periodic boundary conditions, if any
+  #[builder(default)]
+  periodicity: Option<Periodicity>,
   #[builder(setter(skip))]
-  grids: Vec<Grid>,
+  ewald: Option<Ewald>,
+  /// Vortons as a structure of arrays, in the order of the octree
   #[builder(setter(skip))]
-  infos: Vec<std::collections::HashMap<(usize, usize, usize), Info<'a>>>,
+  arrays: VortonArrays,
 }
 
-#[derive(Debug, Clone)]
-enum Info<'a> {
-  SuperVorton ( SuperVorton ),
-  Vortons ( Vec<&'a Vorton> ),
-}
+/// Default opening angle
+pub(crate) const DEFAULT_THETA: f64 = 0.84;
 
-impl From<&Vec<&Vorton>> for Info<'static> {
-  fn from(vortons: &Vec<&Vorton>) -> Info<'static> {
-    Info::SuperVorton ( vortons.iter().fold(SuperVorton::default(), |a, v| a + *v) )
-  }
-}
+/// Default relative growth of the cells beyond which the octree is rebuilt
+pub(crate) const DEFAULT_REBUILD_THRESHOLD: f64 = 0.5;
 
 /*
 impl<'a> Info<'a> {
@@ -57,127 +90,315 @@ impl<'a> Info<'a> {
 
 impl<'a> VortonToVelocity for VortonToVelocityTree<'a> {
   fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
-    if self.grids.len() != self.infos.len() { return Err("Not the same length...".into()); }
-    let r = self.velocity + self.traverse(position, 0, (0, 0, 0))?;
-    Ok(r)
+    Ok(self.velocity + self.induced_velocity_at(position)?)
+  }
+
+  fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+    if let Some(ewald) = &self.ewald {
+      return ewald.shifts().iter().try_fold(ewald.long_range_velocity_gradient(position), |r, shift| {
+        let image = position + shift;
+        Ok(r + self.traverse(&image, 0, (0, 0, 0),
+                             &|range: Range<usize>| self.sum_vortons(range, |v| ewald.short_range_velocity_gradient(v, &image)),
+                             &|s: &SuperVorton| ewald.short_range_velocity_gradient(s.vorton(), &image))?)
+      });
+    }
+    self.traverse(position, 0, (0, 0, 0),
+                  &|range: Range<usize>| self.arrays.velocity_gradient_at(&self.kernel, range, position),
+                  &|s: &SuperVorton| s.velocity_gradient_contribution(&self.kernel, position))
   }
 }
 
 impl<'a> VortonToVelocityTree<'a> {
   pub fn initialize(mut self) -> Result<VortonToVelocityTree<'a>, Box<dyn std::error::Error>> {
-    self.make_grids()?; self.make_infos()?; Ok(self)
+    let mut octree = self.octree.take().filter(|octree| octree.is_built_with(self.max_leaf, self.max_depth));
+    let refitted = octree.as_mut().is_some_and(|octree| Arc::make_mut(octree).refit(self.vortons, self.rebuild_threshold));
+    if !refitted { octree = Some(Arc::new(Octree::new(self.vortons, self.max_leaf, self.max_depth)?)); }
+    self.arrays = VortonArrays::from_indices(self.vortons, octree.as_ref().ok_or("Octree unavailable")?.order());
+    self.octree = octree;
+    if let Some(periodicity) = &self.periodicity {
+      self.ewald = Some(Ewald::new(self.vortons, periodicity, self.kernel)?);
+    }
+    Ok(self)
+  }
+
+  /// Returns the octree, to be refitted by a later evaluation
+  pub fn octree(&self) -> Option<Arc<Octree>> {
+    self.octree.clone()
   }
 
-  fn traverse(&self, 
+  /// Traverse the tree and accumulate the contribution `f` of the ranges of vortons, in the
+  /// order of the octree, and `s` of the super vortons that are relevant to `position`.
+  fn traverse<R, F, S>(&self, 
               position: &Point3<f64>, 
-              level: usize, (i,j,k): (usize, usize, usize)
-              ) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
-    let grid = &self.grids[level];
-    let info = &self.infos[level];
+              level: usize, (i,j,k): (usize, usize, usize),
+              f: &F,
+              s: &S,
+              ) -> Result<R, Box<dyn std::error::Error>> 
+  where R: std::ops::Add<Output = R> + Default,
+        F: Fn(Range<usize>) -> R,
+        S: Fn(&SuperVorton) -> R
+  {
+    let octree = self.octree.as_ref().ok_or("The tree is not initialised")?;
     // println!("Traverse level {level} cell {i},{j},{k}");
-    match info.get(&(i,j,k)) {
-      Some(Info::SuperVorton ( super_vorton )) if super_vorton.n_vortons() == 1 => {
-        Ok(super_vorton.vorton().velocity_contribution(position))
+    let Some(node) = octree.node(level, &(i,j,k)) else { return Ok(R::default()) };
+    match &node.info {
+      // The super vorton of a single vorton has no moment and matches the vorton
+      Info::SuperVorton ( super_vorton ) if super_vorton.n_vortons() == 1 => {
+        Ok(s(super_vorton))
       },
 
-      Some(Info::SuperVorton ( super_vorton )) => {
-        let calculate = true;
-        let calculate = calculate
-             && (position - grid.cell_center(i, j, k)).norm() > 0.7*1.7*grid.cell_length() // #1 position is within the cell
-             && (! super_vorton.vorton().is_inside(position));                             // #2 position is not within the vorton direct influence
-
-        let v = position - super_vorton.vorton().position();
-        let vc = Vorton::new(super_vorton.vorton().position().clone(),                      // #3 Maximum possible contribution to velocity
-                            v.orthogonal().normalize().scale(super_vorton.max_vorticity()), //
-                            super_vorton.vorton().volume())                                 //
-                 .velocity_contribution(position).norm();                                   //
-        let calculate = calculate && (vc < 0.05);                                           // less than 0.05 m/s
-
-        if calculate {
-          Ok(super_vorton.vorton().velocity_contribution(position))
+      Info::SuperVorton ( super_vorton ) => {
+        if self.is_accepted(node, super_vorton, position) {
+          Ok(s(super_vorton))
 
         } else {
-          Ok(
-            self.traverse(position, level+1, (2*i,   2*j,   2*k))?
-          + self.traverse(position, level+1, (2*i+1, 2*j+0, 2*k+0))?
-          + self.traverse(position, level+1, (2*i+0, 2*j+1, 2*k+0))?
-          + self.traverse(position, level+1, (2*i+1, 2*j+1, 2*k+0))?
-          + self.traverse(position, level+1, (2*i+0, 2*j+0, 2*k+1))?
-          + self.traverse(position, level+1, (2*i+1, 2*j+0, 2*k+1))?
-          + self.traverse(position, level+1, (2*i+0, 2*j+1, 2*k+1))?
-          + self.traverse(position, level+1, (2*i+1, 2*j+1, 2*k+1))?
+          (0..8).try_fold(R::default(), |r, c| 
+            Ok(r + self.traverse(position, level+1, (2*i + (c & 1), 2*j + ((c >> 1) & 1), 2*k + ((c >> 2) & 1)), f, s)?)
           )
         }
       },
 
-      Some(Info::Vortons(vortons)) => {
-        Ok(vortons.iter().map(|v| v.velocity_contribution(position)).fold(Vector3::default(), |r, v| r+v))
-      },
+      Info::Vortons(range) => Ok(f(range.clone())),
+    }
+  }
+
+  /// Returns the velocity induced by the vortons at `position`, ie excluding the free stream
+  fn induced_velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
+    if let Some(ewald) = &self.ewald {
+      // The short range part is evaluated at the images of the position in the neighbouring boxes
+      return ewald.shifts().iter().try_fold(ewald.long_range_velocity(position), |r, shift| {
+        let image = position + shift;
+        Ok(r + self.traverse(&image, 0, (0, 0, 0),
+                             &|range: Range<usize>| self.sum_vortons(range, |v| ewald.short_range_velocity(v, &image)),
+                             &|s: &SuperVorton| ewald.short_range_velocity(s.vorton(), &image))?)
+      });
+    }
+    self.traverse(position, 0, (0, 0, 0),
+                  &|range: Range<usize>| self.arrays.velocity_at(&self.kernel, range, position),
+                  &|s: &SuperVorton| s.velocity_contribution(&self.kernel, position))
+  }
+
+  /// Returns the sum of `f` over the vortons in `range` of the order of the octree
+  fn sum_vortons<R, F>(&self, range: Range<usize>, f: F) -> R
+  where R: std::ops::Add<Output = R> + Default,
+        F: Fn(&Vorton) -> R
+  {
+    let order = self.octree.as_ref().map(|octree| &octree.order()[range]).unwrap_or_default();
+    order.iter().fold(R::default(), |r, v| r + f(&self.vortons[*v]))
+  }
+
+  /// Returns true when the super vorton of `node` is used at `position` instead of the
+  /// vortons of the cell
+  fn is_accepted(&self, node: &Node, super_vorton: &SuperVorton, position: &Point3<f64>) -> bool {
+    node.length() < self.theta * (position - &node.center()).norm()   // #1 cell seen under an angle below theta
+    && !super_vorton.vorton().is_inside(position)                     // #2 position is not within the vorton direct influence
+  }
+
+  /// Compare the velocity at `n_samples` vortons, selected at random from `seed`, to the
+  /// direct sum over the vortons, and count the cells visited by the traversal of the tree.
+  pub fn accuracy_report(&self, n_samples: usize, seed: u64) -> Result<AccuracyReport, Box<dyn std::error::Error>> {
+    let zero = Vector3::default();
+    let direct = VortonToVelocitySimpleBuilder::default().vortons(self.vortons).velocity(&zero)
+      .kernel(self.kernel).periodicity(self.periodicity.clone()).build()?;
+    let samples = sample(self.vortons, n_samples, seed);
+    let shifts = match &self.ewald { Some(ewald) => ewald.shifts().to_vec(), None => vec![Vector3::default()] };
+    let results = parallel::try_map(&samples, |position| {
+      let mut levels = Vec::new();
+      for shift in shifts.iter() { self.count(&(position + shift), 0, (0, 0, 0), &mut levels)?; }
+      Ok((self.induced_velocity_at(position)?, direct.velocity_at(position)?, levels))
+    })?;
+    Ok(AccuracyReport::new(results))
+  }
 
-      None => Ok(Vector3::default()),
+  /// Count, by level, the cells whose super vorton is accepted, the cells that are opened and
+  /// the vortons evaluated directly by the traversal for `position`
+  fn count(&self, position: &Point3<f64>, level: usize, (i,j,k): (usize, usize, usize),
+           levels: &mut Vec<LevelStatistics>) -> Result<(), Box<dyn std::error::Error>> {
+    let octree = self.octree.as_ref().ok_or("The tree is not initialised")?;
+    let Some(node) = octree.node(level, &(i,j,k)) else { return Ok(()) };
+    if levels.len() <= level { levels.resize(level + 1, LevelStatistics::default()); }
+    match &node.info {
+      Info::SuperVorton ( super_vorton ) if super_vorton.n_vortons() == 1 => { levels[level].direct += 1; },
+      Info::SuperVorton ( super_vorton ) if self.is_accepted(node, super_vorton, position) => { levels[level].accepted += 1; },
+      Info::SuperVorton ( _ ) => {
+        levels[level].opened += 1;
+        for c in 0..8 {
+          self.count(position, level+1, (2*i + (c & 1), 2*j + ((c >> 1) & 1), 2*k + ((c >> 2) & 1)), levels)?;
+        }
+      },
+      Info::Vortons(range) => { levels[level].direct += range.len(); },
     }
+    Ok(())
   }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::random::random;
 
-  fn make_infos(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    if self.infos.len() == 0 {
-      self.make_grids()?;
-
-      let mut infos = Vec::new();
-      let grid = &self.grids[self.n_grids - 1];
-      let info = self.vortons.iter()
-          .map(|v| (grid.cell_ijk(v.position()), v) )
-          .filter(|(ijk, _v)| ijk.is_some())
-          .map(|(ijk, v)| (ijk.unwrap(), v))
-          .fold(std::collections::HashMap::<(usize, usize, usize), Vec<&Vorton>>::new(),
-              |mut map, (ijk, v)| {
-                map.entry(ijk).or_insert(Vec::new()).push(v);
-                map
-              })
-          .into_iter()
-          .map(|(key, val)| (key, Info::Vortons(val)))
-          .collect::<std::collections::HashMap::<(usize, usize, usize), Info>>();
-      infos.push(info);
-
-      while infos.len() < self.n_grids {
-        let info = infos.first().ok_or("Unable to retrieve info")?
-          .iter()
-          .map(|(key, info)| {
-            let (i, j, k) = key;
-            ((i.div_euclid(2), j.div_euclid(2), k.div_euclid(2)),
-             if let Info::Vortons(vortons) = info {
-               vortons.into()
-             } else {
-               info.clone()
-             })
-          })
-          .fold(std::collections::HashMap::<(usize, usize, usize), Info>::new(),
-                |mut map, (ijk, info)| {
-                  if let Some(Info::SuperVorton ( super_vorton )) = map.remove(&ijk) {
-                    if let Info::SuperVorton ( other_super_vorton ) = info {
-                      map.insert(ijk, Info::SuperVorton ( super_vorton + &other_super_vorton ));
-                    } else {
-                      eprintln!("Something wrong...");
-                    }
-                  } else {
-                    map.insert(ijk, info);
-                  }
-                  // println!("after: {:?}", map.get(&ijk));
-                  map
-                });
-        infos.insert(0, info);
+  /// The error decreases with the opening angle, for a tree of uniform depth as well as for
+  /// an adaptive tree
+  #[test]
+  fn it_converges_with_theta() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed = 11;
+    // A dense cluster within a sparse cloud of vortons of random vorticity
+    let vortons = (0..1000)
+      .map(|i| {
+        let scale = if i < 500 { 0.1 } else { 1.0 };
+        Vorton::new(Point3::new(scale * random(&mut seed), scale * random(&mut seed), scale * random(&mut seed)),
+                    Vector3::new(random(&mut seed) - 0.5, random(&mut seed) - 0.5, random(&mut seed) - 0.5).scale(1e-3),
+                    1e-6)
+      })
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+    let expected = vortons.iter().map(|v| simple.velocity_at(v.position())).collect::<Result<Vec<_>, _>>()?;
+    let error = |tree: &VortonToVelocityTree| -> Result<f64, Box<dyn std::error::Error>> {
+      let (mut e, mut n) = (0.0, 0.0);
+      for (v, u) in vortons.iter().zip(expected.iter()) {
+        e += (tree.velocity_at(v.position())? - u.clone()).norm().powi(2); n += u.norm().powi(2);
       }
-      self.infos = infos;
+      Ok((e / n).sqrt())
+    };
+
+    let errors = [0.84, 0.5, 0.25].iter()
+      .map(|theta| error(&VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+                         .theta(*theta).max_depth(6).build()?.initialize()?))
+      .collect::<Result<Vec<f64>, _>>()?;
+    let adaptive = [0.5, 0.25].iter()
+      .map(|theta| error(&VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+                         .theta(*theta).max_leaf(8).max_depth(10).build()?.initialize()?))
+      .collect::<Result<Vec<f64>, _>>()?;
+    println!("Errors: {errors:?}, adaptive: {adaptive:?}");
+    assert!(errors.windows(2).all(|e| e[1] < e[0]));
+    assert!(errors[2] < 1e-3);
+    assert!(adaptive[1] < adaptive[0] && adaptive[1] < 1e-3);
+    Ok(())
+  }
+
+  /// The octree is refitted to slightly displaced vortons, with the same accuracy as a new
+  /// octree, and rebuilt when the vortons moved too far
+  #[test]
+  fn it_refits_octree() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed = 5;
+    let vortons = (0..1000)
+      .map(|_| Vorton::new(Point3::new(random(&mut seed), random(&mut seed), random(&mut seed)),
+                           Vector3::new(random(&mut seed) - 0.5, random(&mut seed) - 0.5, random(&mut seed) - 0.5).scale(1e-3),
+                           1e-6))
+      .collect::<Vec<Vorton>>();
+    let displace = |vortons: &Vec<Vorton>, scale: f64, seed: &mut u64| vortons.iter()
+      .map(|v| v.advect(&Vector3::new(random(seed) - 0.5, random(seed) - 0.5, random(seed) - 0.5), scale))
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    let tree = |vortons, octree| VortonToVelocityTreeBuilder::default().vortons(vortons).velocity(&velocity)
+      .theta(0.5).max_leaf(8).max_depth(8).octree(octree).build()?.initialize();
+    let octree = tree(&vortons, None)?.octree();
+
+    let moved = displace(&vortons, 1e-3, &mut seed);
+    let (refitted, rebuilt) = (tree(&moved, octree.clone())?, tree(&moved, None)?);
+    assert_eq!(refitted.octree().map(|o| o.refits()), Some(1));
+    let simple = VortonToVelocitySimpleBuilder::default().vortons(&moved).velocity(&velocity).build()?;
+    let (mut e_refitted, mut e_rebuilt, mut n) = (0.0, 0.0, 0.0);
+    for v in moved.iter().step_by(10) {
+      let u = simple.velocity_at(v.position())?;
+      e_refitted += (refitted.velocity_at(v.position())? - u.clone()).norm().powi(2);
+      e_rebuilt += (rebuilt.velocity_at(v.position())? - u.clone()).norm().powi(2);
+      n += u.norm().powi(2);
     }
+    let (e_refitted, e_rebuilt) = ((e_refitted / n).sqrt(), (e_rebuilt / n).sqrt());
+    println!("Errors: refitted {e_refitted}, rebuilt {e_rebuilt}");
+    assert!(e_refitted < 1e-2 && e_refitted < 2.0 * e_rebuilt);
+
+    let scattered = displace(&vortons, 0.2, &mut seed);
+    assert_eq!(tree(&scattered, octree.clone())?.octree().map(|o| o.refits()), Some(0));
+
+    // Two clusters of 8 vortons on either side of the middle of the domain, which becomes
+    // the leaf of both when one cluster moves by a fraction of its cell
+    let cluster = |x: f64, seed: &mut u64| (0..8)
+      .map(|_| Vorton::new(Point3::new(x + 0.01 * random(seed), 0.3 + 0.01 * random(seed), 0.3 + 0.01 * random(seed)),
+                           Vector3::new(0.0, 0.0, 1e-3), 1e-6))
+      .collect::<Vec<Vorton>>();
+    let corners = vec![Vorton::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1e-3), 1e-6),
+                       Vorton::new(Point3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 0.0, 1e-3), 1e-6)];
+    let clusters = [corners, cluster(0.44, &mut seed), cluster(0.55, &mut seed)].concat();
+    let octree = tree(&clusters, None)?.octree();
+    let gathered = clusters.iter().enumerate()
+      .map(|(i, v)| if i >= 10 { v.advect(&Vector3::new(-0.1, 0.0, 0.0), 1.0) } else { v.clone() })
+      .collect::<Vec<Vorton>>();
+    assert_eq!(tree(&gathered, octree)?.octree().map(|o| o.refits()), Some(0));
     Ok(())
   }
 
-  fn make_grids(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-    use std::convert::TryInto;
-    if self.grids.len() == 0 {
-      self.grids.push(self.vortons.try_into()?);
-      while self.grids.len() < self.n_grids { self.grids.push(self.grids.last().ok_or("Grid unavailable")?.into()); }
+  /// A single vorton, or coincident vortons, are kept in a tree the size of their core
+  #[test]
+  fn it_handles_coincident_vortons() -> Result<(), Box<dyn std::error::Error>> {
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    let p = Point3::new(0.5, 0.2, 0.1);
+    for n in [1, 3] {
+      let vortons = vec![Vorton::new(Point3::new(0.3, 0.2, 0.1), Vector3::new(0.0, 0.0, 1.0), 1e-3); n];
+      let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+      for max_leaf in [0, 1] {
+        let tree = VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+          .max_leaf(max_leaf).max_depth(6).build()?.initialize()?;
+        let (expected, u) = (simple.velocity_at(&p)?, tree.velocity_at(&p)?);
+        assert!(expected.norm() > 0.0 && (u - expected.clone()).norm() < 1e-6 * expected.norm());
+      }
     }
     Ok(())
   }
-}
 
+  /// Without super vortons the tree is exact, and the report accounts for every vorton
+  #[test]
+  fn it_reports_accuracy() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed = 3;
+    let vortons = (0..500)
+      .map(|_| Vorton::new(Point3::new(random(&mut seed), random(&mut seed), random(&mut seed)),
+                           Vector3::new(random(&mut seed) - 0.5, random(&mut seed) - 0.5, random(&mut seed) - 0.5).scale(1e-3),
+                           1e-6))
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let report = |theta| VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+      .theta(theta).max_leaf(8).max_depth(8).build()?.initialize()?.accuracy_report(50, 1);
+
+    let exact = report(0.0)?;
+    println!("{exact}");
+    assert_eq!(exact.n_samples, 50);
+    assert!(exact.l2_error < 1e-12 && exact.linf_error < 1e-12);
+    assert_eq!(exact.levels.iter().map(|l| l.accepted).sum::<usize>(), 0);
+    assert_eq!(exact.levels.iter().map(|l| l.direct).sum::<usize>(), 50 * vortons.len());
+
+    let approximate = report(0.5)?;
+    println!("{approximate}");
+    assert!(approximate.l2_error > 0.0 && approximate.l2_error < 1e-2);
+    assert!(approximate.levels.iter().map(|l| l.accepted).sum::<usize>() > 0);
+    Ok(())
+  }
+
+  /// The opening angle alone decides which cells are accepted, whatever the strength of the
+  /// vorticity, so that the relative error does not depend on it
+  #[test]
+  fn it_accepts_cells_of_any_strength() -> Result<(), Box<dyn std::error::Error>> {
+    let mut seed = 5;
+    let vortons = (0..500)
+      .map(|_| Vorton::new(Point3::new(random(&mut seed), random(&mut seed), random(&mut seed)),
+                           Vector3::new(random(&mut seed) - 0.5, random(&mut seed) - 0.5, random(&mut seed) - 0.5),
+                           1.0 / 500.0))
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::new(0.0, 0.0, 0.0);
+    let reports = [1.0, 1e3].iter()
+      .map(|strength| {
+        let vortons = vortons.iter()
+          .map(|v| Vorton::new(v.position().clone(), v.vorticity().scale(*strength), v.volume()))
+          .collect::<Vec<Vorton>>();
+        VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+          .theta(0.5).max_leaf(8).max_depth(8).build()?.initialize()?.accuracy_report(50, 1)
+      })
+      .collect::<Result<Vec<AccuracyReport>, _>>()?;
+    let accepted = reports.iter().map(|r| r.levels.iter().map(|l| l.accepted).sum::<usize>()).collect::<Vec<_>>();
+    println!("Accepted: {accepted:?}, errors: {:?}", reports.iter().map(|r| r.l2_error).collect::<Vec<_>>());
+    assert!(accepted[0] > 0 && accepted[0] == accepted[1]);
+    assert!(reports[0].l2_error < 1e-2 && (reports[1].l2_error - reports[0].l2_error).abs() < 1e-9);
+    Ok(())
+  }
+}
@@ -0,0 +1,77 @@
+use crate::{Point3, Vector3, Vorton};
+use crate::random::random;
+
+/// Deviation of the tree from the direct sum over the vortons, evaluated at sample
+/// positions. Errors are relative to the velocity induced by the vortons, ie excluding the
+/// free stream velocity:
+///  - `l2_error = √(Σ |u_tree - u_direct|² / Σ |u_direct|²)`;
+///  - `linf_error = max |u_tree - u_direct| / max |u_direct|`.
+#[derive(Debug, Clone)]
+pub struct AccuracyReport {
+  pub n_samples: usize,
+  pub l2_error: f64,
+  pub linf_error: f64,
+  /// Cells visited by the traversal of the tree at each level, summed over the samples
+  pub levels: Vec<LevelStatistics>,
+}
+
+/// Outcome of the traversal of the cells of a level of the tree
+#[derive(Debug, Clone, Default)]
+pub struct LevelStatistics {
+  /// Cells represented by their super vorton
+  pub accepted: usize,
+  /// Cells subdivided into their children
+  pub opened: usize,
+  /// Vortons evaluated individually
+  pub direct: usize,
+}
+
+impl AccuracyReport {
+  /// Make the report from the velocity induced by the tree and by the direct sum, and the
+  /// statistics of the traversal, at each sample
+  pub(super) fn new(results: Vec<(Vector3<f64>, Vector3<f64>, Vec<LevelStatistics>)>) -> AccuracyReport {
+    let (mut error, mut norm, mut max_error, mut max_norm) = (0.0, 0.0, 0.0f64, 0.0f64);
+    let mut levels: Vec<LevelStatistics> = Vec::new();
+    for (tree, direct, statistics) in results.iter() {
+      let e = (tree.clone() - direct.clone()).norm();
+      error += e * e; norm += direct.norm().powi(2);
+      max_error = max_error.max(e); max_norm = max_norm.max(direct.norm());
+      if levels.len() < statistics.len() { levels.resize(statistics.len(), LevelStatistics::default()); }
+      for (level, s) in levels.iter_mut().zip(statistics.iter()) {
+        level.accepted += s.accepted; level.opened += s.opened; level.direct += s.direct;
+      }
+    }
+    AccuracyReport {
+      n_samples: results.len(),
+      l2_error: if norm > 0.0 { (error / norm).sqrt() } else { 0.0 },
+      linf_error: if max_norm > 0.0 { max_error / max_norm } else { 0.0 },
+      levels,
+    }
+  }
+}
+
+impl std::fmt::Display for AccuracyReport {
+  /// Print the errors, and the statistics of each level averaged per sample
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "Tree accuracy over {} samples: L2 {:.3e}, Linf {:.3e}", self.n_samples, self.l2_error, self.linf_error)?;
+    let n = self.n_samples.max(1) as f64;
+    for (i, level) in self.levels.iter().enumerate() {
+      writeln!(f, "  level {}: {:.1} accepted, {:.1} opened, {:.1} direct", i,
+               level.accepted as f64 / n, level.opened as f64 / n, level.direct as f64 / n)?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns the positions of up to `n_samples` distinct vortons selected at random from `seed`
+pub(super) fn sample(vortons: &[Vorton], n_samples: usize, seed: u64) -> Vec<Point3<f64>> {
+  // Partial Fisher–Yates shuffle of the indices using a linear congruential generator
+  let mut indices = (0..vortons.len()).collect::<Vec<usize>>();
+  let mut state = seed;
+  let n = n_samples.min(vortons.len());
+  for i in 0..n {
+    let j = i + (random(&mut state) * (indices.len() - i) as f64) as usize;
+    indices.swap(i, j);
+  }
+  indices[..n].iter().map(|i| vortons[*i].position().clone()).collect()
+}
@@ -1,6 +1,6 @@
 use crate::{Point3, Vorton, Vector3};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Grid {
   start: Point3<f64>,
   delta: f64,
@@ -8,27 +8,16 @@ pub struct Grid {
 }
 
 impl Grid {
-  /// Returns the `x` position associated with index `i`
-  // pub fn x(&self, i: usize) -> f64 { self.start.x + (i as f64/ self.n_cell as f64) * self.delta }
-
-  /// Returns the `y` position associated with index `i`
-  // pub fn y(&self, i: usize) -> f64 { self.start.y + (i as f64/ self.n_cell as f64) * self.delta }
-
-  /// Returns the `z` position associated with index `i`
-  // pub fn z(&self, i: usize) -> f64 { self.start.z + (i as f64/ self.n_cell as f64) * self.delta }
-
-  /// Return the cell index (in a linear array) associated with the cell index `i, j, k`
-  // pub fn cell_index(&self, i: usize, j: usize, k: usize) -> usize { i + self.n_cell * (j + self.n_cell * k) }
-
   /// Return the cell `i, j, k` associated with the provided `Point3`. Returns None if the 
-  /// `Point3` is outside of the grid.
+  /// `Point3` is outside of the grid. Points on the upper faces of the grid belong to the
+  /// last cell along the axis, so that the indices are always valid.
   pub fn cell_ijk(&self, p: &Point3<f64>) -> Option<(usize, usize, usize)> {
-    let i = (p.x - self.start.x) / self.delta; if i < 0.0 || i > 1.0 { return None };
-    let j = (p.y - self.start.y) / self.delta; if j < 0.0 || j > 1.0 { return None };
-    let k = (p.z - self.start.z) / self.delta; if k < 0.0 || k > 1.0 { return None };
-    Some((((i * (self.n_cell as f64)).floor() as usize).min(self.n_cell),
-          ((j * (self.n_cell as f64)).floor() as usize).min(self.n_cell),
-          ((k * (self.n_cell as f64)).floor() as usize).min(self.n_cell)))
+    let i = (p.x - self.start.x) / self.delta; if !(0.0..=1.0).contains(&i) { return None };
+    let j = (p.y - self.start.y) / self.delta; if !(0.0..=1.0).contains(&j) { return None };
+    let k = (p.z - self.start.z) / self.delta; if !(0.0..=1.0).contains(&k) { return None };
+    Some((((i * (self.n_cell as f64)).floor() as usize).min(self.n_cell - 1),
+          ((j * (self.n_cell as f64)).floor() as usize).min(self.n_cell - 1),
+          ((k * (self.n_cell as f64)).floor() as usize).min(self.n_cell - 1)))
   }
 
   /// Returns the position of the cell center
@@ -46,17 +35,21 @@ impl Grid {
   }
 }
 
-impl std::convert::TryFrom<&Vec<Vorton>> for Grid {
+impl std::convert::TryFrom<&[Vorton]> for Grid {
   type Error = Box<dyn std::error::Error>;
-  /// Generate a grid to fit an array of vortons
-  fn try_from(vortons: &Vec<Vorton>) -> Result<Self, Self::Error> {
+  /// Generate a grid to fit an array of vortons. The grid is at least as large as the core
+  /// of the largest vorton, so that a single vorton or coincident vortons fit in it.
+  fn try_from(vortons: &[Vorton]) -> Result<Self, Self::Error> {
     use std::convert::TryInto;
-    vortons.iter()
+    let (min, max) = vortons.iter()
     .fold(None, |acc: Option<(Point3<f64>, Point3<f64>)>, v| 
-                   acc.and_then(|(min, max)| Some((min.min(v.position()), max.max(v.position()))))
+                   acc.map(|(min, max)| (min.min(v.position()), max.max(v.position())))
                    .or(Some((v.position().clone(), v.position().clone()))))
-    .unwrap_or((Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)))
-    .try_into()
+    .unwrap_or((Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)));
+    let core_radius = vortons.iter().map(|v| v.core_radius()).fold(0.0, f64::max);
+    let mid = &min + &(&max - &min).scale(0.5);
+    let half = Vector3::new(core_radius, core_radius, core_radius).scale(0.5);
+    (min.min(&(&mid - &half)), max.max(&(&mid + &half))).try_into()
   }
 }
 
@@ -66,6 +59,7 @@ impl std::convert::TryFrom<(Point3<f64>, Point3<f64>)> for Grid {
   /// that the bounding box remains contained within the grid
   fn try_from((min, max): (Point3<f64>, Point3<f64>)) -> Result<Self, Self::Error> {
     let v = &max - &min; let mid = &min + &v.scale(0.5); let delta = 1.01*v.x.max(v.y).max(v.z);
+    if delta.is_nan() || delta <= 0.0 { return Err("The grid has no extent".into()); }
     let v = Vector3::new(delta, delta, delta);
     Ok(Grid {
       start: mid - v.scale(0.5),
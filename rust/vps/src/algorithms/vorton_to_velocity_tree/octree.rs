@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::{parallel, Point3, Vector3, SuperVorton, Vorton};
+use super::Grid;
+
+/// Octree sorting the vortons in the cells of a hierarchy of grids. Cells with more than
+/// `max_leaf` vortons are represented by a super vorton and subdivided, until the deepest
+/// level where the vortons are kept.
+///
+/// The octree refers to the vortons by their index, so that it can be kept across time
+/// steps: as long as the vortons are neither added, removed nor reordered, the octree is
+/// refitted to their new positions rather than rebuilt. Each cell keeps the box containing
+/// both the cell and its vortons, which grows as the vortons leave the cell.
+///
+/// The vortons of each leaf are contiguous in the order of the octree, so that a leaf
+/// refers to a range of that order.
+#[derive(Debug, Clone)]
+pub struct Octree {
+  max_leaf: usize,
+  max_depth: usize,
+  n_vortons: usize,
+  /// Number of times the octree was refitted since it was built
+  refits: usize,
+  grids: Vec<Grid>,
+  /// Indices of the vortons sorted by leaf
+  order: Vec<usize>,
+  nodes: Vec<HashMap<(usize, usize, usize), Node>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Info {
+  SuperVorton ( Box<SuperVorton> ),
+  /// Range of the order of the octree holding the vortons of a leaf cell
+  Vortons ( Range<usize> ),
+}
+
+/// Cell of the octree
+#[derive(Debug, Clone)]
+pub struct Node {
+  pub info: Info,
+  min: Point3<f64>,
+  max: Point3<f64>,
+}
+
+impl Node {
+  /// Length of the longest side of the box containing the cell and its vortons
+  pub fn length(&self) -> f64 {
+    let d = &self.max - &self.min;
+    d.x.max(d.y).max(d.z)
+  }
+
+  /// Center of the box containing the cell and its vortons
+  pub fn center(&self) -> Point3<f64> {
+    &self.min + &(&self.max - &self.min).scale(0.5)
+  }
+}
+
+impl Octree {
+  pub fn new(vortons: &[Vorton], max_leaf: usize, max_depth: usize) -> Result<Octree, Box<dyn std::error::Error>> {
+    use std::convert::TryInto;
+    if max_depth == 0 { return Err("The tree requires at least one level".into()); }
+    let mut grids: Vec<Grid> = vec![vortons.try_into()?];
+    while grids.len() < max_depth { grids.push(grids.last().ok_or("Grid unavailable")?.into()); }
+
+    let mut nodes = Vec::new();
+    let mut order = Vec::with_capacity(vortons.len());
+    let mut cells = HashMap::from([((0, 0, 0), (0..vortons.len()).collect::<Vec<usize>>())]);
+    for level in 0..max_depth {
+      let is_leaf = |indices: &Vec<usize>| level + 1 == max_depth || indices.len() <= max_leaf;
+      // Cells are subdivided in a fixed order so that the results are reproducible
+      let mut entries = cells.into_iter().collect::<Vec<_>>();
+      entries.sort_by_key(|(key, _)| *key);
+      let mut children = HashMap::<(usize, usize, usize), Vec<usize>>::new();
+      let mut level_nodes = HashMap::new();
+      for ((i, j, k), indices) in entries {
+        let info = if is_leaf(&indices) {
+          let start = order.len();
+          order.extend(indices);
+          Info::Vortons(start..order.len())
+        } else {
+          let grid = &grids[level + 1];
+          for (index, ijk) in indices.iter().zip(parallel::map(&indices, |i| grid.cell_ijk(vortons[*i].position()))) {
+            if let Some(ijk) = ijk { children.entry(ijk).or_default().push(*index); }
+          }
+          Info::SuperVorton(Box::default())
+        };
+        let (min, max) = cell_box(&grids[level], &(i, j, k));
+        level_nodes.insert((i, j, k), Node { info, min, max });
+      }
+      nodes.push(level_nodes);
+      cells = children;
+      if cells.is_empty() { break; }
+    }
+
+    let mut octree = Octree { max_leaf, max_depth, n_vortons: vortons.len(), refits: 0, grids, order, nodes };
+    octree.fit(vortons);
+    Ok(octree)
+  }
+
+  pub fn node(&self, level: usize, ijk: &(usize, usize, usize)) -> Option<&Node> {
+    self.nodes.get(level)?.get(ijk)
+  }
+
+  pub fn refits(&self) -> usize { self.refits }
+
+  /// Returns the indices of the vortons sorted by leaf
+  pub fn order(&self) -> &[usize] { &self.order }
+
+  /// Returns true when the octree was built with the nominated parameters
+  pub fn is_built_with(&self, max_leaf: usize, max_depth: usize) -> bool {
+    self.max_leaf == max_leaf && self.max_depth == max_depth
+  }
+
+  /// Refit the octree to the new positions of the same vortons. Returns false, leaving the
+  /// octree in an unspecified state, when the number of vortons differs, when the box of a
+  /// cell grows beyond `1 + rebuild_threshold` times the cell length or, when the leaves are
+  /// limited to `max_leaf` vortons, when the occupancy of a leaf grows beyond that threshold:
+  /// the octree must then be rebuilt.
+  pub fn refit(&mut self, vortons: &[Vorton], rebuild_threshold: f64) -> bool {
+    if vortons.len() != self.n_vortons { return false; }
+    self.fit(vortons);
+    self.refits += 1;
+    self.nodes.iter().zip(self.grids.iter())
+      .all(|(nodes, grid)| nodes.values().all(|n| n.length() <= (1.0 + rebuild_threshold) * grid.cell_length()))
+    && (self.max_leaf == 0 || self.is_occupancy_within(vortons, rebuild_threshold))
+  }
+
+  /// Returns true when no leaf cell, or cell that was empty, holds more than
+  /// `1 + rebuild_threshold` times the number of vortons it held when the octree was built,
+  /// or `max_leaf` if more, once the vortons are sorted in the cells at their new positions.
+  /// A rebuilt octree would otherwise subdivide the cell.
+  fn is_occupancy_within(&self, vortons: &[Vorton], rebuild_threshold: f64) -> bool {
+    let mut counts = HashMap::<(usize, (usize, usize, usize)), usize>::new();
+    for cell in parallel::map(vortons, |v| self.leaf_at(v.position())) {
+      let Some(cell) = cell else { return false };
+      *counts.entry(cell).or_default() += 1;
+    }
+    counts.iter().all(|((level, ijk), n)| {
+      let built = match self.node(*level, ijk).map(|node| &node.info) { Some(Info::Vortons(range)) => range.len(), _ => 0 };
+      *n as f64 <= (1.0 + rebuild_threshold) * built.max(self.max_leaf) as f64
+    })
+  }
+
+  /// Returns the level and indices of the leaf cell, or of the empty cell, at `position`,
+  /// or None when `position` is outside of the grids
+  fn leaf_at(&self, position: &Point3<f64>) -> Option<(usize, (usize, usize, usize))> {
+    for (level, grid) in self.grids.iter().enumerate().take(self.nodes.len()) {
+      let ijk = grid.cell_ijk(position)?;
+      if !matches!(self.node(level, &ijk).map(|node| &node.info), Some(Info::SuperVorton(_))) { return Some((level, ijk)); }
+    }
+    None
+  }
+
+  /// Evaluate the super vortons and boxes of the cells, from the deepest level up
+  fn fit(&mut self, vortons: &[Vorton]) {
+    let (grids, order) = (&self.grids, &self.order);
+    for level in (0..self.nodes.len()).rev() {
+      let (nodes, children) = self.nodes.split_at_mut(level + 1);
+      let (nodes, children) = (&mut nodes[level], children.first());
+      let keys = nodes.keys().cloned().collect::<Vec<_>>();
+      let fitted = parallel::map(&keys, |(i, j, k)| {
+        let node = &nodes[&(*i, *j, *k)];
+        let (min, max) = cell_box(&grids[level], &(*i, *j, *k));
+        match &node.info {
+          Info::Vortons(range) => {
+            let (min, max) = order[range.clone()].iter()
+              .fold((min, max), |(min, max), v| (min.min(vortons[*v].position()), max.max(vortons[*v].position())));
+            Node { info: node.info.clone(), min, max }
+          },
+          Info::SuperVorton(_) => {
+            let (super_vorton, min, max) = (0..8)
+              .filter_map(|c| children?.get(&(2*i + (c & 1), 2*j + ((c >> 1) & 1), 2*k + ((c >> 2) & 1))))
+              .fold((SuperVorton::default(), min, max), |(s, min, max), child| {
+                let s = match &child.info {
+                  Info::SuperVorton(super_vorton) => s + super_vorton.as_ref(),
+                  Info::Vortons(range) => order[range.clone()].iter().fold(s, |s, v| s + &vortons[*v]),
+                };
+                (s, min.min(&child.min), max.max(&child.max))
+              });
+            Node { info: Info::SuperVorton(Box::new(super_vorton)), min, max }
+          },
+        }
+      });
+      for (key, node) in keys.into_iter().zip(fitted) { nodes.insert(key, node); }
+    }
+  }
+}
+
+/// Returns the corners of the cell `ijk` of `grid`
+fn cell_box(grid: &Grid, (i, j, k): &(usize, usize, usize)) -> (Point3<f64>, Point3<f64>) {
+  let (center, half) = (grid.cell_center(*i, *j, *k), 0.5 * grid.cell_length());
+  let half = Vector3::new(half, half, half);
+  (&center - &half, &center + &half)
+}
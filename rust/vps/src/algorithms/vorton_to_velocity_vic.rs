@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use rustfft::{FftPlanner, FftDirection, num_complex::Complex};
+
+use crate::{parallel, sim, VortonToVelocity, Point3, Vorton, Kernel, Vector3, Matrix3, UniformGrid, Interpolation};
+
+mod fft; use fft::fft3;
+mod green; pub use green::GreenFunction;
+
+/// Default maximum number of cells of the grid. The padded grid of the FFT has eight times
+/// as many cells, each taking 16 bytes for the Green's function and for each transform.
+const DEFAULT_MAX_CELLS: usize = 1 << 21;
+
+/// Algorithm to calculate the velocity from a field of vortons using the Vortex-In-Cell
+/// method. The vorticity of the vortons is interpolated onto a `UniformGrid`, the vector
+/// Poisson equation `∇²ψ = -ω` is solved for the stream function using FFT and the velocity
+/// `u = ∇ × ψ` is evaluated on the grid using finite differences. The velocity at a position
+/// is interpolated from the grid.
+///
+/// The Poisson equation is solved in free space by convolution with the Green's function
+/// `1/(4π|r|)` on a grid padded with zeros to twice its size (Hockney–Eastwood). Positions
+/// located outside of the grid are evaluated by direct summation using the smoothing kernel.
+///
+/// The initialisation fails when the grid would have more than `max_cells` cells, eg when a
+/// stray vorton is far from the others. The Green's function of a previous evaluation can be
+/// provided to be reused when the grid has the same size.
+#[derive(derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct VortonToVelocityVic<'a> {
+  vortons: &'a [Vorton],
+  velocity: &'a Vector3<f64>,
+  /// Smoothing kernel of the vortons, used for positions outside of the grid
+  #[builder(default)]
+  kernel: Kernel,
+  /// Spacing of the grid. Defaults to the cube root of the median vorton volume.
+  #[builder(default)]
+  spacing: Option<f64>,
+  /// Kernel used to interpolate between the vortons and the grid
+  #[builder(default)]
+  interpolation: Interpolation,
+  /// Maximum number of cells of the grid
+  #[builder(default = "DEFAULT_MAX_CELLS")]
+  max_cells: usize,
+  /// Green's function of a previous evaluation, reused when the grid has the same size
+  #[builder(default)]
+  green: Option<Arc<GreenFunction>>,
+  #[builder(setter(skip))]
+  grid: Option<UniformGrid>,
+  #[builder(setter(skip))]
+  velocities: Vec<Vector3<f64>>,
+  #[builder(setter(skip))]
+  gradients: Vec<Matrix3<f64>>,
+}
+
+impl<'a> VortonToVelocity for VortonToVelocityVic<'a> {
+  fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
+    let velocity = self.interpolate(position, 1, |index, w| self.velocities[index].scale(w))?
+      .unwrap_or_else(|| self.vortons.iter()
+                      .fold(Vector3::default(), |r, v| r + v.velocity_contribution(&self.kernel, position)));
+    Ok(self.velocity + velocity)
+  }
+
+  fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+    Ok(
+    self.interpolate(position, 2, |index, w| self.gradients[index].scale(w))?
+      .unwrap_or_else(|| self.vortons.iter()
+                      .fold(Matrix3::default(), |r, v| r + v.velocity_gradient_contribution(&self.kernel, position)))
+    )
+  }
+}
+
+impl<'a> VortonToVelocityVic<'a> {
+  pub fn initialize(mut self) -> Result<VortonToVelocityVic<'a>, Box<dyn std::error::Error>> {
+    if self.vortons.is_empty() { return Ok(self); }
+    let spacing = self.spacing.unwrap_or_else(|| sim::median_spacing(self.vortons));
+    if spacing <= 0.0 { return Err("VIC grid spacing must be positive".into()); }
+
+    // The margin ensures that the stencils of the interpolation and of the finite
+    // differences of the velocity gradient remain within the grid around each vorton
+    let (min, max) = self.vortons.iter()
+      .fold((self.vortons[0].position().clone(), self.vortons[0].position().clone()),
+            |(min, max), v| (min.min(v.position()), max.max(v.position())));
+    let margin = Vector3::new(1.0, 1.0, 1.0).scale((self.interpolation.support() + 2) as f64 * spacing);
+    let grid = UniformGrid::from_spacing(&(min - margin.clone()), &(max + margin), spacing)?;
+    let (n_x, n_y, n_z) = grid.n_cells_ijk();
+    let n_cells = n_x.checked_mul(n_y).and_then(|n| n.checked_mul(n_z));
+    if n_cells.is_none_or(|n| n > self.max_cells) {
+      return Err(format!("The VIC grid of {} x {} x {} cells exceeds the maximum of {} cells", n_x, n_y, n_z, self.max_cells).into());
+    }
+
+    let stream_function = self.solve_poisson(&grid, &self.vorticity(&grid)?, spacing);
+    self.velocities = VortonToVelocityVic::curl(&grid, &stream_function, spacing);
+    self.gradients = VortonToVelocityVic::gradient(&grid, &self.velocities, spacing);
+    self.grid = Some(grid);
+    Ok(self)
+  }
+
+  /// Returns the vorticity interpolated on the grid cells
+  fn vorticity(&self, grid: &UniformGrid) -> Result<Vec<Vector3<f64>>, Box<dyn std::error::Error>> {
+    let mut vorticity = vec![Vector3::default(); grid.n_cells()];
+    for vorton in self.vortons.iter() {
+      let (x, y, z) = grid.cell_coordinates(vorton.position());
+      let strength = vorton.vorticity().scale(vorton.volume());
+      let i = &self.interpolation;
+      for (k, w_k) in i.weights(z) {
+        for (j, w_j) in i.weights(y) {
+          for (i, w_i) in i.weights(x) {
+            let index = grid.cell_index(i, j, k).ok_or("Vorton outside of VIC grid")?;
+            vorticity[index] = vorticity[index].clone() + strength.scale(w_i * w_j * w_k / grid.cell_volume(index));
+          }
+        }
+      }
+    }
+    Ok(vorticity)
+  }
+
+  /// Returns the Green's function of the previous evaluation, to be reused by a later one
+  pub fn green(&self) -> Option<Arc<GreenFunction>> {
+    self.green.clone()
+  }
+
+  /// Returns the stream function `ψ = ∫ ω(y) / (4π|x - y|) dy` on the grid cells
+  fn solve_poisson(&mut self, grid: &UniformGrid, vorticity: &[Vector3<f64>], spacing: f64) -> Vec<Vector3<f64>> {
+    let (n_x, n_y, n_z) = grid.n_cells_ijk();
+    let padded = (2 * n_x, 2 * n_y, 2 * n_z);
+    let n_padded = padded.0 * padded.1 * padded.2;
+    let padded_index = |i: usize, j: usize, k: usize| i + padded.0 * (j + padded.1 * k);
+    let mut planner = FftPlanner::new();
+
+    let green = match self.green.take().filter(|green| green.is_for(padded, spacing)) {
+      Some(green) => green,
+      None => Arc::new(GreenFunction::new(&mut planner, padded, spacing)),
+    };
+
+    let mut stream_function = vec![Vector3::default(); grid.n_cells()];
+    for c in 0..3 {
+      let component = |v: &Vector3<f64>| match c { 0 => v.x, 1 => v.y, _ => v.z };
+      let mut data = vec![Complex::default(); n_padded];
+      for k in 0..n_z {
+        for j in 0..n_y {
+          for i in 0..n_x {
+            data[padded_index(i, j, k)] = Complex::new(component(&vorticity[i + n_x * (j + n_y * k)]), 0.0);
+          }
+        }
+      }
+      fft3(&mut planner, &mut data, padded, FftDirection::Forward);
+      for (d, g) in data.iter_mut().zip(green.values()) { *d *= g; }
+      fft3(&mut planner, &mut data, padded, FftDirection::Inverse);
+      for k in 0..n_z {
+        for j in 0..n_y {
+          for i in 0..n_x {
+            let psi = data[padded_index(i, j, k)].re / n_padded as f64;
+            let s = &mut stream_function[i + n_x * (j + n_y * k)];
+            match c { 0 => s.x = psi, 1 => s.y = psi, _ => s.z = psi };
+          }
+        }
+      }
+    }
+    self.green = Some(green);
+    stream_function
+  }
+
+  /// Returns `f(p, m)` for the cells `p` and `m` on either side of the cell `i, j, k` along
+  /// each direction. The cell must not be on the boundary of the grid.
+  fn differences<T, F>(grid: &UniformGrid, (i, j, k): (i64, i64, i64), f: F) -> [T; 3]
+  where F: Fn(usize, usize) -> T
+  {
+    let index = |i, j, k| grid.cell_index(i, j, k).unwrap();
+    [
+      f(index(i + 1, j, k), index(i - 1, j, k)),
+      f(index(i, j + 1, k), index(i, j - 1, k)),
+      f(index(i, j, k + 1), index(i, j, k - 1)),
+    ]
+  }
+
+  /// Returns the velocity `u = ∇ × ψ` on the grid cells. Cells on the boundary of the grid
+  /// are set to zero.
+  fn curl(grid: &UniformGrid, stream_function: &[Vector3<f64>], spacing: f64) -> Vec<Vector3<f64>> {
+    let (n_x, n_y, n_z) = grid.n_cells_ijk();
+    let cells = (0..grid.n_cells()).collect::<Vec<usize>>();
+    parallel::map(&cells, |index| {
+      let (i, j, k) = ((index % n_x) as i64, ((index / n_x) % n_y) as i64, (index / (n_x * n_y)) as i64);
+      if !VortonToVelocityVic::is_interior((i, j, k), (n_x, n_y, n_z), 1) { return Vector3::default(); }
+      let d = VortonToVelocityVic::differences(grid, (i, j, k),
+        |p, m| (stream_function[p].clone() - stream_function[m].clone()).scale(0.5 / spacing));
+      Vector3::new(d[1].z - d[2].y, d[2].x - d[0].z, d[0].y - d[1].x)
+    })
+  }
+
+  /// Returns the velocity gradient `du_i/dx_j` on the grid cells. Cells within two cells of
+  /// the boundary of the grid are set to zero.
+  fn gradient(grid: &UniformGrid, velocities: &[Vector3<f64>], spacing: f64) -> Vec<Matrix3<f64>> {
+    let (n_x, n_y, n_z) = grid.n_cells_ijk();
+    let cells = (0..grid.n_cells()).collect::<Vec<usize>>();
+    parallel::map(&cells, |index| {
+      let (i, j, k) = ((index % n_x) as i64, ((index / n_x) % n_y) as i64, (index / (n_x * n_y)) as i64);
+      if !VortonToVelocityVic::is_interior((i, j, k), (n_x, n_y, n_z), 2) { return Matrix3::default(); }
+      let d = VortonToVelocityVic::differences(grid, (i, j, k),
+        |p, m| (velocities[p].clone() - velocities[m].clone()).scale(0.5 / spacing));
+      Matrix3::new([[d[0].x, d[1].x, d[2].x], [d[0].y, d[1].y, d[2].y], [d[0].z, d[1].z, d[2].z]])
+    })
+  }
+
+  /// Returns true if the cell `i, j, k` is at least `margin` cells away from the boundary
+  fn is_interior((i, j, k): (i64, i64, i64), (n_x, n_y, n_z): (usize, usize, usize), margin: i64) -> bool {
+    let inside = |i: i64, n: usize| i >= margin && i < n as i64 - margin;
+    inside(i, n_x) && inside(j, n_y) && inside(k, n_z)
+  }
+
+  /// Interpolate the grid values at `position`, accumulated using `f`, or returns None when
+  /// the interpolation stencil is not within `margin` cells from the boundary of the grid
+  fn interpolate<R, F>(&self, position: &Point3<f64>, margin: i64, f: F) -> Result<Option<R>, Box<dyn std::error::Error>>
+  where R: std::ops::Add<Output = R> + Default,
+        F: Fn(usize, f64) -> R
+  {
+    let Some(grid) = self.grid.as_ref() else { return Ok(None) };
+    let (x, y, z) = grid.cell_coordinates(position);
+    let i = &self.interpolation;
+    let (wx, wy, wz) = (i.weights(x), i.weights(y), i.weights(z));
+    let n = grid.n_cells_ijk();
+    let corners = [(wx[0].0, wy[0].0, wz[0].0), (wx[wx.len() - 1].0, wy[wy.len() - 1].0, wz[wz.len() - 1].0)];
+    if !corners.iter().all(|c| VortonToVelocityVic::is_interior(*c, n, margin)) { return Ok(None); }
+    let mut r = R::default();
+    for (k, w_k) in wz.iter() {
+      for (j, w_j) in wy.iter() {
+        for (i, w_i) in wx.iter() {
+          let index = grid.cell_index(*i, *j, *k).ok_or("Position outside of VIC grid")?;
+          r = r + f(index, w_i * w_j * w_k);
+        }
+      }
+    }
+    Ok(Some(r))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::VortonToVelocitySimpleBuilder;
+
+  #[test]
+  fn it_matches_simple() -> Result<(), Box<dyn std::error::Error>> {
+    // Smooth distribution of vorticity sampled on a regular lattice of vortons
+    let h = 0.05;
+    let vortons = (0..32768)
+      .map(|n| {
+        let p = Point3::new((n % 32) as f64 * h - 0.775, ((n / 32) % 32) as f64 * h - 0.775, (n / 1024) as f64 * h - 0.775);
+        let r2 = (&p - &Point3::origin()).norm().powi(2);
+        Vorton::new(p.clone(), Vector3::new(-p.y, p.x, 0.5).scale((-4.0 * r2).exp()), h * h * h)
+      })
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).kernel(Kernel::Gaussian).build()?;
+    let vic = VortonToVelocityVicBuilder::default().vortons(&vortons).velocity(&velocity).kernel(Kernel::Gaussian).build()?.initialize()?;
+    let (mut error, mut norm) = (0.0f64, 0.0f64);
+    let (mut gradient_error, mut gradient_norm) = (0.0f64, 0.0f64);
+    for p in [Point3::new(0.13, -0.21, 0.05), Point3::new(-0.4, 0.33, -0.27), Point3::new(0.5, 0.5, 0.5), Point3::new(0.02, 0.61, -0.11)] {
+      let (u_vic, u_simple) = (vic.velocity_at(&p)?, simple.velocity_at(&p)?);
+      error = error.max((u_vic - u_simple.clone()).norm()); norm = norm.max((u_simple - velocity.clone()).norm());
+      let (g_vic, g_simple) = (vic.velocity_gradient_at(&p)?, simple.velocity_gradient_at(&p)?);
+      gradient_error = gradient_error.max((g_vic - g_simple.clone()).norm()); gradient_norm = gradient_norm.max(g_simple.norm());
+    }
+    println!("velocity {error} / {norm}, gradient {gradient_error} / {gradient_norm}");
+    assert!(error < 0.05 * norm);
+    assert!(gradient_error < 0.05 * gradient_norm);
+
+    // Positions outside of the grid are evaluated by direct summation
+    let p = Point3::new(3.0, 0.0, 0.0);
+    assert!((vic.velocity_at(&p)? - simple.velocity_at(&p)?).norm() < 1e-12);
+
+    // The Green's function is reused on a grid of the same size
+    let green = vic.green();
+    let again = VortonToVelocityVicBuilder::default().vortons(&vortons).velocity(&velocity).kernel(Kernel::Gaussian)
+      .green(green.clone()).build()?.initialize()?;
+    assert!(Arc::ptr_eq(green.as_ref().ok_or("No Green's function")?, again.green().as_ref().ok_or("No Green's function")?));
+    let p = Point3::new(0.13, -0.21, 0.05);
+    assert_eq!(again.velocity_at(&p)?.x, vic.velocity_at(&p)?.x);
+
+    // A stray vorton would make the grid too large
+    let mut stray = vortons.clone();
+    stray.push(Vorton::new(Point3::new(100.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), h * h * h));
+    assert!(VortonToVelocityVicBuilder::default().vortons(&stray).velocity(&velocity).build()?.initialize().is_err());
+    Ok(())
+  }
+}
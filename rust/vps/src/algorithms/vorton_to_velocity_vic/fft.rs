@@ -0,0 +1,21 @@
+use rustfft::{FftPlanner, FftDirection, num_complex::Complex};
+
+/// Three dimensional discrete Fourier transform of `data`, stored with `x` varying fastest,
+/// ie at index `i + n_x (j + n_y k)`. The inverse transform is not normalised.
+pub fn fft3(planner: &mut FftPlanner<f64>, data: &mut [Complex<f64>], (n_x, n_y, n_z): (usize, usize, usize), direction: FftDirection) {
+  // Along x, the lines are contiguous
+  planner.plan_fft(n_x, direction).process(data);
+
+  // Along y and z, the lines are gathered in a buffer before being transformed
+  let mut buffer = vec![Complex::default(); data.len()];
+  let index = |i: usize, j: usize, k: usize| i + n_x * (j + n_y * k);
+  let fft = planner.plan_fft(n_y, direction);
+  for k in 0..n_z { for i in 0..n_x { for j in 0..n_y { buffer[(k * n_x + i) * n_y + j] = data[index(i, j, k)]; } } }
+  fft.process(&mut buffer);
+  for k in 0..n_z { for i in 0..n_x { for j in 0..n_y { data[index(i, j, k)] = buffer[(k * n_x + i) * n_y + j]; } } }
+
+  let fft = planner.plan_fft(n_z, direction);
+  for j in 0..n_y { for i in 0..n_x { for k in 0..n_z { buffer[(j * n_x + i) * n_z + k] = data[index(i, j, k)]; } } }
+  fft.process(&mut buffer);
+  for j in 0..n_y { for i in 0..n_x { for k in 0..n_z { data[index(i, j, k)] = buffer[(j * n_x + i) * n_z + k]; } } }
+}
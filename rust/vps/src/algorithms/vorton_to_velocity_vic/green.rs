@@ -0,0 +1,44 @@
+use rustfft::{FftPlanner, FftDirection, num_complex::Complex};
+
+use super::fft::fft3;
+
+/// Discrete Fourier transform of the Green's function `1/(4π|r|)` on a padded grid of
+/// `padded` cells of size `spacing`. It only depends on the size of the grid, so it is kept
+/// from one evaluation to the next while the grid keeps its size.
+#[derive(Debug)]
+pub struct GreenFunction {
+  padded: (usize, usize, usize),
+  spacing: f64,
+  values: Vec<Complex<f64>>,
+}
+
+impl GreenFunction {
+  /// Returns the transform of the Green's function, scaled by the cell volume, with distances
+  /// wrapped around the padded grid. The singular value at the origin is replaced by the mean
+  /// of `1/(4π|r|)` over a sphere of the cell volume.
+  pub fn new(planner: &mut FftPlanner<f64>, padded: (usize, usize, usize), spacing: f64) -> GreenFunction {
+    let mut values = vec![Complex::default(); padded.0 * padded.1 * padded.2];
+    let wrap = |i: usize, n: usize| i.min(n - i) as f64 * spacing;
+    let radius = (3.0 / (4.0 * std::f64::consts::PI)).cbrt() * spacing;
+    let volume = spacing * spacing * spacing;
+    for k in 0..padded.2 {
+      for j in 0..padded.1 {
+        for i in 0..padded.0 {
+          let r = (wrap(i, padded.0).powi(2) + wrap(j, padded.1).powi(2) + wrap(k, padded.2).powi(2)).sqrt();
+          let g = if r == 0.0 { 1.5 / radius } else { 1.0 / r };
+          values[i + padded.0 * (j + padded.1 * k)] = Complex::new(g * volume / (4.0 * std::f64::consts::PI), 0.0);
+        }
+      }
+    }
+    fft3(planner, &mut values, padded, FftDirection::Forward);
+    GreenFunction { padded, spacing, values }
+  }
+
+  /// Returns true when the Green's function applies to the padded grid of `padded` cells of
+  /// size `spacing`
+  pub fn is_for(&self, padded: (usize, usize, usize), spacing: f64) -> bool {
+    self.padded == padded && self.spacing == spacing
+  }
+
+  pub fn values(&self) -> &[Complex<f64>] { &self.values }
+}
@@ -1,27 +1,45 @@
 use serde::{Serialize, Deserialize};
 
 use crate::algebra::{Point3, Vector3};
+use crate::sim::{Diffusion, Kernel, Periodicity, Seeding, Emitter};
 
 pub mod vortexring;
 pub use vortexring::VortexRing;
 pub mod empty;
+pub mod buoyantblob;
+pub use buoyantblob::BuoyantBlob;
 
 pub trait InitialConditions {
     fn free_stream_velocity(&self) -> Vector3<f64>;
     fn domain(&self) -> (Point3<f64>, Point3<f64>);
     fn vorticity(&self, p: &Point3<f64>) -> Vector3<f64>;
+    /// Density perturbation, relative to the reference density, used by the buoyancy
+    fn density(&self, _p: &Point3<f64>) -> f64 { 0.0 }
 }
 
+// The variant names are the keys of the json case files
+#[allow(clippy::enum_variant_names)]
 #[derive(Serialize, Deserialize, Clone)]
 pub enum InitialConditionData {
     InitialConditionVortexRing(VortexRing),
     InitialConditionEmpty(empty::Empty),
+    InitialConditionBuoyantBlob(BuoyantBlob),
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Domain {
     pub min: Point3<f64>,
     pub max: Point3<f64>,
+    /// Axes along which the domain is periodic
+    #[serde(default)]
+    pub periodic: [bool; 3],
+}
+
+impl Domain {
+    /// Returns the periodic boundary conditions of the domain, if any axis is periodic
+    pub fn periodicity(&self) -> Option<Periodicity> {
+        self.periodic.iter().any(|p| *p).then(|| Periodicity { min: self.min.clone(), max: self.max.clone(), periodic: self.periodic })
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -30,6 +48,28 @@ pub struct Configuration {
     pub initial_conditions: InitialConditionData,
     pub domain: Domain,
     pub viscosity: f64,
+    #[serde(default)]
+    pub diffusion: Diffusion,
+    #[serde(default)]
+    pub kernel: Kernel,
+    /// Gravity acceleration. Buoyancy is accounted for when either the gravity or the
+    /// diffusivity of the density perturbation is not zero.
+    #[serde(default)]
+    pub gravity: Vector3<f64>,
+    #[serde(default)]
+    pub diffusivity: f64,
+    /// Tracers seeded at the start of the simulation
+    #[serde(default)]
+    pub tracers: Vec<Seeding>,
+    /// Emitters releasing tracers during the simulation
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Configuration::new()
+    }
 }
 
 impl Configuration {
@@ -45,8 +85,14 @@ impl Configuration {
                     thickness: 0.5,
                 }
                 ),
-            domain: Domain { min: Point3::<f64>::new(0.0, 0.0, 0.0), max: Point3::<f64>::new(1.0, 1.0, 1.0) },
-            viscosity: 1e-5
+            domain: Domain { min: Point3::<f64>::new(0.0, 0.0, 0.0), max: Point3::<f64>::new(1.0, 1.0, 1.0), periodic: [false; 3] },
+            viscosity: 1e-5,
+            diffusion: Diffusion::default(),
+            kernel: Kernel::default(),
+            gravity: Vector3::default(),
+            diffusivity: 0.0,
+            tracers: Vec::new(),
+            emitters: Vec::new(),
         }
     }
 
@@ -54,8 +100,14 @@ impl Configuration {
         Configuration {
             n_vortons: 0,
             initial_conditions: InitialConditionData::InitialConditionEmpty(empty::Empty::new()),
-            domain: Domain { min: Point3::<f64>::new(0.0, 0.0, 0.0), max: Point3::<f64>::new(1.0, 1.0, 1.0) },
-            viscosity: 1e-5
+            domain: Domain { min: Point3::<f64>::new(0.0, 0.0, 0.0), max: Point3::<f64>::new(1.0, 1.0, 1.0), periodic: [false; 3] },
+            viscosity: 1e-5,
+            diffusion: Diffusion::default(),
+            kernel: Kernel::default(),
+            gravity: Vector3::default(),
+            diffusivity: 0.0,
+            tracers: Vec::new(),
+            emitters: Vec::new(),
         }
     }
 
@@ -63,6 +115,7 @@ impl Configuration {
         match &self.initial_conditions {
             InitialConditionData::InitialConditionVortexRing(v) => Box::new(v),
             InitialConditionData::InitialConditionEmpty(v) => Box::new(v),
+            InitialConditionData::InitialConditionBuoyantBlob(v) => Box::new(v),
         }
     }
 }
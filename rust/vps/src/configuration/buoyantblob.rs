@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::{Point3, Vector3};
+
+use crate::configuration::{InitialConditions};
+
+/// Spherical blob of fluid at rest with a density perturbation `density` (relative to the
+/// reference density, negative for a light blob) at its centre, decreasing smoothly to
+/// zero at `radius`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BuoyantBlob {
+    pub center: Point3<f64>,
+    pub radius: f64,
+    pub density: f64,
+}
+
+impl InitialConditions for BuoyantBlob {
+    fn free_stream_velocity(&self) -> Vector3<f64> {
+        Vector3::<f64>::new(0.0, 0.0, 0.0)
+    }
+
+    fn domain(&self) -> (Point3<f64>, Point3<f64>) {
+        let extent = Vector3::new(1.0, 1.0, 1.0).scale(1.1*self.radius);
+        (&self.center - &extent, &self.center + &extent)
+    }
+
+    fn vorticity(&self, _p: &Point3<f64>) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    fn density(&self, p: &Point3<f64>) -> f64 {
+        let d = (p - &self.center).norm();
+        if d < self.radius {
+            self.density * (0.5 + 0.5*(std::f64::consts::PI * d / self.radius).cos())
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{parallel, Point3, Vector3, Vorton, Simulation};
+
+/// Integral quantities of the vorticity field used to monitor the conservation properties
+/// of a simulation. With `α = ωV` the strength of a vorton located at `x`:
+///  - total vorticity `Σ α`;
+///  - linear impulse `1/2 Σ x × α`;
+///  - angular impulse `1/3 Σ x × (x × α)`;
+///  - kinetic energy `Σ u·(x × α)`;
+///  - enstrophy `Σ |ω|² V`;
+///  - helicity `Σ u·α`;
+///
+/// where `u` is the velocity induced by the vortons, ie excluding the free stream velocity.
+/// In the absence of viscosity and boundaries the total vorticity and impulses are invariants
+/// of the flow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub iteration: usize,
+    pub time: f64,
+    pub n_vortons: usize,
+    pub total_vorticity: Vector3<f64>,
+    pub linear_impulse: Vector3<f64>,
+    pub angular_impulse: Vector3<f64>,
+    pub kinetic_energy: f64,
+    pub enstrophy: f64,
+    pub helicity: f64,
+}
+
+impl Diagnostics {
+    /// Evaluate the diagnostics of the current state of the simulation
+    pub fn new(simulation: &Simulation) -> Result<Diagnostics, Box<dyn std::error::Error>> {
+        let vorton_to_velocity = simulation.get_vorton_to_velocity()?;
+        let free_stream_velocity = simulation.free_stream_velocity();
+        Diagnostics::from_vortons(simulation.iteration(), simulation.time(), simulation.vortons(),
+            |p| Ok(vorton_to_velocity.velocity_at(p)? - free_stream_velocity.clone()))
+    }
+
+    /// Evaluate the diagnostics of a set of vortons, with `velocity` returning the velocity
+    /// induced by the vortons
+    fn from_vortons<F>(iteration: usize, time: f64, vortons: &[Vorton], velocity: F) -> Result<Diagnostics, Box<dyn std::error::Error>>
+    where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send
+    {
+        let velocities = parallel::try_map(vortons, |vorton| velocity(vorton.position()))?;
+        let mut diagnostics = Diagnostics {
+            iteration, time,
+            n_vortons: vortons.len(),
+            total_vorticity: Vector3::default(),
+            linear_impulse: Vector3::default(),
+            angular_impulse: Vector3::default(),
+            kinetic_energy: 0.0,
+            enstrophy: 0.0,
+            helicity: 0.0,
+        };
+        for (vorton, u) in vortons.iter().zip(velocities.iter()) {
+            let x = vorton.position() - &Point3::origin();
+            let alpha = vorton.vorticity().scale(vorton.volume());
+            let x_alpha = x.cross(&alpha);
+            diagnostics.total_vorticity = diagnostics.total_vorticity + alpha.clone();
+            diagnostics.linear_impulse = diagnostics.linear_impulse + x_alpha.scale(0.5);
+            diagnostics.angular_impulse = diagnostics.angular_impulse + x.cross(&x_alpha).scale(1.0 / 3.0);
+            diagnostics.kinetic_energy += u.dot(&x_alpha);
+            diagnostics.enstrophy += vorton.vorticity().dot(vorton.vorticity()) * vorton.volume();
+            diagnostics.helicity += u.dot(&alpha);
+        }
+        Ok(diagnostics)
+    }
+
+    /// Header of the CSV time history written using `to_csv`
+    pub fn csv_header() -> &'static str {
+        "iteration, time, vortons, \
+         total vorticity x, total vorticity y, total vorticity z, \
+         linear impulse x, linear impulse y, linear impulse z, \
+         angular impulse x, angular impulse y, angular impulse z, \
+         kinetic energy, enstrophy, helicity\n"
+    }
+
+    /// Returns the diagnostics as a line of the CSV time history
+    pub fn to_csv(&self) -> String {
+        let (w, i, a) = (&self.total_vorticity, &self.linear_impulse, &self.angular_impulse);
+        format!("{}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}\n",
+                self.iteration, self.time, self.n_vortons,
+                w.x, w.y, w.z, i.x, i.y, i.z, a.x, a.y, a.z,
+                self.kinetic_energy, self.enstrophy, self.helicity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{VortonToVelocity, VortonToVelocitySimpleBuilder};
+
+  /// A thin vortex ring of radius `R` and circulation `Γ` in the plane `x = 0` has a linear
+  /// impulse `π R² Γ` along `x` and no total vorticity, angular impulse or helicity.
+  #[test]
+  fn it_evaluates_vortex_ring_invariants() -> Result<(), Box<dyn std::error::Error>> {
+    let (radius, circulation, n) = (1.0, 2.0, 200);
+    let volume = 1e-4;
+    let vortons = (0..n)
+      .map(|i| {
+        let theta = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+        let tangent = Vector3::new(0.0, -theta.sin(), theta.cos());
+        Vorton::new(Point3::new(0.0, radius * theta.cos(), radius * theta.sin()),
+                    tangent.scale(circulation * 2.0 * std::f64::consts::PI * radius / (n as f64 * volume)), volume)
+      })
+      .collect::<Vec<Vorton>>();
+    let velocity = Vector3::default();
+    let vorton_to_velocity = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+
+    let diagnostics = Diagnostics::from_vortons(0, 0.0, &vortons, |p| vorton_to_velocity.velocity_at(p))?;
+    println!("{diagnostics:?}");
+    let expected = std::f64::consts::PI * radius * radius * circulation;
+    assert!(diagnostics.total_vorticity.norm() < 1e-10);
+    assert!((diagnostics.linear_impulse.clone() - Vector3::new(expected, 0.0, 0.0)).norm() < 1e-10);
+    assert!(diagnostics.angular_impulse.norm() < 1e-10);
+    assert!(diagnostics.kinetic_energy > 0.0);
+    assert!(diagnostics.helicity.abs() < 1e-8);
+    assert_eq!(diagnostics.to_csv().split(',').count(), Diagnostics::csv_header().split(',').count());
+    Ok(())
+  }
+}
@@ -1,37 +1,78 @@
-use crate::{Vorton, Point3, Vector3};
+use crate::{Kernel, Vorton, Point3, Vector3};
 
+mod transform; pub use transform::Transform;
+mod surface;
 mod sphere; pub use sphere::Sphere;
 mod cube; pub use cube::Cube;
+mod cylinder; pub use cylinder::Cylinder;
+mod torus; pub use torus::Torus;
+mod flat_plate; pub use flat_plate::FlatPlate;
+mod ground_plane; pub use ground_plane::GroundPlane;
+mod mesh; pub use mesh::{Mesh, Triangle};
+mod kinematics; pub use kinematics::{Kinematics, Trajectory};
+mod moving; pub use moving::Moving;
+mod panels; pub use panels::{Panel, Panels};
+
+/// Axis aligned bounding box defined by its `(min, max)` corners
+pub type BoundingBox = (Point3<f64>, Point3<f64>);
 
 pub trait GeometryTrait {
   fn step(&mut self, _time_step: f64) -> Result<(), Box<dyn std::error::Error>> { Ok(()) }
-  fn bounding_box(&self) -> Result<Option<(Point3<f64>, Point3<f64>)>, Box<dyn std::error::Error>>;
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>>;
   fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>>;
   fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>>;
-  fn enforce<F>(&self, f: F) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>;
+  /// Generate the vortons bringing the velocity `f` of the fluid to the velocity `value` of
+  /// the surface
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send;
+  /// Returns the panels discretising the surface for the panel method
+  fn panels(&self) -> Vec<Panel>;
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub enum Geometry {
   Sphere(Sphere),
   Cube(Cube),
+  Cylinder(Cylinder),
+  Torus(Torus),
+  FlatPlate(FlatPlate),
+  GroundPlane(GroundPlane),
+  Mesh(Mesh),
+  Moving(Moving),
 }
 
 impl Geometry {
   pub fn cube() -> Geometry { Geometry::Cube(Cube::default()) }
 
+  pub fn sphere(center: Point3<f64>, radius: f64) -> Geometry { Geometry::Sphere(Sphere::new(center, radius)) }
+
+  /// Returns the geometry moved by the `kinematics`
+  pub fn moving(self, kinematics: Kinematics) -> Geometry { Geometry::Moving(Moving::new(self, kinematics)) }
+
   pub fn step(&mut self, time_step: f64) -> Result<(), Box<dyn std::error::Error>> {
     match self {
       Geometry::Sphere(sphere) => sphere.step(time_step),
       Geometry::Cube(cube) =>     cube.step(time_step),
+      Geometry::Cylinder(cylinder) => cylinder.step(time_step),
+      Geometry::Torus(torus) =>   torus.step(time_step),
+      Geometry::FlatPlate(plate) => plate.step(time_step),
+      Geometry::GroundPlane(plane) => plane.step(time_step),
+      Geometry::Mesh(mesh) =>     mesh.step(time_step),
+      Geometry::Moving(moving) => moving.step(time_step),
     }
   }
 
-  pub fn bounding_box(&self) -> Result<Option<(Point3<f64>, Point3<f64>)>, Box<dyn std::error::Error>> {
+  pub fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
     match self {
       Geometry::Sphere(sphere) => sphere.bounding_box(),
       Geometry::Cube(cube) =>     cube.bounding_box(),
+      Geometry::Cylinder(cylinder) => cylinder.bounding_box(),
+      Geometry::Torus(torus) =>   torus.bounding_box(),
+      Geometry::FlatPlate(plate) => plate.bounding_box(),
+      Geometry::GroundPlane(plane) => plane.bounding_box(),
+      Geometry::Mesh(mesh) =>     mesh.bounding_box(),
+      Geometry::Moving(moving) => moving.bounding_box(),
     }
   }
 
@@ -39,6 +80,12 @@ impl Geometry {
     match self {
       Geometry::Sphere(sphere) => sphere.is_inside(point),
       Geometry::Cube(cube) =>     cube.is_inside(point),
+      Geometry::Cylinder(cylinder) => cylinder.is_inside(point),
+      Geometry::Torus(torus) =>   torus.is_inside(point),
+      Geometry::FlatPlate(plate) => plate.is_inside(point),
+      Geometry::GroundPlane(plane) => plane.is_inside(point),
+      Geometry::Mesh(mesh) =>     mesh.is_inside(point),
+      Geometry::Moving(moving) => moving.is_inside(point),
     }
   }
 
@@ -46,17 +93,49 @@ impl Geometry {
     match self {
       Geometry::Sphere(sphere) => sphere.intersect(start, end),
       Geometry::Cube(cube) =>     cube.intersect(start, end),
+      Geometry::Cylinder(cylinder) => cylinder.intersect(start, end),
+      Geometry::Torus(torus) =>   torus.intersect(start, end),
+      Geometry::FlatPlate(plate) => plate.intersect(start, end),
+      Geometry::GroundPlane(plane) => plane.intersect(start, end),
+      Geometry::Mesh(mesh) =>     mesh.intersect(start, end),
+      Geometry::Moving(moving) => moving.intersect(start, end),
     }
   }
 
-  pub fn enforce<F>(&self, f: F) -> Result<Vec<Vorton>, Box<dyn std::error::Error>> 
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>
+  /// Generate the vortons enforcing the no-slip condition on a body at rest
+  pub fn enforce<F>(&self, kernel: &Kernel, f: F) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send
+  {
+    self.enforce_with_velocity(kernel, f, |_| Vector3::default())
+  }
+
+  pub fn enforce_with_velocity<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
   {
     match self {
-      Geometry::Sphere(sphere) => sphere.enforce(f),
-      Geometry::Cube(cube) =>     cube.enforce(f),
+      Geometry::Sphere(sphere) => sphere.enforce(kernel, f, value),
+      Geometry::Cube(cube) =>     cube.enforce(kernel, f, value),
+      Geometry::Cylinder(cylinder) => cylinder.enforce(kernel, f, value),
+      Geometry::Torus(torus) =>   torus.enforce(kernel, f, value),
+      Geometry::FlatPlate(plate) => plate.enforce(kernel, f, value),
+      Geometry::GroundPlane(plane) => plane.enforce(kernel, f, value),
+      Geometry::Mesh(mesh) =>     mesh.enforce(kernel, f, value),
+      Geometry::Moving(moving) => moving.enforce(kernel, f, value),
     }
   }
 
+  pub fn panels(&self) -> Vec<Panel> {
+    match self {
+      Geometry::Sphere(sphere) => sphere.panels(),
+      Geometry::Cube(cube) =>     cube.panels(),
+      Geometry::Cylinder(cylinder) => cylinder.panels(),
+      Geometry::Torus(torus) =>   torus.panels(),
+      Geometry::FlatPlate(plate) => plate.panels(),
+      Geometry::GroundPlane(plane) => plane.panels(),
+      Geometry::Mesh(mesh) =>     mesh.panels(),
+      Geometry::Moving(moving) => moving.panels(),
+    }
+  }
 }
 
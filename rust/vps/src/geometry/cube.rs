@@ -1,146 +1,142 @@
-use crate::{Point3, Vector3, Vorton, GeometryTrait};
+use crate::{Point3, Vector3, Vorton, Kernel};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+/// Box of dimensions `extents` along the axes of its local frame, centred on the origin of
+/// the local frame, and placed by `transform`. The velocity is corrected at the centres of
+/// a division of each face into cells of at most `spacing`. The default is the unit box
+/// `[0, 1]³` with faces divided into 4 by 4 cells.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Cube {
+  transform: Transform,
+  extents: Vector3<f64>,
+  spacing: f64,
+}
 
-#[derive(serde::Deserialize, serde::Serialize, Default)]
-pub struct Cube {}
+impl Default for Cube {
+  fn default() -> Self {
+    Cube {
+      transform: Transform::new(Point3::new(0.5, 0.5, 0.5), Vector3::default()),
+      extents: Vector3::new(1.0, 1.0, 1.0),
+      spacing: 0.25,
+    }
+  }
+}
 
 impl super::GeometryTrait for Cube {
-  fn bounding_box(&self) -> Result<Option<(Point3<f64>, Point3<f64>)>, Box<dyn std::error::Error>> {
-    Ok(Some((Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))))
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let half = self.extents.scale(0.5);
+    Ok(Some(self.transform.bounding_box(&(Point3::origin() - half.clone(), Point3::origin() + half))))
   }
 
   fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
-    let v = point - &Point3::new(0.0, 0.0, 0.0);
+    let p = self.transform.to_local(point);
     Ok(
-       0.0 <= v.x && v.x <= 1.0
-    && 0.0 <= v.y && v.y <= 1.0
-    && 0.0 <= v.z && v.z <= 1.0
+       p.x.abs() <= 0.5 * self.extents.x
+    && p.y.abs() <= 0.5 * self.extents.y
+    && p.z.abs() <= 0.5 * self.extents.z
     )
   }
 
+  /// Returns the first point of the segment `[start, end]` on a face of the box, clipping
+  /// the segment by the slabs bounded by each pair of faces in the local frame
   fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
-    if self.is_inside(start)? != self.is_inside(end)? {
-      Err("Algorithm to be implemented".into())
-    } else {
-      Ok(None)
+    let (a, b) = (self.transform.to_local(start), self.transform.to_local(end));
+    let d = &b - &a;
+    let (mut enter, mut exit) = (f64::NEG_INFINITY, f64::INFINITY);
+    for (a, d, half) in [(a.x, d.x, 0.5 * self.extents.x), (a.y, d.y, 0.5 * self.extents.y), (a.z, d.z, 0.5 * self.extents.z)] {
+      if d.abs() < 1e-300 {
+        if a.abs() > half { return Ok(None); }
+      } else {
+        let (t0, t1) = ((-half - a) / d, (half - a) / d);
+        enter = enter.max(t0.min(t1)); exit = exit.min(t0.max(t1));
+      }
     }
+    if enter > exit { return Ok(None); }
+    Ok(surface::first_crossing([enter, exit]).map(|t| start + &(end - start).scale(t)))
   }
 
-  fn enforce<F>(&self, f: F) -> Result<Vec<Vorton>, Box<dyn std::error::Error>> 
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>
+  /// Generate vortons forming a vortex sheet that cancels the velocity at the samples
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
   {
-    let n = 4; let delta = 1.0 / n as f64;
-    let mut r = Vec::new();
-    // front face x = 0
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new(0.0, (i as f64 + 0.5)*delta, (j as f64 + 0.5)*delta);
-    let n = Vector3::new(-1.0, 0.0, 0.0); let t1 = Vector3::new(0.0, 1.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
-    // Back face x = 1
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new(1.0, (i as f64 + 0.5)*delta, (j as f64 + 0.5)*delta);
-    let n = Vector3::new(1.0, 0.0, 0.0); let t1 = Vector3::new(0.0, 1.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
-    // Face y = 0
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new((i as f64 + 0.5)*delta, 0.0, (j as f64 + 0.5)*delta);
-    let n = Vector3::new(0.0,-1.0, 0.0); let t1 = Vector3::new(1.0, 0.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
-    // Face y = 1
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new((i as f64 + 0.5)*delta, 1.0, (j as f64 + 0.5)*delta);
-    let n = Vector3::new(0.0, 1.0, 0.0); let t1 = Vector3::new(1.0, 0.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
-    // Face z = 0
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new((i as f64 + 0.5)*delta, (j as f64 + 0.5)*delta, 0.0);
-    let n = Vector3::new(0.0, 0.0,-1.0); let t1 = Vector3::new(1.0, 0.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
-    // Face z = 1
-    for i in 0..n {
-      for j in 0..n {
-        let p = Point3::new((i as f64 + 0.5)*delta, (j as f64 + 0.5)*delta, 1.0);
-    let n = Vector3::new(0.0, 0.0, 1.0); let t1 = Vector3::new(1.0, 0.0, 0.0);
-        if let Some(v) = self.correct_at(&p, &n, &t1, &Vector3::new(0.0, 0.0, 0.0), &f)? { r.push(v); }
-      }
-    }
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing, f, value)
+  }
 
-    Ok(r)
+  fn panels(&self) -> Vec<Panel> {
+    let e = &self.extents;
+    surface::panels(&self.samples(), 2.0 * (e.x * e.y + e.y * e.z + e.z * e.x))
   }
 }
 
 impl Cube {
-  /// Generate a vorton that correct the velocity at the contact point to meet the provided value
-  fn correct_at<F>(&self, 
-                contact_point: &Point3<f64>, 
-                normal: &Vector3<f64>, 
-                tangent1: &Vector3<f64>,
-                value: &Vector3<f64>,
-                f: &F) -> Result<Option<Vorton>, Box<dyn std::error::Error>> 
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>
-  {
-    let current = f(contact_point)?;
-    let correction = (current + value.scale(-1.0)).scale(-1.0);
-    if correction.norm().abs() < 1e-6 { return Ok(None); }
-
-    let distance: f64 = 0.02; let radius = distance / 4.0; let volume = 4.0 / 3.0 * std::f64::consts::PI * radius.powi(3);
-
-    // Find where to put the new vorton
-    let d = correction.normalize();
-    let direction = 
-      if (normal.dot(&d).abs() - 1.0).abs() < 1e-6 {
-        tangent1.clone()
-      } else {
-        normal.cross(&d).normalize().cross(&d)
-      };
-    let mut position = contact_point + &direction.scale(distance);
-    if self.is_inside(&position)? { position = contact_point + &direction.scale(-distance); }
-    Ok(Some( Vorton::make_velocity_at(position, volume, (contact_point, &correction))? ))
+  pub fn new(transform: Transform, extents: Vector3<f64>) -> Cube {
+    Cube { transform, extents, ..Cube::default() }
   }
 
-  /// Generate a vorton based on the vorticity at a point next to the boundary
-  fn correct_at_v1<F>(contact_point: &Point3<f64>, 
-                normal: &Vector3<f64>, 
-                tangent1: &Vector3<f64>,
-                value: &Vector3<f64>,
-                f: &F) -> Result<Option<Vorton>, Box<dyn std::error::Error>> 
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>
-  {
-    if f(contact_point)?.norm().abs() < 1e-6 { return Ok(None); }
-
+  pub fn with_spacing(mut self, spacing: f64) -> Cube {
+    self.spacing = spacing; self
+  }
 
-    let distance: f64 = 0.01; let radius = distance / 4.0; let volume = 4.0 / 3.0 * std::f64::consts::PI * radius.powi(3);
-    let position = contact_point + &normal.scale(0.5 * distance);
-    let test_point = contact_point + &normal.scale(distance);
+  /// Returns the points of the faces where the velocity is corrected, with the outward
+  /// normal of the face
+  pub fn samples(&self) -> Vec<Sample> {
+    let e = [self.extents.x, self.extents.y, self.extents.z];
+    let axes = [Vector3::x(), Vector3::y(), Vector3::z()];
+    let mut samples = Vec::new();
+    // Faces normal to each axis `a`, spanned by the axes `u` and `v`
+    for (a, u, v) in [(0, 1, 2), (1, 0, 2), (2, 0, 1)] {
+      for side in [-1.0, 1.0] {
+        for su in surface::divide(e[u], self.spacing) {
+          for sv in surface::divide(e[v], self.spacing) {
+            let p = Point3::origin() + axes[a].scale(0.5 * side * e[a]) + axes[u].scale(su - 0.5 * e[u]) + axes[v].scale(sv - 0.5 * e[v]);
+            samples.push((self.transform.to_global(&p), self.transform.vector_to_global(&axes[a].scale(side))));
+          }
+        }
+      }
+    }
+    samples
+  }
+}
 
-    let tangent2 = normal.cross(&tangent1);
-    let convert = |v: &Vector3<f64>| {Vector3::new(v.dot(normal), v.dot(tangent1), v.dot(&tangent2))};
-    
-    let velocity = convert(&f(&test_point)?);
-    let derivative_distance = 0.001;
-    let velocity_tangent1 = convert(&f(&(&test_point + &tangent1.scale(derivative_distance)))?);
-    let velocity_tangent2 = convert(&f(&(&test_point + &tangent2.scale(derivative_distance)))?);
 
-    let vorticity 
-    =   normal.scale(0.5*(velocity_tangent1.z - velocity.z) / derivative_distance - 0.5*(velocity_tangent2.y - velocity.y) / derivative_distance)
-    + tangent1.scale(0.5*(velocity_tangent2.x - velocity.x) / derivative_distance - (velocity.z - value.z) / distance)
-    + tangent2.scale(    (velocity.y - value.y) / distance                        - 0.5*(velocity_tangent1.x - velocity.x) / derivative_distance);
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    // Box of 2 x 1 x 1 turned by a quarter about z, ie extending along y
+    let cube = Cube::new(Transform::new(Point3::new(1.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 0.5 * std::f64::consts::PI)), Vector3::new(2.0, 1.0, 1.0));
+    assert!(cube.is_inside(&Point3::new(1.4, 1.9, 0.4))? && !cube.is_inside(&Point3::new(1.9, 1.4, 0.0))?);
+    let p = cube.intersect(&Point3::new(1.2, -1.0, 0.1), &Point3::new(1.2, 1.0, 0.1))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.2, 0.0, 0.1)).norm() < 1e-12);
+    // From the inside
+    let p = cube.intersect(&Point3::new(1.0, 1.0, 0.0), &Point3::new(3.0, 1.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.5, 1.0, 0.0)).norm() < 1e-12);
+    assert!(cube.intersect(&Point3::new(0.0, 3.0, 0.0), &Point3::new(3.0, 3.0, 0.0))?.is_none());
+    // The default is the unit box with 16 samples per face
+    assert_eq!(Cube::default().samples().len(), 6 * 16);
+    assert!(Cube::default().is_inside(&Point3::new(0.9, 0.1, 0.5))?);
+    Ok(())
+  }
 
-    Ok(Some( Vorton::new(position, vorticity, volume) ))
+  /// Uniform flow past the box: the correction vortons, outside of the box, cancel the
+  /// velocity at the samples of the faces, less closely next to the edges and corners
+  #[test]
+  fn it_enforces_no_slip() -> Result<(), Box<dyn std::error::Error>> {
+    let cube = Cube::default();
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let kernel = Kernel::default();
+    let vortons = cube.enforce(&kernel, |_| Ok(velocity.clone()), |_| Vector3::default())?;
+    assert_eq!(vortons.len(), cube.samples().len());
+    assert!(vortons.iter().all(|v| !cube.is_inside(v.position()).unwrap()));
+    let velocity_at = |p: &Point3<f64>| vortons.iter().fold(velocity.clone(), |r, v| r + v.velocity_contribution(&kernel, p));
+    let mean_sample = cube.samples().iter().map(|(p, _)| velocity_at(p).norm()).sum::<f64>() / cube.samples().len() as f64;
+    println!("Velocity at samples: {mean_sample}");
+    assert!(mean_sample < 0.1);
+    Ok(())
   }
 }
-
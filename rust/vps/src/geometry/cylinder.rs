@@ -0,0 +1,133 @@
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+/// Closed cylinder of `radius` and `length` along the `z` axis of its local frame, centred
+/// on the origin of the local frame and placed by `transform`. The velocity is corrected on
+/// the side and the end caps at points about `spacing` apart.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Cylinder {
+  #[serde(default)]
+  transform: Transform,
+  radius: f64,
+  length: f64,
+  #[serde(default = "default_spacing")]
+  spacing: f64,
+}
+
+fn default_spacing() -> f64 { 0.1 }
+
+impl super::GeometryTrait for Cylinder {
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let half = Vector3::new(self.radius, self.radius, 0.5 * self.length);
+    Ok(Some(self.transform.bounding_box(&(Point3::origin() - half.clone(), Point3::origin() + half))))
+  }
+
+  fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    let p = self.transform.to_local(point);
+    Ok(p.x * p.x + p.y * p.y <= self.radius * self.radius && p.z.abs() <= 0.5 * self.length)
+  }
+
+  /// Returns the first point of the segment `[start, end]` on the side or on a cap
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let (a, b) = (self.transform.to_local(start), self.transform.to_local(end));
+    let d = &b - &a;
+    let at = |t: f64| &a + &d.scale(t);
+    let mut candidates = Vec::new();
+    // Side: `x² + y² = radius²` within the length
+    let qa = d.x * d.x + d.y * d.y;
+    let (qb, qc) = (a.x * d.x + a.y * d.y, a.x * a.x + a.y * a.y - self.radius * self.radius);
+    if qa > 1e-300 && qb * qb - qa * qc >= 0.0 {
+      let s = (qb * qb - qa * qc).sqrt();
+      candidates.extend([(-qb - s) / qa, (-qb + s) / qa].iter().filter(|t| at(**t).z.abs() <= 0.5 * self.length));
+    }
+    // Caps: `z = ±length/2` within the radius
+    if d.z.abs() > 1e-300 {
+      candidates.extend([-0.5 * self.length, 0.5 * self.length].iter()
+        .map(|z| (z - a.z) / d.z)
+        .filter(|t| { let p = at(*t); p.x * p.x + p.y * p.y <= self.radius * self.radius }));
+    }
+    Ok(surface::first_crossing(candidates).map(|t| start + &(end - start).scale(t)))
+  }
+
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing, f, value)
+  }
+
+  fn panels(&self) -> Vec<Panel> {
+    surface::panels(&self.samples(), 2.0 * std::f64::consts::PI * self.radius * (self.length + self.radius))
+  }
+}
+
+impl Cylinder {
+  pub fn new(transform: Transform, radius: f64, length: f64) -> Cylinder {
+    Cylinder { transform, radius, length, spacing: default_spacing() }
+  }
+
+  pub fn with_spacing(mut self, spacing: f64) -> Cylinder {
+    self.spacing = spacing; self
+  }
+
+  /// Returns the points of the side, on rings along the axis, and of the caps, on
+  /// concentric rings, where the velocity is corrected
+  pub fn samples(&self) -> Vec<Sample> {
+    let ring = |r: f64| {
+      let n = ((2.0 * std::f64::consts::PI * r / self.spacing).round() as usize).max(1);
+      (0..n).map(move |i| 2.0 * std::f64::consts::PI * i as f64 / n as f64)
+    };
+    let mut samples = Vec::new();
+    for z in surface::divide(self.length, self.spacing) {
+      for theta in ring(self.radius) {
+        let normal = Vector3::new(theta.cos(), theta.sin(), 0.0);
+        samples.push((Point3::new(self.radius * theta.cos(), self.radius * theta.sin(), z - 0.5 * self.length), normal));
+      }
+    }
+    for side in [-1.0, 1.0] {
+      for r in surface::divide(self.radius, self.spacing) {
+        for theta in ring(r) {
+          samples.push((Point3::new(r * theta.cos(), r * theta.sin(), 0.5 * side * self.length), Vector3::new(0.0, 0.0, side)));
+        }
+      }
+    }
+    samples.into_iter()
+      .map(|(p, n)| (self.transform.to_global(&p), self.transform.vector_to_global(&n)))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    // Cylinder along x
+    let cylinder = Cylinder::new(Transform::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.5 * std::f64::consts::PI, 0.0)), 0.5, 2.0);
+    assert!(cylinder.is_inside(&Point3::new(1.9, 0.4, 0.0))? && !cylinder.is_inside(&Point3::new(2.1, 0.0, 0.0))?);
+    let p = cylinder.intersect(&Point3::new(1.5, -2.0, 0.0), &Point3::new(1.5, 2.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.5, -0.5, 0.0)).norm() < 1e-12);
+    let p = cylinder.intersect(&Point3::new(3.0, 0.1, 0.2), &Point3::new(1.0, 0.1, 0.2))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(2.0, 0.1, 0.2)).norm() < 1e-12);
+    assert!(cylinder.intersect(&Point3::new(2.5, -2.0, 0.0), &Point3::new(2.5, 2.0, 0.0))?.is_none());
+    Ok(())
+  }
+
+  #[test]
+  fn it_enforces_no_slip() -> Result<(), Box<dyn std::error::Error>> {
+    let cylinder = Cylinder::new(Transform::default(), 0.25, 0.5).with_spacing(0.1);
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let kernel = Kernel::default();
+    let vortons = cylinder.enforce(&kernel, |_| Ok(velocity.clone()), |_| Vector3::default())?;
+    let samples = cylinder.samples();
+    assert_eq!(vortons.len(), samples.len());
+    let residual = samples.iter()
+      .map(|(p, _)| vortons.iter().fold(velocity.clone(), |r, v| r + v.velocity_contribution(&kernel, p)).norm())
+      .sum::<f64>() / samples.len() as f64;
+    println!("Residual velocity: {residual}");
+    assert!(residual < 0.1);
+    Ok(())
+  }
+}
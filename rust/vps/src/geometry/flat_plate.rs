@@ -0,0 +1,118 @@
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+/// Rectangular plate of negligible thickness, of dimensions `width` along `x` and `height`
+/// along `y` of its local frame, centred on the origin of the local frame and placed by
+/// `transform`. The velocity is corrected on both faces at points about `spacing` apart.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct FlatPlate {
+  #[serde(default)]
+  transform: Transform,
+  width: f64,
+  height: f64,
+  #[serde(default = "default_spacing")]
+  spacing: f64,
+}
+
+fn default_spacing() -> f64 { 0.1 }
+
+impl super::GeometryTrait for FlatPlate {
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let half = Vector3::new(0.5 * self.width, 0.5 * self.height, 0.0);
+    Ok(Some(self.transform.bounding_box(&(Point3::origin() - half.clone(), Point3::origin() + half))))
+  }
+
+  /// The plate has no volume
+  fn is_inside(&self, _point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(false)
+  }
+
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let (a, b) = (self.transform.to_local(start), self.transform.to_local(end));
+    if (a.z < 0.0) == (b.z < 0.0) { return Ok(None); }
+    let t = a.z / (a.z - b.z);
+    let p = &a + &(&b - &a).scale(t);
+    if p.x.abs() > 0.5 * self.width || p.y.abs() > 0.5 * self.height { return Ok(None); }
+    Ok(Some(start + &(end - start).scale(t)))
+  }
+
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing, f, value)
+  }
+
+  fn panels(&self) -> Vec<Panel> {
+    surface::panels(&self.samples(), 2.0 * self.width * self.height)
+  }
+}
+
+impl FlatPlate {
+  pub fn new(transform: Transform, width: f64, height: f64) -> FlatPlate {
+    FlatPlate { transform, width, height, spacing: default_spacing() }
+  }
+
+  pub fn with_spacing(mut self, spacing: f64) -> FlatPlate {
+    self.spacing = spacing; self
+  }
+
+  /// Returns the points of both faces where the velocity is corrected
+  pub fn samples(&self) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for side in [-1.0, 1.0] {
+      for x in surface::divide(self.width, self.spacing) {
+        for y in surface::divide(self.height, self.spacing) {
+          samples.push((self.transform.to_global(&Point3::new(x - 0.5 * self.width, y - 0.5 * self.height, 0.0)),
+                        self.transform.vector_to_global(&Vector3::new(0.0, 0.0, side))));
+        }
+      }
+    }
+    samples
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    // Plate of 2 x 1 in the plane x = 1, extending along z and y
+    let plate = FlatPlate::new(Transform::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.5 * std::f64::consts::PI, 0.0)), 2.0, 1.0);
+    assert!(!plate.is_inside(&Point3::new(1.0, 0.0, 0.0))?);
+    let p = plate.intersect(&Point3::new(0.0, 0.2, 0.8), &Point3::new(2.0, 0.2, 0.8))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.0, 0.2, 0.8)).norm() < 1e-12);
+    let p = plate.intersect(&Point3::new(1.5, -0.4, 0.0), &Point3::new(0.5, 0.0, -0.2))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.0, -0.2, -0.1)).norm() < 1e-12);
+    // Across the plane outside of the plate, and on one side of the plate
+    assert!(plate.intersect(&Point3::new(0.0, 0.6, 0.0), &Point3::new(2.0, 0.6, 0.0))?.is_none());
+    assert!(plate.intersect(&Point3::new(0.0, 0.0, 1.2), &Point3::new(2.0, 0.0, 1.2))?.is_none());
+    assert!(plate.intersect(&Point3::new(1.5, 0.0, 0.0), &Point3::new(1.1, 0.3, 0.5))?.is_none());
+    // Samples on both faces, with opposite normals
+    let samples = plate.samples();
+    assert_eq!(samples.len(), 2 * 20 * 10);
+    let (upper, lower) = samples.split_at(samples.len() / 2);
+    assert!(upper.iter().zip(lower).all(|((p, n), (q, m))| (p - q).norm() < 1e-12 && (n.clone() + m.clone()).norm() < 1e-12));
+    Ok(())
+  }
+
+  /// Flow along and across the plate: the vortons on both sides cancel the velocity at the
+  /// samples
+  #[test]
+  fn it_enforces_no_slip() -> Result<(), Box<dyn std::error::Error>> {
+    let plate = FlatPlate::new(Transform::default(), 0.5, 0.5);
+    let velocity = Vector3::new(1.0, 0.0, 0.3);
+    let kernel = Kernel::default();
+    let vortons = plate.enforce(&kernel, |_| Ok(velocity.clone()), |_| Vector3::default())?;
+    let samples = plate.samples();
+    assert_eq!(vortons.len(), samples.len());
+    let residual = samples.iter()
+      .map(|(p, _)| vortons.iter().fold(velocity.clone(), |r, v| r + v.velocity_contribution(&kernel, p)).norm())
+      .sum::<f64>() / samples.len() as f64;
+    println!("Residual velocity: {residual}");
+    assert!(residual < 0.1);
+    Ok(())
+  }
+}
@@ -0,0 +1,93 @@
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+/// Infinite plane through the origin of its local frame, placed by `transform`, with the
+/// fluid on the side of the local `z` axis. The velocity is corrected at points about
+/// `spacing` apart within the square of half width `extent` centred on the origin.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct GroundPlane {
+  #[serde(default)]
+  transform: Transform,
+  #[serde(default = "default_extent")]
+  extent: f64,
+  #[serde(default = "default_spacing")]
+  spacing: f64,
+}
+
+fn default_extent() -> f64 { 1.0 }
+fn default_spacing() -> f64 { 0.25 }
+
+impl Default for GroundPlane {
+  fn default() -> Self {
+    GroundPlane { transform: Transform::default(), extent: default_extent(), spacing: default_spacing() }
+  }
+}
+
+impl super::GeometryTrait for GroundPlane {
+  /// The plane is unbounded
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    Ok(None)
+  }
+
+  fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(self.transform.to_local(point).z < 0.0)
+  }
+
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let (a, b) = (self.transform.to_local(start).z, self.transform.to_local(end).z);
+    if (a < 0.0) == (b < 0.0) { return Ok(None); }
+    Ok(Some(start + &(end - start).scale(a / (a - b))))
+  }
+
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing, f, value)
+  }
+
+  fn panels(&self) -> Vec<Panel> {
+    surface::panels(&self.samples(), 4.0 * self.extent * self.extent)
+  }
+}
+
+impl GroundPlane {
+  pub fn new(transform: Transform) -> GroundPlane {
+    GroundPlane { transform, ..GroundPlane::default() }
+  }
+
+  pub fn with_extent(mut self, extent: f64) -> GroundPlane {
+    self.extent = extent; self
+  }
+
+  pub fn with_spacing(mut self, spacing: f64) -> GroundPlane {
+    self.spacing = spacing; self
+  }
+
+  /// Returns the points of the plane where the velocity is corrected
+  pub fn samples(&self) -> Vec<Sample> {
+    let normal = self.transform.vector_to_global(&Vector3::z());
+    let cells = surface::divide(2.0 * self.extent, self.spacing);
+    cells.iter()
+      .flat_map(|x| cells.iter().map(move |y| Point3::new(x - self.extent, y - self.extent, 0.0)))
+      .map(|p| (self.transform.to_global(&p), normal.clone()))
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    // Plane `x + z = 0`, the fluid being on the side of positive `x + z`
+    let plane = GroundPlane::new(Transform::new(Point3::origin(), Vector3::new(0.0, 0.25 * std::f64::consts::PI, 0.0)));
+    assert!(plane.is_inside(&Point3::new(-1.0, 5.0, 0.5))? && !plane.is_inside(&Point3::new(1.0, -5.0, 0.0))?);
+    let p = plane.intersect(&Point3::new(-1.0, 3.0, 0.0), &Point3::new(1.0, 3.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(0.0, 3.0, 0.0)).norm() < 1e-12);
+    assert!(plane.intersect(&Point3::new(1.0, 3.0, 0.0), &Point3::new(2.0, 3.0, 0.0))?.is_none());
+    Ok(())
+  }
+}
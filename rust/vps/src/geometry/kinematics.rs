@@ -0,0 +1,192 @@
+use std::convert::TryFrom;
+
+use crate::{Point3, Vector3};
+use super::Transform;
+
+/// Time step of the central differences giving the velocity of the body from its pose
+const DIFFERENCE_STEP: f64 = 1e-6;
+
+/// Prescribed motion of a body, as the pose of the body frame at each time
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+pub enum Kinematics {
+  /// Translation at constant `velocity`
+  Translation { velocity: Vector3<f64> },
+  /// Rotation about the line through `center` along `axis` at the angular velocity `omega`,
+  /// in radians per unit time
+  Rotation {
+    #[serde(default)]
+    center: Point3<f64>,
+    axis: Vector3<f64>,
+    omega: f64,
+  },
+  /// Sinusoidal heave, a displacement of amplitude `heave`, and pitch about `center`, a
+  /// rotation vector of amplitude `pitch`, at `frequency` oscillations per unit time. The
+  /// pitch leads the heave by `phase` radians.
+  Oscillation {
+    #[serde(default)]
+    center: Point3<f64>,
+    #[serde(default)]
+    heave: Vector3<f64>,
+    #[serde(default)]
+    pitch: Vector3<f64>,
+    frequency: f64,
+    #[serde(default)]
+    phase: f64,
+  },
+  /// Tabulated trajectory
+  Trajectory(Trajectory),
+}
+
+impl Kinematics {
+  /// Returns the pose of the body frame at `time`
+  pub fn pose(&self, time: f64) -> Transform {
+    match self {
+      Kinematics::Translation { velocity } => Transform::new(Point3::origin() + velocity.scale(time), Vector3::default()),
+      Kinematics::Rotation { center, axis, omega } =>
+        about(center, &Vector3::default(), axis.normalize().scale(omega * time)),
+      Kinematics::Oscillation { center, heave, pitch, frequency, phase } => {
+        let angle = 2.0 * std::f64::consts::PI * frequency * time;
+        about(center, &heave.scale(angle.sin()), pitch.scale((angle + phase).sin()))
+      },
+      Kinematics::Trajectory(trajectory) => trajectory.pose(time),
+    }
+  }
+
+  /// Returns the velocity at `time` of the point of the body at the global position `point`,
+  /// from central differences of the pose
+  pub fn velocity(&self, time: f64, point: &Point3<f64>) -> Vector3<f64> {
+    let p = self.pose(time).to_local(point);
+    let (before, after) = (self.pose(time - DIFFERENCE_STEP).to_global(&p), self.pose(time + DIFFERENCE_STEP).to_global(&p));
+    (after - before).scale(0.5 / DIFFERENCE_STEP)
+  }
+}
+
+/// Returns the pose rotating by `rotation` about `center`, then moving by `displacement`
+fn about(center: &Point3<f64>, displacement: &Vector3<f64>, rotation: Vector3<f64>) -> Transform {
+  let rotated = Transform::new(Point3::origin(), rotation.clone()).vector_to_global(&(center - &Point3::origin()));
+  Transform::new(&(center + displacement) - &rotated, rotation)
+}
+
+/// Trajectory tabulated at increasing times, interpolated linearly between the times and
+/// held constant before the first and after the last. The rotation vectors are interpolated
+/// component by component, which is accurate when the rotation changes little between two
+/// times.
+///
+/// The trajectory is read from a CSV file with the columns `time, x, y, z, rx, ry, rz`, the
+/// position of the origin and the rotation vector of the body frame, and an optional header
+/// line. Like a mesh, it is saved as the path of the file, read again on restart, or as the
+/// poses when it was not read from a file.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "TrajectoryDefinition", into = "TrajectoryDefinition")]
+pub struct Trajectory {
+  path: Option<String>,
+  poses: Vec<(f64, Transform)>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct TrajectoryDefinition {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  path: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  poses: Vec<(f64, Transform)>,
+}
+
+impl TryFrom<TrajectoryDefinition> for Trajectory {
+  type Error = String;
+  fn try_from(definition: TrajectoryDefinition) -> Result<Self, Self::Error> {
+    match definition.path {
+      Some(path) => Trajectory::from_file(&path).map_err(|e| e.to_string()),
+      None => Trajectory::new(definition.poses).map_err(|e| e.to_string()),
+    }
+  }
+}
+
+impl From<Trajectory> for TrajectoryDefinition {
+  fn from(trajectory: Trajectory) -> Self {
+    let poses = if trajectory.path.is_some() { Vec::new() } else { trajectory.poses };
+    TrajectoryDefinition { path: trajectory.path, poses }
+  }
+}
+
+impl Trajectory {
+  pub fn new(poses: Vec<(f64, Transform)>) -> Result<Trajectory, Box<dyn std::error::Error>> {
+    if poses.is_empty() { return Err("The trajectory has no pose".into()); }
+    if poses.windows(2).any(|w| w[1].0 <= w[0].0) { return Err("The times of the trajectory are not increasing".into()); }
+    Ok(Trajectory { path: None, poses })
+  }
+
+  /// Read the trajectory from a CSV file
+  pub fn from_file(path: &str) -> Result<Trajectory, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut poses = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+      if line.trim().is_empty() { continue; }
+      let values = line.split(',').map(|v| v.trim().parse::<f64>()).collect::<Result<Vec<f64>, _>>();
+      match values {
+        Ok(v) if v.len() == 7 =>
+          poses.push((v[0], Transform::new(Point3::new(v[1], v[2], v[3]), Vector3::new(v[4], v[5], v[6])))),
+        Err(_) if i == 0 => continue,
+        _ => return Err(format!("{path}:{}: expected 7 numbers: time, x, y, z, rx, ry, rz", i + 1).into()),
+      }
+    }
+    Ok(Trajectory { path: Some(path.to_string()), ..Trajectory::new(poses)? })
+  }
+
+  pub fn poses(&self) -> &[(f64, Transform)] { &self.poses }
+
+  /// Returns the pose interpolated at `time`
+  pub fn pose(&self, time: f64) -> Transform {
+    let i = self.poses.partition_point(|(t, _)| *t <= time);
+    if i == 0 { return self.poses[0].1.clone(); }
+    if i == self.poses.len() { return self.poses[i - 1].1.clone(); }
+    let ((t0, a), (t1, b)) = (&self.poses[i - 1], &self.poses[i]);
+    let s = (time - t0) / (t1 - t0);
+    Transform::new(&a.origin + &(&b.origin - &a.origin).scale(s), a.rotation.clone() + (b.rotation.clone() - a.rotation.clone()).scale(s))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_moves_bodies() {
+    let pi = std::f64::consts::PI;
+    let translation = Kinematics::Translation { velocity: Vector3::new(1.0, 2.0, 0.0) };
+    assert!((translation.pose(2.0).to_global(&Point3::new(0.0, 0.0, 1.0)) - Point3::new(2.0, 4.0, 1.0)).norm() < 1e-12);
+    assert!((translation.velocity(2.0, &Point3::new(5.0, 0.0, 0.0)) - Vector3::new(1.0, 2.0, 0.0)).norm() < 1e-6);
+
+    // Quarter turn about the vertical line through (1, 0, 0) after one unit of time
+    let rotation = Kinematics::Rotation { center: Point3::new(1.0, 0.0, 0.0), axis: Vector3::new(0.0, 0.0, 2.0), omega: 0.5 * pi };
+    assert!((rotation.pose(1.0).to_global(&Point3::new(2.0, 0.0, 0.0)) - Point3::new(1.0, 1.0, 0.0)).norm() < 1e-12);
+    // Velocity `omega x r`
+    let v = rotation.velocity(0.3, &Point3::new(1.0, -2.0, 0.5));
+    assert!((v - Vector3::new(pi, 0.0, 0.0)).norm() < 1e-6);
+
+    let oscillation = Kinematics::Oscillation { center: Point3::origin(), heave: Vector3::new(0.0, 0.0, 0.1),
+                                                pitch: Vector3::new(0.0, 0.2, 0.0), frequency: 0.5, phase: 0.5 * pi };
+    // At the top of the heave, the pitch is back to zero and the body moves by rotation only
+    let pose = oscillation.pose(0.5);
+    assert!((pose.origin.clone() - Point3::new(0.0, 0.0, 0.1)).norm() < 1e-12 && pose.rotation.norm() < 1e-12);
+    let v = oscillation.velocity(0.5, &Point3::new(1.0, 0.0, 0.1));
+    assert!((v - Vector3::new(0.0, 0.0, 0.2 * pi)).norm() < 1e-6);
+  }
+
+  #[test]
+  fn it_interpolates_trajectories() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join(format!("vps-trajectory-{}.csv", std::process::id()));
+    std::fs::write(&path, "time, x, y, z, rx, ry, rz\n0, 0, 0, 0, 0, 0, 0\n1, 1, 0, 0, 0, 0, 0\n3, 1, 2, 0, 0, 0, 1\n")?;
+    let trajectory = Trajectory::from_file(&path.to_string_lossy())?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(trajectory.poses().len(), 3);
+    assert!((trajectory.pose(-1.0).origin.clone() - Point3::origin()).norm() < 1e-12);
+    assert!((trajectory.pose(0.5).origin.clone() - Point3::new(0.5, 0.0, 0.0)).norm() < 1e-12);
+    let pose = trajectory.pose(2.0);
+    assert!((pose.origin.clone() - Point3::new(1.0, 1.0, 0.0)).norm() < 1e-12 && (pose.rotation.z - 0.5).abs() < 1e-12);
+    assert!((trajectory.pose(5.0).origin.clone() - Point3::new(1.0, 2.0, 0.0)).norm() < 1e-12);
+    let v = Kinematics::Trajectory(trajectory).velocity(0.5, &Point3::new(0.0, 3.0, 0.0));
+    assert!((v - Vector3::new(1.0, 0.0, 0.0)).norm() < 1e-6);
+    assert!(Trajectory::new(vec![(1.0, Transform::default()), (1.0, Transform::default())]).is_err());
+    Ok(())
+  }
+}
@@ -0,0 +1,356 @@
+use std::convert::TryFrom;
+
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+mod bvh; use bvh::Bvh;
+mod file;
+
+/// Triangle given by its vertices, ordered counterclockwise seen from outside
+pub type Triangle = [Point3<f64>; 3];
+
+/// Cluster of triangles in the local frame: the centroid, the mean outward normal and the area
+type Cluster = (Point3<f64>, Vector3<f64>, f64);
+
+/// Directions of the rays cast to count the crossings of the surface, chosen away from the
+/// axes and diagonals to avoid grazing the edges of axis aligned meshes
+const RAYS: [[f64; 3]; 3] = [
+  [1.0, 0.7548776662466927, 0.5698402909980532],
+  [-0.5698402909980532, 1.0, 0.7548776662466927],
+  [0.7548776662466927, -0.5698402909980532, 1.0],
+];
+
+/// Smallest barycentric coordinate of a crossing counted by a ray, closer crossings grazing
+/// an edge or a vertex, where the crossing may be counted on several triangles or none
+const MARGIN: f64 = 1e-9;
+
+/// Maximum number of samples, bounding the size of the dense system solved by `enforce`
+const MAX_SAMPLES: usize = 1024;
+
+/// Closed triangle mesh, read from an STL or OBJ file, or made from triangles, and placed
+/// by `transform`. The triangles are sorted in a bounding volume hierarchy accelerating the
+/// segment queries. The velocity is corrected at samples about `spacing` apart, clustering
+/// the triangles facing the same way in cells of a grid of that size. The spacing defaults
+/// to the size of the triangles, and is widened to keep at most `MAX_SAMPLES` samples.
+///
+/// The mesh is saved as the path of the file, which is read again on restart, or as the
+/// triangles when the mesh was not read from a file.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "MeshDefinition", into = "MeshDefinition")]
+pub struct Mesh {
+  path: Option<String>,
+  transform: Transform,
+  triangles: Vec<Triangle>,
+  spacing: Option<f64>,
+  bvh: Bvh,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct MeshDefinition {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  path: Option<String>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  triangles: Vec<Triangle>,
+  #[serde(default)]
+  transform: Transform,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  spacing: Option<f64>,
+}
+
+impl TryFrom<MeshDefinition> for Mesh {
+  type Error = String;
+  fn try_from(definition: MeshDefinition) -> Result<Self, Self::Error> {
+    let mesh = match definition.path {
+      Some(path) => Mesh::from_file(&path, definition.transform).map_err(|e| e.to_string())?,
+      None => Mesh::new(definition.triangles, definition.transform),
+    };
+    Ok(Mesh { spacing: definition.spacing, ..mesh })
+  }
+}
+
+impl From<Mesh> for MeshDefinition {
+  fn from(mesh: Mesh) -> Self {
+    let triangles = if mesh.path.is_some() { Vec::new() } else { mesh.triangles };
+    MeshDefinition { path: mesh.path, triangles, transform: mesh.transform, spacing: mesh.spacing }
+  }
+}
+
+impl super::GeometryTrait for Mesh {
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    Ok(self.bvh.bounding_box().map(|b| self.transform.bounding_box(&b)))
+  }
+
+  /// Returns true when most of the rays cast from `point` cross the surface an odd number of
+  /// times. Rays grazing an edge or a vertex are left out of the vote, and the winding number
+  /// of the surface decides when all rays graze it.
+  fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    let Some((min, max)) = self.bvh.bounding_box() else { return Ok(false) };
+    let p = self.transform.to_local(point);
+    if p.x < min.x || p.y < min.y || p.z < min.z || p.x > max.x || p.y > max.y || p.z > max.z { return Ok(false); }
+    let length = 2.0 * (&max - &min).norm();
+    let votes = RAYS.iter()
+      .filter_map(|[x, y, z]| self.parity(&p, &(&p + &Vector3::new(*x, *y, *z).normalize().scale(length))))
+      .collect::<Vec<bool>>();
+    if votes.is_empty() { return Ok(self.winding_number(&p) > 0.5); }
+    Ok(2 * votes.iter().filter(|odd| **odd).count() > votes.len())
+  }
+
+  /// Returns the first point of the segment `[start, end]` on a triangle
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let (a, b) = (self.transform.to_local(start), self.transform.to_local(end));
+    Ok(surface::first_crossing(self.crossings(&a, &b)).map(|t| start + &(end - start).scale(t)))
+  }
+
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    let (spacing, clusters) = self.clusters();
+    if clusters.is_empty() { return Ok(Vec::new()); }
+    let samples = clusters.into_iter()
+      .map(|(p, n, _)| (self.transform.to_global(&p), self.transform.vector_to_global(&n)))
+      .collect::<Vec<Sample>>();
+    surface::enforce_no_slip(kernel, &samples, spacing, f, value)
+  }
+
+  /// Returns a panel per sample, of the area of the triangles of its cluster
+  fn panels(&self) -> Vec<Panel> {
+    self.clusters().1.into_iter()
+      .map(|(p, n, area)| Panel::new(self.transform.to_global(&p), self.transform.vector_to_global(&n), area))
+      .collect()
+  }
+}
+
+impl Mesh {
+  pub fn new(triangles: Vec<Triangle>, transform: Transform) -> Mesh {
+    let bvh = Bvh::new(&triangles);
+    Mesh { path: None, transform, triangles, spacing: None, bvh }
+  }
+
+  pub fn with_spacing(mut self, spacing: f64) -> Mesh {
+    self.spacing = Some(spacing); self
+  }
+
+  /// Read the mesh from an ASCII or binary STL file, or from an OBJ file
+  pub fn from_file(path: &str, transform: Transform) -> Result<Mesh, Box<dyn std::error::Error>> {
+    let triangles = file::read(path)?;
+    if triangles.is_empty() { return Err(format!("The mesh {path} has no triangle").into()); }
+    Ok(Mesh { path: Some(path.to_string()), ..Mesh::new(triangles, transform) })
+  }
+
+  pub fn triangles(&self) -> &[Triangle] { &self.triangles }
+
+  /// Returns the centroids of the clusters of triangles with their outward normals
+  pub fn samples(&self) -> Vec<Sample> {
+    self.clusters().1.into_iter()
+      .map(|(p, n, _)| (self.transform.to_global(&p), self.transform.vector_to_global(&n)))
+      .collect()
+  }
+
+  /// Returns the spacing of the samples: the given spacing, or the size of the triangles from
+  /// their mean area, widened so that the surface holds at most about `MAX_SAMPLES` cells
+  fn spacing(&self) -> f64 {
+    let area = self.triangles.iter().map(|[a, b, c]| 0.5 * (b - a).cross(&(c - a)).norm()).sum::<f64>();
+    let spacing = self.spacing.unwrap_or_else(|| (area / self.triangles.len().max(1) as f64).sqrt());
+    spacing.max((area / MAX_SAMPLES as f64).sqrt())
+  }
+
+  /// Returns the spacing of the samples and the clusters of the triangles in the cells of a
+  /// grid of that spacing, widened until there are at most `MAX_SAMPLES` clusters.
+  /// Degenerate triangles, and clusters where the normals cancel out, are ignored.
+  fn clusters(&self) -> (f64, Vec<Cluster>) {
+    let Some((min, _)) = self.bvh.bounding_box() else { return (self.spacing(), Vec::new()) };
+    // Centroids relative to the corner of the mesh, and normals scaled by the areas
+    let triangles = self.triangles.iter()
+      .map(|[a, b, c]| ((a - &min) + ((b - a) + (c - a)).scale(1.0 / 3.0), (b - a).cross(&(c - a)).scale(0.5)))
+      .filter(|(_, n)| n.norm() >= 1e-300)
+      .collect::<Vec<_>>();
+    let (spacing, groups) = surface::cluster(&triangles, self.spacing(), MAX_SAMPLES);
+    let clusters = groups.iter()
+      .map(|group| {
+        let area = group.iter().map(|i| triangles[*i].1.norm()).sum::<f64>();
+        let (centroid, normal) = group.iter()
+          .fold((Vector3::default(), Vector3::default()), |(c, n), i| {
+            let (p, m) = &triangles[*i];
+            (c + p.scale(m.norm() / area), n + m.clone())
+          });
+        (&min + &centroid, normal, area)
+      })
+      .filter(|(_, n, area)| n.norm() > 1e-6 * area)
+      .map(|(c, n, area)| (c, n.normalize(), area))
+      .collect();
+    (spacing, clusters)
+  }
+
+  /// Returns the parameters `t` in `[0, 1]` where the local segment `[start, end]` crosses
+  /// the triangles
+  fn crossings(&self, start: &Point3<f64>, end: &Point3<f64>) -> Vec<f64> {
+    let d = end - start;
+    let mut crossings = Vec::new();
+    self.bvh.visit_segment(start, end, |i| {
+      if let Some((t, _)) = segment_triangle(start, &d, &self.triangles[i]) { crossings.push(t); }
+    });
+    crossings
+  }
+
+  /// Returns true when the local segment `[start, end]` crosses the triangles an odd number
+  /// of times, or `None` when it grazes an edge or a vertex
+  fn parity(&self, start: &Point3<f64>, end: &Point3<f64>) -> Option<bool> {
+    let d = end - start;
+    let (mut count, mut grazes) = (0, false);
+    self.bvh.visit_segment(start, end, |i| {
+      if let Some((_, margin)) = segment_triangle(start, &d, &self.triangles[i]) {
+        count += 1; grazes |= margin < MARGIN;
+      }
+    });
+    (!grazes).then_some(count % 2 == 1)
+  }
+
+  /// Returns the winding number of the surface around the local point `p`, one inside and
+  /// zero outside, summing the solid angles of the triangles seen from `p` (Van Oosterom and
+  /// Strackee)
+  fn winding_number(&self, p: &Point3<f64>) -> f64 {
+    self.triangles.iter()
+      .map(|[a, b, c]| {
+        let (a, b, c) = (a - p, b - p, c - p);
+        let (la, lb, lc) = (a.norm(), b.norm(), c.norm());
+        let denominator = la * lb * lc + a.dot(&b) * lc + b.dot(&c) * la + c.dot(&a) * lb;
+        2.0 * a.dot(&b.cross(&c)).atan2(denominator)
+      })
+      .sum::<f64>() / (4.0 * std::f64::consts::PI)
+  }
+}
+
+/// Returns the parameter `t` in `[0, 1]` where the segment `start + t d` crosses the
+/// triangle, using the Möller–Trumbore algorithm, with the smallest barycentric coordinate
+/// of the crossing
+fn segment_triangle(start: &Point3<f64>, d: &Vector3<f64>, [v0, v1, v2]: &Triangle) -> Option<(f64, f64)> {
+  let (e1, e2) = (v1 - v0, v2 - v0);
+  let p = d.cross(&e2);
+  let det = e1.dot(&p);
+  if det.abs() < 1e-300 { return None; }
+  let s = start - v0;
+  let u = s.dot(&p) / det;
+  if !(0.0..=1.0).contains(&u) { return None; }
+  let q = s.cross(&e1);
+  let v = d.dot(&q) / det;
+  if v < 0.0 || u + v > 1.0 { return None; }
+  let t = e2.dot(&q) / det;
+  (0.0..=1.0).contains(&t).then_some((t, u.min(v).min(1.0 - u - v)))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  /// Returns the triangles of the box `[0, 1] x [0, 2] x [0, 1]`, with 2 triangles per face
+  fn make_box() -> Vec<Triangle> {
+    let p = |i: usize| Point3::new((i & 1) as f64, 2.0 * ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64);
+    let faces = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+    faces.iter().flat_map(|f| [[p(f[0]), p(f[1]), p(f[2])], [p(f[0]), p(f[2]), p(f[3])]]).collect()
+  }
+
+  #[test]
+  fn it_queries_the_mesh() -> Result<(), Box<dyn std::error::Error>> {
+    let mesh = Mesh::new(make_box(), Transform::new(Point3::new(1.0, 0.0, 0.0), Vector3::default()));
+    let (min, max) = mesh.bounding_box()?.ok_or("No bounding box")?;
+    assert!((min - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-12 && (max - Point3::new(2.0, 2.0, 1.0)).norm() < 1e-12);
+    assert!(mesh.is_inside(&Point3::new(1.5, 1.0, 0.5))? && mesh.is_inside(&Point3::new(1.01, 1.99, 0.99))?);
+    assert!(!mesh.is_inside(&Point3::new(0.5, 1.0, 0.5))? && !mesh.is_inside(&Point3::new(1.5, 2.5, 0.5))?);
+    let p = mesh.intersect(&Point3::new(0.0, 0.5, 0.5), &Point3::new(3.0, 0.5, 0.5))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.0, 0.5, 0.5)).norm() < 1e-12);
+    assert!(mesh.intersect(&Point3::new(0.0, 3.0, 0.5), &Point3::new(3.0, 3.0, 0.5))?.is_none());
+    // Outward normals
+    assert!(mesh.samples().iter().all(|(p, n)| !mesh.is_inside(&(p + &n.scale(0.1))).unwrap_or(true)));
+    Ok(())
+  }
+
+  /// Points next to a corner, where the rays go through the corner or along the edges of
+  /// the faces, each split into two triangles by a diagonal
+  #[test]
+  fn it_is_robust_to_rays_through_vertices() -> Result<(), Box<dyn std::error::Error>> {
+    let mesh = Mesh::new(make_box(), Transform::default());
+    let corner = Point3::new(1.0, 2.0, 1.0);
+    let ray = Vector3::new(RAYS[0][0], RAYS[0][1], RAYS[0][2]).normalize();
+    for t in [0.1, 0.25, 0.3, 0.5] {
+      let p = &corner - &ray.scale(t);
+      assert!(mesh.is_inside(&p)?);
+      assert!(!mesh.is_inside(&(&corner + &ray.scale(t)))?);
+      assert!((mesh.winding_number(&p) - 1.0).abs() < 1e-12);
+    }
+    assert!(mesh.winding_number(&Point3::new(1.5, 1.0, 0.5)).abs() < 1e-12);
+    Ok(())
+  }
+
+  /// A finely divided box is sampled about `spacing` apart, and at most `MAX_SAMPLES` times
+  #[test]
+  fn it_clusters_the_triangles() -> Result<(), Box<dyn std::error::Error>> {
+    // The box `[0, 1] x [0, 2] x [0, 1]` with faces divided into squares of 1 / n
+    let n = 40;
+    let mut triangles = Vec::new();
+    for [a, b, c] in make_box() {
+      let (u, v) = ((&b - &a).scale(1.0 / n as f64), (&c - &a).scale(1.0 / n as f64));
+      let p = |i: usize, j: usize| &a + &(u.scale(i as f64) + v.scale(j as f64));
+      for i in 0..n {
+        for j in 0..n - i {
+          triangles.push([p(i, j), p(i + 1, j), p(i, j + 1)]);
+          if i + j + 1 < n { triangles.push([p(i + 1, j), p(i + 1, j + 1), p(i, j + 1)]); }
+        }
+      }
+    }
+    assert_eq!(triangles.len(), 12 * n * n);
+    let area = |panels: &[Panel]| panels.iter().map(|p| p.area).sum::<f64>();
+
+    let mesh = Mesh::new(triangles, Transform::default());
+    assert!(mesh.samples().len() <= MAX_SAMPLES);
+    assert!((area(&mesh.panels()) - 10.0).abs() < 1e-9);
+    let mesh = mesh.with_spacing(0.25);
+    // A sample per square of 0.25 of the faces
+    let samples = mesh.samples();
+    assert_eq!(samples.len(), 10 * 16);
+    assert!(samples.iter().all(|(p, n)| !mesh.is_inside(&(p + &n.scale(0.1))).unwrap_or(true)
+                                     && mesh.is_inside(&(p - &n.scale(0.1))).unwrap_or(false)));
+    assert!((area(&mesh.panels()) - 10.0).abs() < 1e-9);
+    Ok(())
+  }
+
+  #[test]
+  fn it_reads_stl_and_obj_files() -> Result<(), Box<dyn std::error::Error>> {
+    let triangles = make_box();
+    let dir = std::env::temp_dir().join(format!("vps-mesh-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let mut ascii = "solid box\n".to_string();
+    let mut binary = vec![0u8; 80]; binary.extend((triangles.len() as u32).to_le_bytes());
+    let mut obj = String::new();
+    for (i, t) in triangles.iter().enumerate() {
+      ascii += "facet normal 0 0 0\nouter loop\n";
+      binary.extend([0u8; 12]);
+      for p in t.iter() {
+        ascii += &format!("vertex {} {} {}\n", p.x, p.y, p.z);
+        for c in [p.x, p.y, p.z] { binary.extend((c as f32).to_le_bytes()); }
+        obj += &format!("v {} {} {}\n", p.x, p.y, p.z);
+      }
+      ascii += "endloop\nendfacet\n";
+      binary.extend([0u8; 2]);
+      obj += &format!("f {}/1 {} {}\n", 3 * i + 1, 3 * i + 2, -1);
+    }
+    ascii += "endsolid box\n";
+    std::fs::write(dir.join("ascii.stl"), ascii)?;
+    std::fs::write(dir.join("binary.stl"), binary)?;
+    std::fs::write(dir.join("box.obj"), obj)?;
+
+    for name in ["ascii.stl", "binary.stl", "box.obj"] {
+      let path = dir.join(name).to_string_lossy().to_string();
+      let mesh = Mesh::from_file(&path, Transform::default())?;
+      assert_eq!(mesh.triangles().len(), triangles.len());
+      assert!(mesh.triangles().iter().flatten().zip(triangles.iter().flatten()).all(|(a, b)| (a - b).norm() < 1e-6));
+      // Restarts read the file again
+      let restart = Mesh::try_from(MeshDefinition::from(mesh))?;
+      assert_eq!(restart.triangles().len(), triangles.len());
+    }
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+  }
+}
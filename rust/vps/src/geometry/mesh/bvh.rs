@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use crate::Point3;
+use super::{BoundingBox, Triangle};
+
+/// Maximum number of triangles of a leaf of the hierarchy
+const MAX_LEAF: usize = 4;
+
+/// Bounding volume hierarchy of the triangles of a mesh. Each node holds the box containing
+/// its triangles, and the triangles are split between the two children of a node at the
+/// median of their centroids along the longest side of the box.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+  nodes: Vec<Node>,
+  /// Indices of the triangles sorted by leaf
+  order: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+  min: Point3<f64>,
+  max: Point3<f64>,
+  content: Content,
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+  /// Range of the order of the hierarchy holding the triangles of the leaf
+  Leaf(Range<usize>),
+  /// Indices of the children nodes
+  Branch(usize, usize),
+}
+
+impl Bvh {
+  pub fn new(triangles: &[Triangle]) -> Bvh {
+    let mut bvh = Bvh { nodes: Vec::new(), order: (0..triangles.len()).collect() };
+    if !triangles.is_empty() { bvh.build(triangles, 0..triangles.len()); }
+    bvh
+  }
+
+  /// Returns the box containing all triangles
+  pub fn bounding_box(&self) -> Option<BoundingBox> {
+    self.nodes.first().map(|n| (n.min.clone(), n.max.clone()))
+  }
+
+  /// Calls `f` with the index of each triangle in a leaf whose box is crossed by the segment
+  /// `[start, end]`
+  pub fn visit_segment<F>(&self, start: &Point3<f64>, end: &Point3<f64>, mut f: F)
+  where F: FnMut(usize)
+  {
+    if self.nodes.is_empty() { return; }
+    let mut stack = vec![0];
+    while let Some(i) = stack.pop() {
+      let node = &self.nodes[i];
+      if !crosses_box(start, end, &node.min, &node.max) { continue; }
+      match &node.content {
+        Content::Leaf(range) => self.order[range.clone()].iter().for_each(|t| f(*t)),
+        Content::Branch(left, right) => { stack.push(*left); stack.push(*right); },
+      }
+    }
+  }
+
+  /// Make the node of the triangles of `range` of the order, and its descendants. Returns
+  /// the index of the node.
+  fn build(&mut self, triangles: &[Triangle], range: Range<usize>) -> usize {
+    let (min, max) = self.order[range.clone()].iter()
+      .flat_map(|t| triangles[*t].iter())
+      .fold((Point3::new(f64::MAX, f64::MAX, f64::MAX), Point3::new(f64::MIN, f64::MIN, f64::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)));
+    let index = self.nodes.len();
+    self.nodes.push(Node { min: min.clone(), max: max.clone(), content: Content::Leaf(range.clone()) });
+    if range.len() <= MAX_LEAF { return index; }
+
+    let d = &max - &min;
+    let axis = if d.x >= d.y && d.x >= d.z { 0 } else if d.y >= d.z { 1 } else { 2 };
+    let centroid = |t: &usize| {
+      let c = triangles[*t].iter().fold(0.0, |r, p| r + [p.x, p.y, p.z][axis]);
+      c / 3.0
+    };
+    let middle = range.len() / 2;
+    self.order[range.clone()].select_nth_unstable_by(middle, |a, b| centroid(a).total_cmp(&centroid(b)));
+    let left = self.build(triangles, range.start..range.start + middle);
+    let right = self.build(triangles, range.start + middle..range.end);
+    self.nodes[index].content = Content::Branch(left, right);
+    index
+  }
+}
+
+/// Returns true when the segment `[start, end]` crosses the box `(min, max)`
+fn crosses_box(start: &Point3<f64>, end: &Point3<f64>, min: &Point3<f64>, max: &Point3<f64>) -> bool {
+  let d = end - start;
+  let (mut enter, mut exit) = (0.0f64, 1.0f64);
+  for (a, d, min, max) in [(start.x, d.x, min.x, max.x), (start.y, d.y, min.y, max.y), (start.z, d.z, min.z, max.z)] {
+    if d.abs() < 1e-300 {
+      if a < min || a > max { return false; }
+    } else {
+      let (t0, t1) = ((min - a) / d, (max - a) / d);
+      enter = enter.max(t0.min(t1)); exit = exit.min(t0.max(t1));
+      if enter > exit { return false; }
+    }
+  }
+  true
+}
@@ -0,0 +1,90 @@
+use std::convert::TryInto;
+
+use crate::Point3;
+use super::Triangle;
+
+/// Read the triangles of an STL or OBJ file, selected from the extension of `path`
+pub fn read(path: &str) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+  let extension = std::path::Path::new(path).extension()
+    .and_then(|e| e.to_str()).map(|e| e.to_lowercase()).unwrap_or_default();
+  let bytes = std::fs::read(path).map_err(|e| format!("Unable to read mesh {path}: {e}"))?;
+  match extension.as_str() {
+    "stl" => read_stl(&bytes),
+    "obj" => read_obj(std::str::from_utf8(&bytes)?),
+    _ => Err(format!("Unsupported mesh format: {path}").into()),
+  }
+}
+
+/// Read an ASCII or binary STL file. A file starting with `solid` is read as ASCII unless
+/// its size matches the number of triangles of the binary header, as some exporters start
+/// binary headers with `solid` as well.
+pub fn read_stl(bytes: &[u8]) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+  let binary_size = bytes.get(80..84)
+    .map(|n| 84 + 50 * u32::from_le_bytes(n.try_into().unwrap_or_default()) as usize);
+  if bytes.starts_with(b"solid") && binary_size != Some(bytes.len()) {
+    read_ascii_stl(std::str::from_utf8(bytes)?)
+  } else {
+    read_binary_stl(bytes)
+  }
+}
+
+fn read_ascii_stl(text: &str) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+  let mut vertices = Vec::new();
+  let mut lines = text.lines().map(|l| l.split_whitespace().collect::<Vec<&str>>());
+  lines.try_for_each(|tokens| {
+    if tokens.first() == Some(&"vertex") { vertices.push(point(&tokens[1..])?); }
+    Ok::<(), Box<dyn std::error::Error>>(())
+  })?;
+  if vertices.len() % 3 != 0 { return Err("The STL facets are not triangles".into()); }
+  Ok(vertices.chunks(3).map(|v| [v[0].clone(), v[1].clone(), v[2].clone()]).collect())
+}
+
+fn read_binary_stl(bytes: &[u8]) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+  let n = u32::from_le_bytes(bytes.get(80..84).ok_or("Truncated STL header")?.try_into()?) as usize;
+  let f = |offset: usize| -> Result<f64, Box<dyn std::error::Error>> {
+    Ok(f32::from_le_bytes(bytes.get(offset..offset + 4).ok_or("Truncated STL file")?.try_into()?) as f64)
+  };
+  (0..n)
+    .map(|i| {
+      // The facet normal, ie the first 12 bytes, is recomputed from the vertices
+      let o = 84 + 50 * i + 12;
+      let p = |k: usize| -> Result<Point3<f64>, Box<dyn std::error::Error>> {
+        Ok(Point3::new(f(o + 12 * k)?, f(o + 12 * k + 4)?, f(o + 12 * k + 8)?))
+      };
+      Ok([p(0)?, p(1)?, p(2)?])
+    })
+    .collect()
+}
+
+/// Read the vertices and faces of an OBJ file. Polygonal faces are split into triangles
+/// sharing their first vertex.
+pub fn read_obj(text: &str) -> Result<Vec<Triangle>, Box<dyn std::error::Error>> {
+  let mut vertices = Vec::new();
+  let mut triangles = Vec::new();
+  for line in text.lines() {
+    let tokens = line.split_whitespace().collect::<Vec<&str>>();
+    match tokens.first() {
+      Some(&"v") => vertices.push(point(&tokens[1..])?),
+      Some(&"f") => {
+        // Indices are 1-based, or relative to the end of the vertices when negative, and
+        // may be followed by texture and normal indices
+        let face = tokens[1..].iter()
+          .map(|t| {
+            let i = t.split('/').next().unwrap_or_default().parse::<i64>()?;
+            let i = if i < 0 { vertices.len() as i64 + i } else { i - 1 };
+            vertices.get(i as usize).cloned().ok_or_else(|| format!("Invalid OBJ vertex index {t}").into())
+          })
+          .collect::<Result<Vec<Point3<f64>>, Box<dyn std::error::Error>>>()?;
+        for k in 2..face.len() { triangles.push([face[0].clone(), face[k - 1].clone(), face[k].clone()]); }
+      },
+      _ => (),
+    }
+  }
+  Ok(triangles)
+}
+
+fn point(tokens: &[&str]) -> Result<Point3<f64>, Box<dyn std::error::Error>> {
+  let c = tokens.iter().take(3).map(|t| t.parse::<f64>()).collect::<Result<Vec<f64>, _>>()?;
+  if c.len() < 3 { return Err("Vertex with less than 3 coordinates".into()); }
+  Ok(Point3::new(c[0], c[1], c[2]))
+}
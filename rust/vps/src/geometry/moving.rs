@@ -0,0 +1,107 @@
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Geometry, Kinematics, Panel};
+
+type VelocityFn<'a> = dyn Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send + 'a;
+
+/// Geometry moved by prescribed `kinematics`. The `geometry` is defined in the body frame,
+/// placed at each time by the pose of the kinematics, and the no-slip condition brings the
+/// velocity of the fluid to the velocity of the surface of the body.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Moving {
+  geometry: Box<Geometry>,
+  kinematics: Kinematics,
+  /// Time of the motion, advanced by `step`
+  #[serde(default)]
+  time: f64,
+}
+
+impl super::GeometryTrait for Moving {
+  fn step(&mut self, time_step: f64) -> Result<(), Box<dyn std::error::Error>> {
+    self.time += time_step;
+    self.geometry.step(time_step)
+  }
+
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let pose = self.kinematics.pose(self.time);
+    Ok(self.geometry.bounding_box()?.map(|b| pose.bounding_box(&b)))
+  }
+
+  fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    self.geometry.is_inside(&self.kinematics.pose(self.time).to_local(point))
+  }
+
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let pose = self.kinematics.pose(self.time);
+    Ok(self.geometry.intersect(&pose.to_local(start), &pose.to_local(end))?.map(|p| pose.to_global(&p)))
+  }
+
+  /// Enforce the no-slip condition in the body frame, where the velocities are expressed
+  /// with the axes of the body, and move the vortons back to the global frame
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    let pose = self.kinematics.pose(self.time);
+    // Trait objects stop the instantiation of ever nested closures for nested motions
+    let f: &VelocityFn = &|p| Ok(pose.vector_to_local(&f(&pose.to_global(p))?));
+    let value: &(dyn Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send) =
+      &|p| { let p = pose.to_global(p); pose.vector_to_local(&(value(&p) + self.kinematics.velocity(self.time, &p))) };
+    let vortons = self.geometry.enforce_with_velocity(kernel, f, value)?;
+    Ok(vortons.iter()
+       .map(|v| Vorton::new(pose.to_global(v.position()), pose.vector_to_global(v.vorticity()), v.volume()).with_density(v.density()))
+       .collect())
+  }
+
+  /// Returns the panels of the geometry placed by the pose, moving with the body
+  fn panels(&self) -> Vec<Panel> {
+    let pose = self.kinematics.pose(self.time);
+    self.geometry.panels().into_iter()
+      .map(|p| {
+        let center = pose.to_global(&p.center);
+        let velocity = pose.vector_to_global(&p.velocity) + self.kinematics.velocity(self.time, &center);
+        Panel::new(center, pose.vector_to_global(&p.normal), p.area).with_velocity(velocity)
+      })
+      .collect()
+  }
+}
+
+impl Moving {
+  pub fn new(geometry: Geometry, kinematics: Kinematics) -> Moving {
+    Moving { geometry: Box::new(geometry), kinematics, time: 0.0 }
+  }
+
+  pub fn time(&self) -> f64 { self.time }
+
+  pub fn kinematics(&self) -> &Kinematics { &self.kinematics }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_moves_with_the_body() -> Result<(), Box<dyn std::error::Error>> {
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let mut sphere = Moving::new(Geometry::sphere(Point3::origin(), 0.5), Kinematics::Translation { velocity: velocity.clone() });
+    sphere.step(0.5)?;
+    sphere.step(0.5)?;
+    assert!(sphere.is_inside(&Point3::new(1.4, 0.0, 0.0))? && !sphere.is_inside(&Point3::new(0.0, 0.0, 0.0))?);
+    let (min, max) = sphere.bounding_box()?.ok_or("No bounding box")?;
+    assert!((min - Point3::new(0.5, -0.5, -0.5)).norm() < 1e-12 && (max - Point3::new(1.5, 0.5, 0.5)).norm() < 1e-12);
+    let p = sphere.intersect(&Point3::new(-1.0, 0.0, 0.0), &Point3::new(3.0, 0.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(0.5, 0.0, 0.0)).norm() < 1e-12);
+
+    // In still fluid, the fluid at the surface is dragged along by the body
+    let kernel = Kernel::default();
+    let vortons = sphere.enforce(&kernel, |_| Ok(Vector3::default()), |_| Vector3::default())?;
+    assert!(!vortons.is_empty());
+    let samples = crate::Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5).samples();
+    let error = samples.iter()
+      .map(|(p, _)| (vortons.iter().fold(Vector3::default(), |r, v| r + v.velocity_contribution(&kernel, p)) - velocity.clone()).norm())
+      .sum::<f64>() / samples.len() as f64;
+    println!("Slip velocity: {error}");
+    assert!(error < 0.05);
+    Ok(())
+  }
+}
@@ -0,0 +1,358 @@
+use std::sync::Arc;
+
+use crate::{parallel, Point3, Vector3, Matrix3};
+use crate::algebra::dense;
+use super::surface;
+
+/// Regularisation of the linear system giving the strength of the panels
+const REGULARISATION: f64 = 1e-12;
+
+/// Maximum number of panels, beyond which neighbouring panels are agglomerated
+const MAX_PANELS: usize = 1024;
+
+/// Tolerance on the positions, relative to the size of the surface, and on the normals of
+/// panels matching the panels of a factorized system
+const TOLERANCE: f64 = 1e-9;
+
+/// Element of the surface of a geometry, of `area` around `center`, moving at `velocity`
+#[derive(Debug, Clone)]
+pub struct Panel {
+  pub center: Point3<f64>,
+  /// Outward normal
+  pub normal: Vector3<f64>,
+  pub area: f64,
+  pub velocity: Vector3<f64>,
+}
+
+impl Panel {
+  pub fn new(center: Point3<f64>, normal: Vector3<f64>, area: f64) -> Panel {
+    Panel { center, normal, area, velocity: Vector3::default() }
+  }
+
+  pub fn with_velocity(mut self, velocity: Vector3<f64>) -> Panel {
+    self.velocity = velocity; self
+  }
+
+  /// Radius of the core smoothing the velocity induced by the panel
+  fn core_radius(&self) -> f64 {
+    0.5 * self.area.sqrt()
+  }
+
+  /// Returns true when `point` is the center of the panel, or of a coincident panel such as
+  /// the other face of a plate
+  fn is_at(&self, point: &Point3<f64>) -> bool {
+    (point - &self.center).norm() < 1e-6 * self.area.sqrt()
+  }
+}
+
+/// Panel method enforcing the no-through-flow condition on the surface of geometries. Each
+/// panel carries a source, of strength per unit area `source`, and a tangential vortex
+/// sheet of strength `vortex`. The strengths are lumped at the centres of the panels and
+/// smoothed over a core of the size of the panel, so the velocity they induce is accurate
+/// a few panels away from the surface.
+///
+/// The strengths are the solution of smallest norm cancelling the normal velocity relative
+/// to each panel at its centre, with a total circulation of the vortex sheets of zero: closed
+/// bodies are mostly represented by sources, open surfaces such as plates, where the sources
+/// on both faces cancel out, by vortex sheets.
+///
+/// The factorized system is kept and reused while the panels move rigidly, as a whole, so
+/// that only the velocity relative to the panels is evaluated at each step for static bodies
+/// or a single moving body. Beyond `MAX_PANELS`, neighbouring panels are agglomerated.
+#[derive(Debug, Clone, Default)]
+pub struct Panels {
+  panels: Vec<Panel>,
+  sources: Vec<f64>,
+  vortices: Vec<Vector3<f64>>,
+  system: Option<Arc<System>>,
+}
+
+/// Frame attached to a set of panels, following them in a rigid motion: the centre of the
+/// first panel, with axes along its normal and towards the centre of another panel
+#[derive(Debug, Clone)]
+struct Frame {
+  origin: Point3<f64>,
+  axes: [Vector3<f64>; 3],
+}
+
+impl Frame {
+  fn new(panels: &[Panel]) -> Frame {
+    let origin = panels[0].center.clone();
+    let e1 = panels[0].normal.normalize();
+    let across = |p: &Panel| { let r = &p.center - &origin; r.clone() - e1.scale(r.dot(&e1)) };
+    let max = panels.iter().map(|p| across(p).norm()).fold(0.0, f64::max);
+    let e2 = panels.iter().map(across).find(|r| r.norm() > 1e-3 * max)
+      .map_or_else(|| surface::tangents(&e1).0, |r| r.normalize());
+    let e3 = e1.cross(&e2);
+    Frame { origin, axes: [e1, e2, e3] }
+  }
+
+  /// Returns the components of the global vector `v` along the axes
+  fn to_local(&self, v: &Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(v.dot(&self.axes[0]), v.dot(&self.axes[1]), v.dot(&self.axes[2]))
+  }
+
+  /// Returns the global vector of components `v` along the axes
+  fn to_global(&self, v: &Vector3<f64>) -> Vector3<f64> {
+    self.axes[0].scale(v.x) + self.axes[1].scale(v.y) + self.axes[2].scale(v.z)
+  }
+}
+
+/// Factorized system giving the strengths of the panels, with the centres, normals and
+/// areas of the panels and the tangents of the vortex sheets in the frame of the panels
+#[derive(Debug)]
+struct System {
+  panels: Vec<(Vector3<f64>, Vector3<f64>, f64)>,
+  tangents: Vec<(Vector3<f64>, Vector3<f64>)>,
+  size: f64,
+  solver: dense::MinimumNorm,
+}
+
+impl System {
+  fn new(panels: &[Panel], frame: &Frame) -> Result<System, Box<dyn std::error::Error>> {
+    let tangents = panels.iter().map(|p| surface::tangents(&p.normal)).collect::<Vec<_>>();
+    // Unknowns: source, then vortex sheet along both tangents, of each panel
+    let mut rows = parallel::map(panels, |target| {
+      panels.iter().zip(&tangents)
+        .flat_map(|(panel, (t1, t2))| {
+          let (source, vortex) = (Panels::influence(panel, &target.center, &target.normal, 1.0, &Vector3::default()),
+                                  |t: &Vector3<f64>| Panels::influence(panel, &target.center, &target.normal, 0.0, t));
+          [source.dot(&target.normal), vortex(t1).dot(&target.normal), vortex(t2).dot(&target.normal)]
+        })
+        .collect::<Vec<f64>>()
+    });
+    // Zero total circulation, scaled like the normal velocity rows
+    let area = panels.iter().map(|p| p.area).sum::<f64>() / panels.len() as f64;
+    for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+      rows.push(panels.iter().zip(&tangents)
+                .flat_map(|(p, (t1, t2))| [0.0, p.area / area * t1.dot(&axis), p.area / area * t2.dot(&axis)])
+                .collect());
+    }
+    Ok(System {
+      panels: panels.iter().map(|p| (frame.to_local(&(&p.center - &frame.origin)), frame.to_local(&p.normal), p.area)).collect(),
+      tangents: tangents.iter().map(|(t1, t2)| (frame.to_local(t1), frame.to_local(t2))).collect(),
+      size: (area * panels.len() as f64).sqrt(),
+      solver: dense::MinimumNorm::new(rows, REGULARISATION)?,
+    })
+  }
+
+  /// Returns true when the `panels`, seen from their `frame`, are the panels of the system.
+  /// The influence of the panels on each other, and the row space of the zero circulation
+  /// rows, are then those of the system with the tangents turned with the frame.
+  fn is_for(&self, panels: &[Panel], frame: &Frame) -> bool {
+    self.panels.len() == panels.len()
+    && self.panels.iter().zip(panels).all(|((center, normal, area), p)| {
+      (frame.to_local(&(&p.center - &frame.origin)) - center.clone()).norm() <= TOLERANCE * self.size
+      && (frame.to_local(&p.normal) - normal.clone()).norm() <= TOLERANCE
+      && (p.area - area).abs() <= TOLERANCE * area
+    })
+  }
+}
+
+impl Panels {
+  /// Solve for the strengths of the `panels` in the flow of velocity `f`, reusing the
+  /// factorized system of the `previous` panels when the panels only moved rigidly
+  pub fn solve<F>(panels: Vec<Panel>, previous: Option<&Panels>, f: F) -> Result<Panels, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send
+  {
+    if panels.is_empty() { return Ok(Panels::default()); }
+    let panels = Panels::agglomerate(panels);
+    let frame = Frame::new(&panels);
+    let system = match previous.and_then(|p| p.system.clone()).filter(|s| s.is_for(&panels, &frame)) {
+      Some(system) => system,
+      None => Arc::new(System::new(&panels, &frame)?),
+    };
+    let mut rhs = parallel::try_map(&panels, |p| Ok((p.velocity.clone() - f(&p.center)?).dot(&p.normal)))?;
+    rhs.extend([0.0; 3]);
+    let strengths = system.solver.solve(&rhs)?;
+    let (sources, vortices) = strengths.chunks(3).zip(&system.tangents)
+      .map(|(s, (t1, t2))| (s[0], frame.to_global(t1).scale(s[1]) + frame.to_global(t2).scale(s[2])))
+      .unzip();
+    Ok(Panels { panels, sources, vortices, system: Some(system) })
+  }
+
+  /// Returns the `panels`, agglomerated into groups of neighbouring panels facing the same way
+  /// when there are more than `MAX_PANELS`. The groups are made in the frame of the panels,
+  /// shifted by half a cell, so that the same panels are agglomerated while they move rigidly.
+  fn agglomerate(panels: Vec<Panel>) -> Vec<Panel> {
+    if panels.len() <= MAX_PANELS { return panels; }
+    let frame = Frame::new(&panels);
+    let spacing = (panels.iter().map(|p| p.area).sum::<f64>() / MAX_PANELS as f64).sqrt();
+    let shift = Vector3::new(0.5, 0.5, 0.5).scale(spacing);
+    let elements = panels.iter()
+      .map(|p| (frame.to_local(&(&p.center - &frame.origin)) + shift.clone(), frame.to_local(&p.normal)))
+      .collect::<Vec<_>>();
+    let (_, groups) = surface::cluster(&elements, spacing, MAX_PANELS);
+    groups.iter()
+      .filter_map(|group| {
+        let area = group.iter().map(|i| panels[*i].area).sum::<f64>();
+        let (offset, normal, velocity) = group.iter()
+          .fold((Vector3::default(), Vector3::default(), Vector3::default()), |(c, n, v), i| {
+            let p = &panels[*i]; let w = p.area / area;
+            (c + (&p.center - &frame.origin).scale(w), n + p.normal.scale(w), v + p.velocity.scale(w))
+          });
+        (normal.norm() > 1e-6).then(|| Panel::new(&frame.origin + &offset, normal.normalize(), area).with_velocity(velocity))
+      })
+      .collect()
+  }
+
+  pub fn panels(&self) -> &[Panel] { &self.panels }
+
+  /// Returns the source strength per unit area of each panel
+  pub fn sources(&self) -> &[f64] { &self.sources }
+
+  /// Returns the vortex sheet strength of each panel
+  pub fn vortices(&self) -> &[Vector3<f64>] { &self.vortices }
+
+  /// Returns the total circulation of the vortex sheets, zero up to round-off
+  pub fn circulation(&self) -> Vector3<f64> {
+    self.panels.iter().zip(&self.vortices).fold(Vector3::default(), |r, (p, v)| r + v.scale(p.area))
+  }
+
+  /// Returns the velocity induced by the panels at `position`
+  pub fn velocity_at(&self, position: &Point3<f64>) -> Vector3<f64> {
+    self.panels.iter().zip(self.sources.iter().zip(&self.vortices))
+      .fold(Vector3::default(), |r, (p, (s, v))| {
+        let (r_p, s2) = Panels::separation(p, position);
+        let factor = p.area / (4.0 * std::f64::consts::PI * s2 * s2.sqrt());
+        r + (r_p.scale(*s) + v.cross(&r_p)).scale(factor)
+      })
+  }
+
+  /// Returns the gradient of the velocity induced by the panels at `position`, with entry
+  /// `[i][j]` being `du_i/dx_j`
+  pub fn velocity_gradient_at(&self, position: &Point3<f64>) -> Matrix3<f64> {
+    let mut g = Matrix3::default();
+    for (p, (s, v)) in self.panels.iter().zip(self.sources.iter().zip(&self.vortices)) {
+      let (r, s2) = Panels::separation(p, position);
+      let factor = p.area / (4.0 * std::f64::consts::PI * s2 * s2.sqrt());
+      let u = r.scale(*s) + v.cross(&r);
+      let m = (Matrix3::identity().scale(*s) + Matrix3::cross_product(v)) + Matrix3::outer(&u, &r).scale(-3.0 / s2);
+      g = g + m.scale(factor);
+    }
+    g
+  }
+
+  /// Returns the velocity induced by the panels on the outer side of the panel `i`,
+  /// including the jump across the sheets at the centre of the panel
+  pub fn surface_velocity(&self, i: usize) -> Vector3<f64> {
+    let target = &self.panels[i];
+    self.panels.iter().zip(self.sources.iter().zip(&self.vortices))
+      .filter(|(p, _)| p.is_at(&target.center))
+      .fold(self.velocity_at(&target.center), |r, (p, (s, v))| r + Panels::jump(p, &target.normal, *s, v))
+  }
+
+  /// Returns the velocity induced at `position`, on the side `normal` of any coincident
+  /// sheet, by the `panel` with the strengths `source` and `vortex`
+  fn influence(panel: &Panel, position: &Point3<f64>, normal: &Vector3<f64>, source: f64, vortex: &Vector3<f64>) -> Vector3<f64> {
+    if panel.is_at(position) { return Panels::jump(panel, normal, source, vortex); }
+    let (r, s2) = Panels::separation(panel, position);
+    (r.scale(source) + vortex.cross(&r)).scale(panel.area / (4.0 * std::f64::consts::PI * s2 * s2.sqrt()))
+  }
+
+  /// Returns half the jump of the velocity across the sheets of the `panel`, seen from the
+  /// side `normal`
+  fn jump(panel: &Panel, normal: &Vector3<f64>, source: f64, vortex: &Vector3<f64>) -> Vector3<f64> {
+    let side = if normal.dot(&panel.normal) >= 0.0 { 0.5 } else { -0.5 };
+    (panel.normal.scale(source) + vortex.cross(&panel.normal)).scale(side)
+  }
+
+  /// Returns the vector from the panel to `position` and its smoothed squared norm
+  fn separation(panel: &Panel, position: &Point3<f64>) -> (Vector3<f64>, f64) {
+    let r = position - &panel.center;
+    let s2 = r.dot(&r) + panel.core_radius().powi(2);
+    (r, s2)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{Geometry, Transform, FlatPlate};
+
+  #[test]
+  fn it_cancels_the_normal_velocity() -> Result<(), Box<dyn std::error::Error>> {
+    let free_stream = Vector3::new(1.0, 0.0, 0.0);
+    let sphere = Geometry::sphere(Point3::origin(), 0.5);
+    let panels = Panels::solve(sphere.panels(), None, |_| Ok(free_stream.clone()))?;
+    for i in 0..panels.panels().len() {
+      let normal = &panels.panels()[i].normal;
+      assert!((panels.surface_velocity(i) + free_stream.clone()).dot(normal).abs() < 1e-6);
+    }
+    assert!(panels.circulation().norm() < 1e-8);
+    // Potential flow around the sphere, `u = U (1 - R³/r³)` upstream, `U (1 + R³/2r³)` aside
+    let u = panels.velocity_at(&Point3::new(-1.0, 0.0, 0.0)) + free_stream.clone();
+    println!("Upstream: {u:?}");
+    assert!((u - Vector3::new(0.875, 0.0, 0.0)).norm() < 0.03);
+    let u = panels.velocity_at(&Point3::new(0.0, 1.0, 0.0)) + free_stream.clone();
+    println!("Aside: {u:?}");
+    assert!((u - Vector3::new(1.0625, 0.0, 0.0)).norm() < 0.03);
+
+    // Finite differences of the velocity
+    let p = Point3::new(0.3, 0.7, -0.2);
+    let g = panels.velocity_gradient_at(&p);
+    for (j, d) in [Vector3::x(), Vector3::y(), Vector3::z()].iter().enumerate() {
+      let d = d.scale(1e-5);
+      let du = (panels.velocity_at(&(&p + &d)) - panels.velocity_at(&(&p - &d))).scale(0.5e5);
+      assert!((du - Vector3::new(g.m[0][j], g.m[1][j], g.m[2][j])).norm() < 1e-6);
+    }
+
+    // A plate across the flow blocks it through vortex sheets
+    let plate = FlatPlate::new(Transform::new(Point3::origin(), Vector3::new(0.0, 0.5 * std::f64::consts::PI, 0.0)), 1.0, 1.0);
+    let panels = Panels::solve(Geometry::FlatPlate(plate).panels(), None, |_| Ok(free_stream.clone()))?;
+    for i in 0..panels.panels().len() {
+      let normal = &panels.panels()[i].normal;
+      assert!((panels.surface_velocity(i) + free_stream.clone()).dot(normal).abs() < 1e-6);
+    }
+    Ok(())
+  }
+
+  /// The factorized system is reused for the panels of a body moved rigidly, giving the
+  /// strengths of a new solve, and solved again for a body changing shape
+  #[test]
+  fn it_reuses_the_system_of_rigid_bodies() -> Result<(), Box<dyn std::error::Error>> {
+    let f = |p: &Point3<f64>| Ok(Vector3::new(1.0 + 0.3 * p.y, 0.2 * p.z, -0.1));
+    let sphere = Geometry::sphere(Point3::new(0.2, 0.0, 0.0), 0.5);
+    let panels = Panels::solve(sphere.panels(), None, f)?;
+    let pose = Transform::new(Point3::new(0.5, -0.3, 1.0), Vector3::new(0.4, -1.1, 0.7));
+    let moved = sphere.panels().into_iter()
+      .map(|p| Panel::new(pose.to_global(&p.center), pose.vector_to_global(&p.normal), p.area).with_velocity(Vector3::new(0.0, 0.5, 0.0)))
+      .collect::<Vec<_>>();
+    let reused = Panels::solve(moved.clone(), Some(&panels), f)?;
+    assert!(Arc::ptr_eq(reused.system.as_ref().ok_or("No system")?, panels.system.as_ref().ok_or("No system")?));
+    let solved = Panels::solve(moved, None, f)?;
+    for p in [Point3::new(2.0, 0.5, 1.0), Point3::new(-0.5, 1.5, 0.0)] {
+      assert!((reused.velocity_at(&p) - solved.velocity_at(&p)).norm() < 1e-9);
+    }
+    for i in 0..reused.panels().len() {
+      let p = &reused.panels()[i];
+      assert!((reused.surface_velocity(i) + f(&p.center)? - p.velocity.clone()).dot(&p.normal).abs() < 1e-6);
+    }
+    assert!(reused.circulation().norm() < 1e-8);
+
+    let larger = Panels::solve(Geometry::sphere(Point3::new(0.2, 0.0, 0.0), 0.6).panels(), Some(&panels), f)?;
+    assert!(!Arc::ptr_eq(larger.system.as_ref().ok_or("No system")?, panels.system.as_ref().ok_or("No system")?));
+    Ok(())
+  }
+
+  /// Beyond `MAX_PANELS`, the panels are agglomerated, keeping the area and the outward normals
+  #[test]
+  fn it_agglomerates_panels() {
+    let sphere = crate::Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5).with_n_samples(5000);
+    let sphere = Geometry::Sphere(sphere);
+    let panels = Panels::agglomerate(sphere.panels());
+    assert!(panels.len() <= MAX_PANELS && panels.len() > MAX_PANELS / 4);
+    assert!((panels.iter().map(|p| p.area).sum::<f64>() - std::f64::consts::PI).abs() < 1e-9);
+    for p in panels.iter() {
+      let radial = (&p.center - &Point3::new(1.0, 0.0, 0.0)).normalize();
+      assert!(p.normal.dot(&radial) > 0.9);
+    }
+    // The same panels are agglomerated after a rigid motion
+    let pose = Transform::new(Point3::new(0.5, -0.3, 1.0), Vector3::new(0.4, -1.1, 0.7));
+    let moved = Panels::agglomerate(sphere.panels().into_iter()
+      .map(|p| Panel::new(pose.to_global(&p.center), pose.vector_to_global(&p.normal), p.area))
+      .collect());
+    assert_eq!(moved.len(), panels.len());
+    assert!(moved.iter().zip(&panels).all(|(m, p)| (m.center.clone() - pose.to_global(&p.center)).norm() < 1e-9));
+  }
+}
@@ -1,15 +1,23 @@
-use crate::{Vorton, Point3, Vector3};
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, surface::{self, Sample}};
 
+/// Sphere on which the no-slip condition is enforced at `n_samples` points distributed
+/// over the surface along a Fibonacci spiral.
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Sphere {
   center: Point3<f64>,
   radius: f64,
+  /// Number of points of the surface where the velocity is corrected
+  #[serde(default = "default_n_samples")]
+  n_samples: usize,
 }
 
+fn default_n_samples() -> usize { 64 }
+
 impl super::GeometryTrait for Sphere {
-  fn bounding_box(&self) -> Result<Option<(Point3<f64>, Point3<f64>)>, Box<dyn std::error::Error>> {
-    Ok(Some(( &self.center + &Vector3::new(-1.0, -1.0, -1.0).normalize().scale(self.radius), 
-              &self.center + &Vector3::new(1.0, 1.0, 1.0).normalize().scale(self.radius) )))
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let r = Vector3::new(self.radius, self.radius, self.radius);
+    Ok(Some(( &self.center - &r, &self.center + &r )))
   }
 
   fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
@@ -17,17 +25,100 @@ impl super::GeometryTrait for Sphere {
     Ok(r)
   }
 
+  /// Returns the first point of the segment `[start, end]` on the surface of the sphere,
+  /// solving `|start + t (end - start) - center|² = radius²` for `t` in `[0, 1]`
   fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
-    if self.is_inside(start)? != self.is_inside(end)? {
-      Err("Need to implement algorithm to calculate intersection".into())
-    } else {
-      Ok(None)
-    }
+    let (d, m) = (end - start, start - &self.center);
+    let a = d.dot(&d);
+    if a < 1e-24 { return Ok(None); }
+    let (b, c) = (m.dot(&d), m.dot(&m) - self.radius * self.radius);
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 { return Ok(None); }
+    let s = discriminant.sqrt();
+    Ok(surface::first_crossing([(-b - s) / a, (-b + s) / a]).map(|t| start + &d.scale(t)))
   }
 
-  fn enforce<F>(&self, f: F) -> Result<Vec<Vorton>, Box<dyn std::error::Error>> 
-  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>>
+  /// Generate vortons forming a vortex sheet that cancels the velocity at the samples
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
   {
-    Ok(Vec::new())
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing(), f, value)
+  }
+
+  fn panels(&self) -> Vec<Panel> {
+    surface::panels(&self.samples(), 4.0 * std::f64::consts::PI * self.radius * self.radius)
+  }
+}
+
+impl Sphere {
+  pub fn new(center: Point3<f64>, radius: f64) -> Sphere {
+    Sphere { center, radius, n_samples: default_n_samples() }
+  }
+
+  pub fn with_n_samples(mut self, n_samples: usize) -> Sphere {
+    self.n_samples = n_samples; self
+  }
+
+  /// Returns the points of the surface, and the outward normals, where the velocity is
+  /// corrected, distributed along a Fibonacci spiral
+  pub fn samples(&self) -> Vec<Sample> {
+    let golden_angle = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+    (0..self.n_samples)
+      .map(|i| {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / self.n_samples as f64;
+        let (r, phi) = ((1.0 - z * z).sqrt(), golden_angle * i as f64);
+        let normal = Vector3::new(r * phi.cos(), r * phi.sin(), z);
+        (&self.center + &normal.scale(self.radius), normal)
+      })
+      .collect()
+  }
+
+  /// Returns the average distance between neighbouring samples
+  fn spacing(&self) -> f64 {
+    self.radius * (4.0 * std::f64::consts::PI / self.n_samples as f64).sqrt()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    let sphere = Sphere::new(Point3::new(1.0, 0.0, 0.0), 0.5);
+    let p = sphere.intersect(&Point3::new(-1.0, 0.0, 0.0), &Point3::new(1.0, 0.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(0.5, 0.0, 0.0)).norm() < 1e-12);
+    // From the inside, and through the sphere
+    let p = sphere.intersect(&Point3::new(1.0, 0.0, 0.0), &Point3::new(1.0, 2.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.0, 0.5, 0.0)).norm() < 1e-12);
+    let p = sphere.intersect(&Point3::new(1.0, -2.0, 0.0), &Point3::new(1.0, 2.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(1.0, -0.5, 0.0)).norm() < 1e-12);
+    assert!(sphere.intersect(&Point3::new(0.0, 1.0, 0.0), &Point3::new(2.0, 1.0, 0.0))?.is_none());
+    assert!(sphere.intersect(&Point3::new(-1.0, 0.0, 0.0), &Point3::new(0.0, 0.0, 0.0))?.is_none());
+    Ok(())
+  }
+
+  /// Uniform flow past the sphere: the correction vortons cancel the velocity at the samples
+  /// and over the rest of the surface
+  #[test]
+  fn it_enforces_no_slip() -> Result<(), Box<dyn std::error::Error>> {
+    let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5);
+    let velocity = Vector3::new(1.0, 0.0, 0.0);
+    let kernel = Kernel::default();
+    let vortons = sphere.enforce(&kernel, |_| Ok(velocity.clone()), |_| Vector3::default())?;
+    assert_eq!(vortons.len(), sphere.n_samples);
+    assert!(vortons.iter().all(|v| !sphere.is_inside(v.position()).unwrap()));
+    let velocity_at = |p: &Point3<f64>| vortons.iter().fold(velocity.clone(), |r, v| r + v.velocity_contribution(&kernel, p));
+
+    let max_sample = sphere.samples().iter().map(|(p, _)| velocity_at(p).norm()).fold(0.0, f64::max);
+    // Surface points in between the samples
+    let surface = Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5).with_n_samples(101).samples();
+    let mean_surface = surface.iter().map(|(p, _)| velocity_at(p).norm()).sum::<f64>() / surface.len() as f64;
+    println!("Velocity at samples: {max_sample}, on the surface: {mean_surface}");
+    assert!(max_sample < 0.05);
+    assert!(mean_surface < 0.05);
+    Ok(())
   }
 }
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::{parallel, Kernel, Vorton, Point3, Vector3};
+use crate::algebra::dense;
+use super::Panel;
+
+/// Point of the surface of a geometry where the velocity is corrected, with the outward
+/// normal of the surface
+pub type Sample = (Point3<f64>, Vector3<f64>);
+
+/// Regularisation of the least squares problem giving the strength of the vortons
+const REGULARISATION: f64 = 1e-8;
+
+/// Generate vortons bringing the velocity `f` to the velocity `value` of the surface at the
+/// `samples` of a surface, the samples being about `spacing` apart. A vorton is located
+/// outside of the geometry along the normal of each sample, one spacing away from the
+/// surface and with a core radius of one spacing so that the cores of neighbouring vortons
+/// overlap. Their tangential vorticity is the least squares solution correcting the
+/// velocity at all samples at once, so that the vortons form a vortex sheet shielding the
+/// surface from the flow.
+pub(crate) fn enforce_no_slip<F, V>(kernel: &Kernel, samples: &[Sample], spacing: f64, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+      V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+{
+  // Slip velocity of the fluid relative to the surface
+  let velocities = parallel::try_map(samples, |(p, _)| Ok(f(p)? - value(p)))?;
+  if velocities.iter().all(|v| v.norm() < 1e-6) { return Ok(Vec::new()); }
+
+  // Vortons of unit strength along the tangents of each sample
+  let volume = std::f64::consts::PI / 6.0 * spacing.powi(3);
+  let units = samples.iter()
+    .flat_map(|(p, normal)| {
+      let position = p + &normal.scale(spacing);
+      let (t1, t2) = tangents(normal);
+      [Vorton::new(position.clone(), t1.scale(1.0 / volume), volume), Vorton::new(position, t2.scale(1.0 / volume), volume)]
+    })
+    .collect::<Vec<Vorton>>();
+  let rows = parallel::map(samples, |(p, _)| {
+    let u = units.iter().map(|v| v.velocity_contribution(kernel, p)).collect::<Vec<Vector3<f64>>>();
+    [u.iter().map(|u| u.x).collect::<Vec<f64>>(), u.iter().map(|u| u.y).collect(), u.iter().map(|u| u.z).collect()]
+  });
+  let rhs = velocities.iter().flat_map(|v| [-v.x, -v.y, -v.z]).collect::<Vec<f64>>();
+  let strengths = dense::least_squares(&rows.into_iter().flatten().collect::<Vec<_>>(), &rhs, REGULARISATION)?;
+
+  Ok(units.chunks(2).zip(strengths.chunks(2))
+     .map(|(v, s)| Vorton::new(v[0].position().clone(), v[0].vorticity().scale(s[0]) + v[1].vorticity().scale(s[1]), volume))
+     .collect())
+}
+
+/// Returns the panels centred on the `samples` of a surface of total `area`, the samples
+/// being distributed evenly enough to share the area equally
+pub(crate) fn panels(samples: &[Sample], area: f64) -> Vec<Panel> {
+  let area = area / samples.len().max(1) as f64;
+  samples.iter().map(|(p, n)| Panel::new(p.clone(), n.clone(), area)).collect()
+}
+
+/// Returns the spacing of a grid and the groups of the `elements` in the same cell of the
+/// grid, in the order of their first element. The elements are given by their position
+/// relative to the origin of the grid, and their normal: the elements of a cell are grouped
+/// by the main direction of their normal, so that the opposite faces of thin parts stay
+/// apart. The spacing, `spacing` at first, is widened until there are at most `max` groups.
+pub(crate) fn cluster(elements: &[(Vector3<f64>, Vector3<f64>)], spacing: f64, max: usize) -> (f64, Vec<Vec<usize>>) {
+  let mut spacing = spacing;
+  loop {
+    let mut groups = HashMap::<[i64; 4], usize>::new();
+    let mut r = Vec::<Vec<usize>>::new();
+    for (i, (p, n)) in elements.iter().enumerate() {
+      let [x, y, z] = [p.x, p.y, p.z].map(|c| (c / spacing).floor() as i64);
+      let direction = [n.x, n.y, n.z].iter().enumerate()
+        .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))
+        .map_or(0, |(i, c)| if *c < 0.0 { 2 * i as i64 + 1 } else { 2 * i as i64 });
+      let group = *groups.entry([x, y, z, direction]).or_insert_with(|| { r.push(Vec::new()); r.len() - 1 });
+      r[group].push(i);
+    }
+    if r.len() <= max || !spacing.is_finite() { return (spacing, r); }
+    spacing *= 1.1 * (r.len() as f64 / max as f64).sqrt();
+  }
+}
+
+/// Returns two unit vectors normal to `normal` and to each other
+pub(crate) fn tangents(normal: &Vector3<f64>) -> (Vector3<f64>, Vector3<f64>) {
+  let axis = if normal.x.abs() < 0.5 { Vector3::x() } else { Vector3::y() };
+  let t1 = normal.cross(&axis).normalize();
+  let t2 = normal.cross(&t1);
+  (t1, t2)
+}
+
+/// Returns the centres of the cells of a regular division of `[0, length]` into cells of
+/// at most `spacing`
+pub(crate) fn divide(length: f64, spacing: f64) -> Vec<f64> {
+  let n = ((length / spacing).ceil() as usize).max(1);
+  (0..n).map(|i| (i as f64 + 0.5) * length / n as f64).collect()
+}
+
+/// Returns the smallest `t` in `[0, 1]` where the implicit function `g`, negative inside the
+/// geometry, changes sign along the segment `[start, end]`. The segment is scanned in steps
+/// of at most `step` and the crossing refined by bisection.
+pub(crate) fn first_sign_change<G>(start: &Point3<f64>, end: &Point3<f64>, step: f64, g: G) -> Option<f64>
+where G: Fn(&Point3<f64>) -> f64
+{
+  let d = end - start;
+  let at = |t: f64| g(&(start + &d.scale(t)));
+  let n = ((d.norm() / step).ceil() as usize).max(1);
+  let mut previous = (0.0, at(0.0));
+  for i in 1..=n {
+    let t = i as f64 / n as f64;
+    let value = at(t);
+    if (previous.1 <= 0.0) != (value <= 0.0) {
+      let (mut a, mut b) = (previous.0, t);
+      for _ in 0..60 {
+        let m = 0.5 * (a + b);
+        if (at(m) <= 0.0) == (previous.1 <= 0.0) { a = m; } else { b = m; }
+      }
+      return Some(0.5 * (a + b));
+    }
+    previous = (t, value);
+  }
+  None
+}
+
+/// Returns the smallest of the candidate `t` within `[0, 1]`
+pub(crate) fn first_crossing<I>(candidates: I) -> Option<f64>
+where I: IntoIterator<Item = f64>
+{
+  candidates.into_iter().filter(|t| (0.0..=1.0).contains(t)).min_by(f64::total_cmp)
+}
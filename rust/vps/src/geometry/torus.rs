@@ -0,0 +1,121 @@
+use crate::{Kernel, Vorton, Point3, Vector3};
+use super::{BoundingBox, Panel, Transform, surface::{self, Sample}};
+
+/// Torus of `major_radius` about the `z` axis of its local frame and of tube radius
+/// `minor_radius`, centred on the origin of the local frame and placed by `transform`. The
+/// velocity is corrected at points about `spacing` apart.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct Torus {
+  #[serde(default)]
+  transform: Transform,
+  major_radius: f64,
+  minor_radius: f64,
+  #[serde(default = "default_spacing")]
+  spacing: f64,
+}
+
+fn default_spacing() -> f64 { 0.1 }
+
+impl super::GeometryTrait for Torus {
+  fn bounding_box(&self) -> Result<Option<BoundingBox>, Box<dyn std::error::Error>> {
+    let r = self.major_radius + self.minor_radius;
+    let half = Vector3::new(r, r, self.minor_radius);
+    Ok(Some(self.transform.bounding_box(&(Point3::origin() - half.clone(), Point3::origin() + half))))
+  }
+
+  fn is_inside(&self, point: &Point3<f64>) -> Result<bool, Box<dyn std::error::Error>> {
+    Ok(self.distance(&self.transform.to_local(point)) <= 0.0)
+  }
+
+  /// Returns the first point of the segment `[start, end]` on the surface. The quartic is
+  /// not solved analytically: the segment is scanned in steps of a fraction of the tube
+  /// radius, which cannot step over the tube.
+  fn intersect(&self, start: &Point3<f64>, end: &Point3<f64>) -> Result<Option<Point3<f64>>, Box<dyn std::error::Error>> {
+    let (a, b) = (self.transform.to_local(start), self.transform.to_local(end));
+    Ok(surface::first_sign_change(&a, &b, 0.25 * self.minor_radius, |p| self.distance(p))
+       .map(|t| start + &(end - start).scale(t)))
+  }
+
+  fn enforce<F, V>(&self, kernel: &Kernel, f: F, value: V) -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+  where F: Fn(&Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> + Sync + Send,
+        V: Fn(&Point3<f64>) -> Vector3<f64> + Sync + Send
+  {
+    surface::enforce_no_slip(kernel, &self.samples(), self.spacing, f, value)
+  }
+
+  fn panels(&self) -> Vec<Panel> {
+    surface::panels(&self.samples(), 4.0 * std::f64::consts::PI * std::f64::consts::PI * self.major_radius * self.minor_radius)
+  }
+}
+
+impl Torus {
+  pub fn new(transform: Transform, major_radius: f64, minor_radius: f64) -> Torus {
+    Torus { transform, major_radius, minor_radius, spacing: default_spacing() }
+  }
+
+  pub fn with_spacing(mut self, spacing: f64) -> Torus {
+    self.spacing = spacing; self
+  }
+
+  /// Returns the points of the surface where the velocity is corrected, on rings around
+  /// the tube
+  pub fn samples(&self) -> Vec<Sample> {
+    let two_pi = 2.0 * std::f64::consts::PI;
+    let n_v = ((two_pi * self.minor_radius / self.spacing).round() as usize).max(3);
+    (0..n_v)
+      .flat_map(|j| {
+        let v = two_pi * j as f64 / n_v as f64;
+        let r = self.major_radius + self.minor_radius * v.cos();
+        let n_u = ((two_pi * r / self.spacing).round() as usize).max(3);
+        (0..n_u).map(move |i| {
+          let u = two_pi * i as f64 / n_u as f64;
+          let normal = Vector3::new(v.cos() * u.cos(), v.cos() * u.sin(), v.sin());
+          let p = Point3::new(r * u.cos(), r * u.sin(), self.minor_radius * v.sin());
+          (self.transform.to_global(&p), self.transform.vector_to_global(&normal))
+        })
+      })
+      .collect()
+  }
+
+  /// Returns the signed distance of the local position `p` to the surface, negative inside
+  fn distance(&self, p: &Point3<f64>) -> f64 {
+    let rho = (p.x * p.x + p.y * p.y).sqrt() - self.major_radius;
+    (rho * rho + p.z * p.z).sqrt() - self.minor_radius
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::GeometryTrait;
+
+  #[test]
+  fn it_intersects_segments() -> Result<(), Box<dyn std::error::Error>> {
+    let torus = Torus::new(Transform::default(), 1.0, 0.25);
+    assert!(torus.is_inside(&Point3::new(0.0, 1.1, 0.1))? && !torus.is_inside(&Point3::new(0.0, 0.0, 0.0))?);
+    // Through the hole of the torus, and across the tube
+    assert!(torus.intersect(&Point3::new(0.0, 0.0, -1.0), &Point3::new(0.0, 0.0, 1.0))?.is_none());
+    let p = torus.intersect(&Point3::new(-2.0, 0.0, 0.0), &Point3::new(2.0, 0.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(-1.25, 0.0, 0.0)).norm() < 1e-9);
+    let p = torus.intersect(&Point3::new(0.0, 1.0, 1.0), &Point3::new(0.0, 1.0, 0.0))?.ok_or("No intersection")?;
+    assert!((p - Point3::new(0.0, 1.0, 0.25)).norm() < 1e-9);
+    Ok(())
+  }
+
+  #[test]
+  fn it_enforces_no_slip() -> Result<(), Box<dyn std::error::Error>> {
+    let torus = Torus::new(Transform::default(), 0.5, 0.15);
+    let velocity = Vector3::new(1.0, 0.0, 0.5);
+    let kernel = Kernel::default();
+    let vortons = torus.enforce(&kernel, |_| Ok(velocity.clone()), |_| Vector3::default())?;
+    let samples = torus.samples();
+    assert_eq!(vortons.len(), samples.len());
+    assert!(vortons.iter().all(|v| !torus.is_inside(v.position()).unwrap()));
+    let residual = samples.iter()
+      .map(|(p, _)| vortons.iter().fold(velocity.clone(), |r, v| r + v.velocity_contribution(&kernel, p)).norm())
+      .sum::<f64>() / samples.len() as f64;
+    println!("Residual velocity: {residual}");
+    assert!(residual < 0.1);
+    Ok(())
+  }
+}
@@ -0,0 +1,71 @@
+use crate::{Point3, Vector3, Matrix3};
+use super::BoundingBox;
+
+/// Placement of a geometry defined in its local frame: the local frame is rotated by
+/// `rotation`, a rotation vector whose norm is the angle in radians, and its origin is
+/// moved to `origin`.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Default)]
+pub struct Transform {
+  #[serde(default)]
+  pub origin: Point3<f64>,
+  #[serde(default)]
+  pub rotation: Vector3<f64>,
+}
+
+impl Transform {
+  pub fn new(origin: Point3<f64>, rotation: Vector3<f64>) -> Transform {
+    Transform { origin, rotation }
+  }
+
+  /// Returns the rotation matrix, from the Rodrigues formula
+  pub fn matrix(&self) -> Matrix3<f64> {
+    let angle = self.rotation.norm();
+    if angle < 1e-12 { return Matrix3::identity(); }
+    let k = Matrix3::cross_product(&self.rotation.scale(1.0 / angle));
+    Matrix3::identity() + k.scale(angle.sin()) + k.product(&k).scale(1.0 - angle.cos())
+  }
+
+  /// Returns the position in the local frame of the global position `p`
+  pub fn to_local(&self, p: &Point3<f64>) -> Point3<f64> {
+    Point3::origin() + self.vector_to_local(&(p - &self.origin))
+  }
+
+  /// Returns the global position of the local position `p`
+  pub fn to_global(&self, p: &Point3<f64>) -> Point3<f64> {
+    &self.origin + &self.vector_to_global(&(p - &Point3::origin()))
+  }
+
+  pub fn vector_to_local(&self, v: &Vector3<f64>) -> Vector3<f64> {
+    self.matrix().transpose().dot(v)
+  }
+
+  pub fn vector_to_global(&self, v: &Vector3<f64>) -> Vector3<f64> {
+    self.matrix().dot(v)
+  }
+
+  /// Returns the global bounding box of the local box `(min, max)`
+  pub fn bounding_box(&self, (min, max): &BoundingBox) -> BoundingBox {
+    (0..8)
+      .map(|c| self.to_global(&Point3::new(if c & 1 == 0 { min.x } else { max.x },
+                                           if c & 2 == 0 { min.y } else { max.y },
+                                           if c & 4 == 0 { min.z } else { max.z })))
+      .fold((Point3::new(f64::MAX, f64::MAX, f64::MAX), Point3::new(f64::MIN, f64::MIN, f64::MIN)),
+            |(min, max), p| (min.min(&p), max.max(&p)))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_transforms_positions() {
+    // Quarter turn about z, then translation
+    let transform = Transform::new(Point3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 0.0, 0.5 * std::f64::consts::PI));
+    let p = transform.to_global(&Point3::new(1.0, 0.0, 0.0));
+    assert!((p.clone() - Point3::new(1.0, 3.0, 3.0)).norm() < 1e-12);
+    assert!((transform.to_local(&p) - Point3::new(1.0, 0.0, 0.0)).norm() < 1e-12);
+    let (min, max) = transform.bounding_box(&(Point3::new(-1.0, -0.5, 0.0), Point3::new(1.0, 0.5, 1.0)));
+    assert!((min - Point3::new(0.5, 1.0, 3.0)).norm() < 1e-12 && (max - Point3::new(1.5, 3.0, 4.0)).norm() < 1e-12);
+  }
+}
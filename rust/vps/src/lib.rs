@@ -2,16 +2,27 @@
 //! is modelled using a set of particle representing the fluid vorticity. The particles are 
 //! convected in a lagrangien manner (ie the particles moves).
 //!
-mod algebra; pub use algebra::{Point3, Vector3};
+mod algebra; pub use algebra::{Point3, Vector3, Matrix3};
 mod algorithms; 
 pub use algorithms::{VortonToVelocity, 
                      VortonToVelocitySimple, VortonToVelocitySimpleBuilder,
-                     VortonToVelocityTree, VortonToVelocityTreeBuilder,
+                     VortonToVelocityTree, VortonToVelocityTreeBuilder, Octree, AccuracyReport, LevelStatistics,
+                     VortonToVelocityFmm, VortonToVelocityFmmBuilder,
+                     VortonToVelocityVic, VortonToVelocityVicBuilder, GreenFunction,
                      };
-mod sim; pub use sim::{UniformGrid, Vorton, SuperVorton};
+mod sim; pub use sim::{UniformGrid, Vorton, SuperVorton, VortonArrays, Stretching, Diffusion, Kernel, Buoyancy,
+                       Remeshing, RemeshingTrigger, Interpolation,
+                       PopulationControl, PopulationReport, Periodicity,
+                       Tracer, Tracers, Seeding, Emitter};
 mod configuration; pub use configuration::{InitialConditions, Configuration};
 mod profiler; pub use profiler::Profiler;
-mod output; pub use output::{Grid, GridBuilder, VortonCollection};
+mod output; pub use output::{Grid, GridBuilder, VortonCollection, TracerCollection};
+mod diagnostics; pub use diagnostics::Diagnostics;
 mod simulation; pub use simulation::{Simulation, VortonToVelocityAlgorithm};
-mod geometry; pub use geometry::{Geometry, GeometryTrait};
+mod time_integrator; pub use time_integrator::{TimeIntegrator, Derivative};
+mod time_step_controller; pub use time_step_controller::TimeStepController;
+mod geometry; pub use geometry::{Geometry, GeometryTrait, Transform, Sphere, Cube, Cylinder, Torus, FlatPlate, GroundPlane, Mesh, Triangle, Kinematics, Trajectory, Moving, Panel, Panels};
+mod parallel;
 
+#[doc(hidden)]
+pub mod random;
@@ -1,2 +1,3 @@
 mod grid; pub use grid::{Grid, GridBuilder};
 mod vorton_collection; pub use vorton_collection::{VortonCollection};
+mod tracer_collection; pub use tracer_collection::{TracerCollection};
@@ -0,0 +1,29 @@
+use crate::{Tracer, Simulation};
+
+#[derive(Debug)]
+pub struct TracerCollection<'a> {
+  tracers: &'a Vec<Tracer>,
+}
+
+impl<'a> From<&'a Simulation> for TracerCollection<'a> {
+  fn from(s: &'a Simulation) -> TracerCollection<'a> {
+    TracerCollection { tracers: s.tracers().tracers() }
+  }
+}
+
+impl<'a> TracerCollection<'a> {
+  pub fn is_empty(&self) -> bool {
+    self.tracers.is_empty()
+  }
+
+  pub fn to_writer_csv<W>(&self, mut writer: W) -> Result<(), Box<dyn std::error::Error>>
+  where W: std::io::Write,
+  {
+    writer.write_all("x coord, y coord, z coord, age\n".as_bytes())?;
+    for t in self.tracers.iter() {
+      writer.write_all(format!("{}, {}, {}, {}\n", 
+                                t.position().x, t.position().y, t.position().z, t.age()).as_bytes())?;
+    }
+    Ok(())
+  }
+}
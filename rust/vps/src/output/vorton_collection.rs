@@ -6,7 +6,7 @@ pub struct VortonCollection<'a> {
 }
 
 impl<'a> From<&'a Simulation> for VortonCollection<'a> {
-  fn from(s: &'a Simulation) -> VortonCollection {
+  fn from(s: &'a Simulation) -> VortonCollection<'a> {
     VortonCollection { vortons: s.vortons() }
   }
 }
@@ -0,0 +1,50 @@
+//! Loops over vortons that are run in parallel, using rayon, when the `parallel` feature
+//! is enabled and sequentially otherwise. Each item is processed independently so that
+//! both paths return identical results, in the order of the input.
+
+/// Returns `f` applied to each item
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where T: Sync, R: Send, F: Fn(&T) -> R + Sync + Send
+{
+  #[cfg(feature = "parallel")]
+  {
+    use rayon::prelude::*;
+    items.par_iter().map(f).collect()
+  }
+  #[cfg(not(feature = "parallel"))]
+  {
+    items.iter().map(f).collect()
+  }
+}
+
+/// Returns `f` applied to each item, or the first error encountered
+pub fn try_map<T, R, F>(items: &[T], f: F) -> Result<Vec<R>, Box<dyn std::error::Error>>
+where T: Sync, R: Send, F: Fn(&T) -> Result<R, Box<dyn std::error::Error>> + Sync + Send
+{
+  #[cfg(feature = "parallel")]
+  {
+    use rayon::prelude::*;
+    // `Box<dyn Error>` is not `Send`: errors are passed between threads as strings
+    items.par_iter()
+      .map(|t| f(t).map_err(|e| e.to_string()))
+      .collect::<Result<Vec<R>, String>>()
+      .map_err(|e| e.into())
+  }
+  #[cfg(not(feature = "parallel"))]
+  {
+    items.iter().map(f).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_preserves_order_and_errors() {
+    let items = (0..1000).collect::<Vec<usize>>();
+    assert_eq!(map(&items, |i| 2 * i), items.iter().map(|i| 2 * i).collect::<Vec<usize>>());
+    assert!(try_map(&items, |i| if *i == 500 { Err("failed".into()) } else { Ok(*i) }).is_err());
+    assert_eq!(try_map(&items, |i| Ok(*i)).unwrap(), items);
+  }
+}
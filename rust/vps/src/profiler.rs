@@ -30,7 +30,7 @@ where F: Fn() -> f64
     fn as_vector(&self) -> Vec<(String, f64)> {
         self.time
             .keys()
-            .map(|k| {(k.to_string(), self.time.get(k).unwrap().clone())})
+            .map(|k| {(k.to_string(), *self.time.get(k).unwrap())})
             .collect::<Vec::<(String, f64)>>()
     }
 
@@ -46,12 +46,12 @@ where F: Fn() -> f64
         v
     }
 
-    pub fn start(&mut self, name: String) -> ()
+    pub fn start(&mut self, name: String)
     {
         self.start.insert(name, (self.f)());
     }
 
-    pub fn finish(&mut self, name: String) -> ()
+    pub fn finish(&mut self, name: String)
     {
         let d = match self.start.remove(&name) {
             Some(t) => (self.f)() - t,
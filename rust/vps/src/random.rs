@@ -0,0 +1,8 @@
+//! Linear congruential generator drawing reproducible pseudo-random numbers from a seed,
+//! shared by the sampling of vortons and the tests and benchmarks.
+
+/// Advances `seed` and returns a value in `[0, 1)`
+pub fn random(seed: &mut u64) -> f64 {
+  *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+  (*seed >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -1,27 +1,29 @@
 use std::error::Error;
-use crate::algebra::{Vector3, Point3};
 
 mod uniformgrid; pub use uniformgrid::UniformGrid;
 mod vorton; pub use vorton::Vorton;
 mod super_vorton; pub use super_vorton::SuperVorton;
+mod vorton_arrays; pub use vorton_arrays::VortonArrays;
+mod stretching; pub use stretching::Stretching;
+mod diffusion; pub use diffusion::Diffusion;
+mod cell_list; pub use cell_list::CellList;
+mod kernel; pub use kernel::Kernel;
+mod buoyancy; pub use buoyancy::Buoyancy;
+mod periodicity; pub use periodicity::Periodicity;
+mod population; pub use population::{PopulationControl, PopulationReport};
+mod tracers; pub use tracers::{Tracer, Tracers, Seeding, Emitter};
+mod remeshing; pub use remeshing::{Remeshing, RemeshingTrigger, Interpolation};
+pub(crate) use remeshing::median_spacing;
 
 use crate::configuration::{InitialConditions, Configuration};
 
-pub trait Positionable {
-    fn position(&self) -> &Point3<f64>;
-}
-
-pub trait Aggregatable<RHS=Self> {
-    fn aggregate(&self, rhs: &RHS) -> RHS;
-}
-
 pub mod functions {
     use super::*;
 
     pub fn make_vortons(configuration: &Configuration) -> Result<Vec<Vorton>, Box<dyn Error>> {
         let mut n_cells = configuration.n_vortons;
         let vortons = make_vortons_from_ncells(n_cells, *configuration.get_initial_conditions())?;
-        if vortons.len() > 0 {
+        if !vortons.is_empty() {
             n_cells = n_cells * configuration.n_vortons/vortons.len(); 
             make_vortons_from_ncells(n_cells, *configuration.get_initial_conditions())
         } else {
@@ -37,22 +39,13 @@ pub mod functions {
                 let p = uniform_grid.cell_position(index);
                 let v = uniform_grid.cell_volume(index);
                 let vorticity = initial_conditions.vorticity(&p);
-                Vorton::new(p, vorticity, v)
+                let density = initial_conditions.density(&p);
+                Vorton::new(p, vorticity, v).with_density(density)
             })
-            .filter(|vorton| vorton.vorticity().norm() > 1e-5)
+            .filter(|vorton| vorton.vorticity().norm() > 1e-5 || vorton.density().abs() > 1e-5)
             .collect::<Vec<Vorton>>())
     }
 
-    /*
-     * Calculate a velocity at a given point
-     */
-    pub fn velocity_at<'a, I>(position: &Point3<f64>, free_stream_velocity: Vector3<f64>, vortons: I) -> Vector3<f64> 
-        where I: Iterator<Item = &'a Vorton>
-    {
-        vortons.map(|vorton| vorton.velocity_contribution(position) ) 
-            .fold(free_stream_velocity, |res, vel| res + vel )
-    }
-
         /*
     #[cfg(test)]
     mod make_velocity {
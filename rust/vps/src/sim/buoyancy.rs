@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::Vector3;
+use crate::parallel;
+use crate::sim::{Vorton, CellList};
+
+/// Buoyancy under the Boussinesq approximation. Each vorton carries the density perturbation
+/// `ρ'/ρ0` relative to the reference density, which generates vorticity at a rate `∇ρ' × g`
+/// (baroclinic term) and diffuses with the nominated `diffusivity`. A temperature field
+/// is represented by the density perturbation `-β ΔT`, with `β` the thermal expansion
+/// coefficient.
+///
+/// The density gradient and laplacian are evaluated from the neighbouring vortons using a
+/// gaussian kernel of smoothing length `smoothing_ratio` times the vorton spacing
+/// `volume^(1/3)`, in the same way as the PSE diffusion of vorticity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Buoyancy {
+    pub gravity: Vector3<f64>,
+    pub diffusivity: f64,
+    pub smoothing_ratio: f64,
+}
+
+impl Default for Buoyancy {
+    fn default() -> Self {
+        Buoyancy {
+            gravity: Vector3::new(0.0, 0.0, -9.81),
+            diffusivity: 0.0,
+            smoothing_ratio: 1.0,
+        }
+    }
+}
+
+/// Cut-off radius of the kernel, expressed as a multiple of the smoothing length
+const CUT_OFF: f64 = 4.0;
+
+impl Buoyancy {
+    /// Returns, for each vorton, the rate of change of vorticity due to the baroclinic term
+    /// and the rate of change of the density perturbation due to diffusion. Using the kernel
+    /// `W_ε(r) = exp(-r²/ε²) / (π^(3/2) ε³)`:
+    ///  - `∇ρ'_p = 2/ε² Σ_q V_q (ρ'_q - ρ'_p) (x_q - x_p) W_ε(x_p - x_q)`;
+    ///  - `κ∇²ρ'_p = 4κ/ε² Σ_q V_q (ρ'_q - ρ'_p) W_ε(x_p - x_q)`.
+    pub fn rates(&self, vortons: &[Vorton]) -> Vec<(Vector3<f64>, f64)> {
+        let smoothing_length = |v: &Vorton| self.smoothing_ratio * v.volume().cbrt();
+        let max_length = vortons.iter().map(smoothing_length).fold(0.0, f64::max);
+        if max_length <= 0.0 { return vec![(Vector3::default(), 0.0); vortons.len()]; }
+        let cell_list = CellList::new(vortons.iter().map(|v| v.position()), CUT_OFF * max_length);
+        let c = 1.0 / std::f64::consts::PI.powf(1.5);
+
+        parallel::map(vortons, |p| {
+            let epsilon_p = smoothing_length(p);
+            let (gradient, laplacian) = cell_list.neighbours(p.position())
+                .map(|q| &vortons[q])
+                .fold((Vector3::default(), 0.0), |(gradient, laplacian), q| {
+                    let epsilon = 0.5 * (epsilon_p + smoothing_length(q));
+                    let r = q.position() - p.position();
+                    let d2 = r.dot(&r) / epsilon.powi(2);
+                    if d2 > CUT_OFF.powi(2) { return (gradient, laplacian); }
+                    let w = q.volume() * (q.density() - p.density()) * c * (-d2).exp() / epsilon.powi(5);
+                    (gradient + r.scale(2.0 * w), laplacian + 4.0 * w)
+                });
+            (gradient.cross(&self.gravity), self.diffusivity * laplacian)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::algebra::Point3;
+
+  /// In a linear density field `ρ' = a·x` the baroclinic term is `a × g` and the density
+  /// does not diffuse
+  #[test]
+  fn it_evaluates_baroclinic_term() {
+    let h = 0.1; let n = 6;
+    let a = Vector3::new(0.3, -0.2, 0.5);
+    let mut vortons = Vec::new();
+    for k in -n..=n {
+      for j in -n..=n {
+        for i in -n..=n {
+          let p = Point3::new(i as f64 * h, j as f64 * h, k as f64 * h);
+          let density = a.dot(&(&p - &Point3::origin()));
+          vortons.push(Vorton::new(p, Vector3::default(), h.powi(3)).with_density(density));
+        }
+      }
+    }
+    let buoyancy = Buoyancy { diffusivity: 1.0, ..Buoyancy::default() };
+    let rates = buoyancy.rates(&vortons);
+    let (baroclinic, diffusion) = &rates[vortons.len() / 2];
+    let expected = a.cross(&buoyancy.gravity);
+    println!("{baroclinic:?} vs {expected:?}, diffusion {diffusion}");
+    assert!((baroclinic.clone() - expected.clone()).norm() < 1e-2 * expected.norm());
+    assert!(diffusion.abs() < 1e-6);
+
+    // The total density perturbation is conserved by the diffusion
+    let total = vortons.iter().zip(rates.iter()).map(|(v, (_, d))| d * v.volume()).sum::<f64>();
+    assert!(total.abs() < 1e-10);
+  }
+}
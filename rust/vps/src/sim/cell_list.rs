@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use crate::algebra::Point3;
+
+/// Bins a set of points into cubic cells of a given size to retrieve, for any position,
+/// the candidate points located within one cell size.
+pub struct CellList {
+    size: f64,
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+impl CellList {
+    pub fn new<'a, I>(positions: I, size: f64) -> CellList
+    where I: Iterator<Item = &'a Point3<f64>>
+    {
+        let mut cells = HashMap::<(i64, i64, i64), Vec<usize>>::new();
+        for (index, p) in positions.enumerate() {
+            cells.entry(CellList::key(size, p)).or_default().push(index);
+        }
+        CellList { size, cells }
+    }
+
+    fn key(size: f64, p: &Point3<f64>) -> (i64, i64, i64) {
+        ((p.x / size).floor() as i64, (p.y / size).floor() as i64, (p.z / size).floor() as i64)
+    }
+
+    /// Returns the index of the points located in the cell containing `p` and the
+    /// surrounding cells. This includes all points within a distance `size` of `p`.
+    pub fn neighbours(&self, p: &Point3<f64>) -> impl Iterator<Item = usize> + '_ {
+        let (i, j, k) = CellList::key(self.size, p);
+        (0..27)
+            .filter_map(move |c| self.cells.get(&(i + c % 3 - 1, j + (c / 3) % 3 - 1, k + c / 9 - 1)))
+            .flat_map(|v| v.iter().cloned())
+    }
+}
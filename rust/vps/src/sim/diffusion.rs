@@ -0,0 +1,114 @@
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::Vector3;
+use crate::parallel;
+use crate::sim::{Vorton, CellList};
+
+/// Model used to represent the viscous diffusion of vorticity
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Diffusion {
+    /// Exponential decay of each vorton vorticity at a rate `0.1 viscosity`. This is not
+    /// physical diffusion and is retained for compatibility with existing cases.
+    #[default]
+    Decay,
+    /// Particle Strength Exchange: vortons exchange vorticity with their neighbours
+    /// according to a gaussian kernel. The smoothing length of each vorton is
+    /// `smoothing_ratio` times its characteristic spacing `volume^(1/3)`.
+    Pse { smoothing_ratio: f64 },
+}
+
+/// Cut-off radius of the PSE kernel, expressed as a multiple of the smoothing length
+const PSE_CUT_OFF: f64 = 4.0;
+
+impl Diffusion {
+    /// Returns the rate of change of the vorticity of each vorton due to viscous diffusion
+    pub fn rates(&self, vortons: &[Vorton], viscosity: f64) -> Vec<Vector3<f64>> {
+        match self {
+            Diffusion::Decay => vortons.iter().map(|v| v.vorticity().scale(-0.1*viscosity)).collect(),
+            Diffusion::Pse { smoothing_ratio } => Diffusion::pse_rates(vortons, viscosity, *smoothing_ratio),
+        }
+    }
+
+    /// Smoothing length of a vorton
+    fn smoothing_length(vorton: &Vorton, smoothing_ratio: f64) -> f64 {
+        smoothing_ratio * vorton.volume().cbrt()
+    }
+
+    /// Evaluate the PSE approximation of `viscosity * laplacian(vorticity)`:
+    /// `dω_p/dt = viscosity / ε² Σ_q V_q (ω_q - ω_p) η_ε(x_p - x_q)`
+    /// with the second order gaussian kernel `η_ε(r) = 4 / (π^(3/2) ε³) exp(-r²/ε²)`.
+    /// The smoothing length of a pair is the average of both vorton smoothing lengths so that the
+    /// exchange is symmetric and the total circulation is conserved.
+    fn pse_rates(vortons: &[Vorton], viscosity: f64, smoothing_ratio: f64) -> Vec<Vector3<f64>> {
+        let max_length = vortons.iter()
+            .map(|v| Diffusion::smoothing_length(v, smoothing_ratio))
+            .fold(0.0, f64::max);
+        if max_length <= 0.0 { return vec![Vector3::default(); vortons.len()]; }
+        let cell_list = CellList::new(vortons.iter().map(|v| v.position()), PSE_CUT_OFF * max_length);
+        let c = 4.0 / std::f64::consts::PI.powf(1.5);
+
+        parallel::map(vortons, |p| {
+            let epsilon_p = Diffusion::smoothing_length(p, smoothing_ratio);
+            cell_list.neighbours(p.position())
+                .map(|q| &vortons[q])
+                .fold(Vector3::default(), |r, q| {
+                    let epsilon = 0.5 * (epsilon_p + Diffusion::smoothing_length(q, smoothing_ratio));
+                    let d2 = (p.position() - q.position()).dot(&(p.position() - q.position())) / epsilon.powi(2);
+                    if d2 > PSE_CUT_OFF.powi(2) { return r; }
+                    let eta = c * (-d2).exp() / epsilon.powi(5);
+                    r + (q.vorticity().clone() - p.vorticity().clone()).scale(viscosity * q.volume() * eta)
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::algebra::Point3;
+
+  /// Lamb–Oseen vortex aligned with `z`: `ω(r, t) = Γ / (4πνt) exp(-r² / 4νt)`
+  fn lamb_oseen(circulation: f64, viscosity: f64, time: f64, r2: f64) -> f64 {
+    circulation / (4.0 * std::f64::consts::PI * viscosity * time) * (-r2 / (4.0 * viscosity * time)).exp()
+  }
+
+  #[test]
+  fn it_matches_lamb_oseen_spreading() -> Result<(), Box<dyn std::error::Error>> {
+    let (circulation, viscosity, time) = (1.0, 0.01, 4.0);
+    let h = 0.05; let n = 25; let n_z = 4;
+    let mut vortons = Vec::new();
+    for k in -n_z..=n_z {
+      for j in -n..=n {
+        for i in -n..=n {
+          let p = Point3::new(i as f64 * h, j as f64 * h, k as f64 * h);
+          let omega = lamb_oseen(circulation, viscosity, time, p.x*p.x + p.y*p.y);
+          vortons.push(Vorton::new(p, Vector3::new(0.0, 0.0, omega), h.powi(3)));
+        }
+      }
+    }
+    let rates = Diffusion::Pse { smoothing_ratio: 1.0 }.rates(&vortons, viscosity);
+
+    // Circulation is conserved
+    let total = vortons.iter().zip(rates.iter())
+      .fold(Vector3::default(), |r, (v, rate)| r + rate.scale(v.volume()));
+    assert!(total.norm() < 1e-10);
+
+    // The vorticity at the centre decays as `Γ / (4πνt²)` and the second moment of
+    // the vorticity distribution grows as `4νΓ`.
+    let middle = vortons.iter().zip(rates.iter())
+      .filter(|(v, _)| v.position().z.abs() < 1e-8)
+      .collect::<Vec<_>>();
+    let centre = middle.iter()
+      .find(|(v, _)| v.position().x.abs() < 1e-8 && v.position().y.abs() < 1e-8)
+      .ok_or("No centre vorton")?.1.z;
+    let expected = -circulation / (4.0 * std::f64::consts::PI * viscosity * time.powi(2));
+    println!("Centre rate: {centre} vs {expected}");
+    assert!((centre - expected).abs() / expected.abs() < 0.02);
+
+    let spreading = middle.iter()
+      .fold(0.0, |r, (v, rate)| r + (v.position().x.powi(2) + v.position().y.powi(2)) * rate.z * h * h);
+    println!("Spreading rate: {spreading} vs {}", 4.0 * viscosity * circulation);
+    assert!((spreading - 4.0 * viscosity * circulation).abs() / (4.0 * viscosity * circulation) < 0.02);
+    Ok(())
+  }
+}
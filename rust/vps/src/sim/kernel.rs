@@ -0,0 +1,138 @@
+use serde::{Serialize, Deserialize};
+
+/// Regularised smoothing kernel used to evaluate the velocity induced by a vorton. A vorton
+/// of vorticity `ω`, volume `V` and core radius `σ = (6V/π)^(1/3)` induces at `r` from its
+/// position the velocity `u = q(ρ) / (4π|r|³) ωV × r` where `ρ = |r|/σ` and `q(ρ)` is the
+/// fraction of the vorton circulation enclosed within `ρ`. All kernels recover the singular
+/// Biot–Savart kernel `q = 1` far from the vorton.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Kernel {
+    /// Uniform vorticity within a sphere of radius `σ`: `q = min(ρ³, 1)`
+    #[default]
+    SolidSphere,
+    /// Gaussian vorticity distribution: `q = erf(ρ/√2) - √(2/π) ρ exp(-ρ²/2)`
+    Gaussian,
+    /// Low order algebraic (Rosenhead–Moore) kernel: `q = ρ³ / (ρ² + 1)^(3/2)`
+    RosenheadMoore,
+    /// High order algebraic (Winckelmans–Leonard) kernel: `q = ρ³ (ρ² + 5/2) / (ρ² + 1)^(5/2)`
+    WinckelmansLeonard,
+}
+
+/// Distance, in core radii, below which the gaussian kernel is evaluated using series
+const GAUSSIAN_SERIES: f64 = 3.0;
+
+impl Kernel {
+    /// Returns the factor `g` such that the velocity induced at a distance `distance` by a
+    /// vorton of core radius `core_radius` is `g ωV × r`, ie `q(ρ) / (4π|r|³)`.
+    pub fn velocity_factor(&self, distance: f64, core_radius: f64) -> f64 {
+        self.shape(distance / core_radius) / (4.0 * std::f64::consts::PI * core_radius.powi(3))
+    }
+
+    /// Returns `g'(|r|)/|r|`, the factor of the contribution `(ωV × r) ⊗ r` to the velocity
+    /// gradient.
+    pub fn gradient_factor(&self, distance: f64, core_radius: f64) -> f64 {
+        self.shape_derivative(distance / core_radius) / (4.0 * std::f64::consts::PI * core_radius.powi(5))
+    }
+
+    /// Returns `q(ρ)/ρ³`
+    fn shape(&self, rho: f64) -> f64 {
+        match self {
+            Kernel::SolidSphere => if rho <= 1.0 { 1.0 } else { rho.powi(-3) },
+            Kernel::Gaussian => {
+                if rho < GAUSSIAN_SERIES {
+                    Kernel::gaussian_series(rho, 3)
+                } else {
+                    let c = (2.0 / std::f64::consts::PI).sqrt();
+                    (1.0 - erfc(rho / std::f64::consts::SQRT_2) - c * rho * (-0.5 * rho * rho).exp()) / rho.powi(3)
+                }
+            },
+            Kernel::RosenheadMoore => (rho * rho + 1.0).powf(-1.5),
+            Kernel::WinckelmansLeonard => (rho * rho + 2.5) * (rho * rho + 1.0).powf(-2.5),
+        }
+    }
+
+    /// Returns `q'(ρ)/ρ⁴ - 3q(ρ)/ρ⁵`, written to remain accurate as `ρ` tends to 0
+    fn shape_derivative(&self, rho: f64) -> f64 {
+        match self {
+            Kernel::SolidSphere => if rho <= 1.0 { 0.0 } else { -3.0 * rho.powi(-5) },
+            Kernel::Gaussian => {
+                if rho < GAUSSIAN_SERIES {
+                    -Kernel::gaussian_series(rho, 5)
+                } else {
+                    let c = (2.0 / std::f64::consts::PI).sqrt();
+                    (c * (-0.5 * rho * rho).exp() - 3.0 * self.shape(rho)) / (rho * rho)
+                }
+            },
+            Kernel::RosenheadMoore => -3.0 * (rho * rho + 1.0).powf(-2.5),
+            Kernel::WinckelmansLeonard => -(3.0 * rho * rho + 10.5) * (rho * rho + 1.0).powf(-3.5),
+        }
+    }
+
+    /// Returns `√(2/π) Σ_n (-ρ²/2)^n / (n! (2n + offset))`. With `offset = 3` this is
+    /// `q(ρ)/ρ³` for the gaussian kernel, and with `offset = 5` it is the opposite of
+    /// `q'(ρ)/ρ⁴ - 3q(ρ)/ρ⁵`.
+    fn gaussian_series(rho: f64, offset: usize) -> f64 {
+        let x = -0.5 * rho * rho;
+        let mut term = 1.0; let mut sum = 0.0;
+        for n in 0..100 {
+            sum += term / (2 * n + offset) as f64;
+            term *= x / (n + 1) as f64;
+            if term.abs() < 1e-17 { break; }
+        }
+        (2.0 / std::f64::consts::PI).sqrt() * sum
+    }
+}
+
+/// Complementary error function evaluated using its continued fraction, accurate for `x > 2`
+fn erfc(x: f64) -> f64 {
+    let f = (1..=60).rev().fold(x, |f, k| x + 0.5 * k as f64 / f);
+    (-x * x).exp() / (std::f64::consts::PI.sqrt() * f)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const KERNELS: [Kernel; 4] = [Kernel::SolidSphere, Kernel::Gaussian, Kernel::RosenheadMoore, Kernel::WinckelmansLeonard];
+
+  #[test]
+  fn it_recovers_biot_savart_far_field() {
+    for kernel in KERNELS {
+      let g = kernel.velocity_factor(100.0, 1.0) * 4.0 * std::f64::consts::PI * 100f64.powi(3);
+      println!("{kernel:?}: {g}");
+      assert!((g - 1.0).abs() < 1e-3);
+    }
+  }
+
+  #[test]
+  fn it_is_consistent_with_its_derivative() {
+    // `q'(ρ)/ρ⁴ - 3q(ρ)/ρ⁵ = d(q/ρ³)/dρ / ρ`
+    for kernel in KERNELS {
+      for rho in [0.05, 0.5, 0.9, 1.5, 2.99, 3.01, 5.0] {
+        let h = 1e-6;
+        let numeric = (kernel.shape(rho + h) - kernel.shape(rho - h)) / (2.0 * h) / rho;
+        println!("{kernel:?} at {rho}: {} vs {numeric}", kernel.shape_derivative(rho));
+        assert!((kernel.shape_derivative(rho) - numeric).abs() < 1e-6);
+      }
+    }
+    // Continuity of the gaussian kernel between series and continued fraction
+    let (a, b) = (Kernel::Gaussian.shape(GAUSSIAN_SERIES - 1e-12), Kernel::Gaussian.shape(GAUSSIAN_SERIES));
+    assert!((a - b).abs() < 1e-12);
+  }
+
+  #[test]
+  fn it_makes_vorton_inducing_target_velocity() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{Vorton, Point3, Vector3};
+    // Target velocity normal to the offset of the vorton
+    let target = (Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, -0.2, 0.1));
+    for kernel in KERNELS {
+      for distance in [0.01, 0.05, 0.2] {
+        let vorton = Vorton::make_velocity_at(&kernel, Point3::new(distance, 0.0, 0.0), 1e-5, (&target.0, &target.1))?;
+        let v = vorton.velocity_contribution(&kernel, &target.0);
+        println!("{kernel:?} at {distance}: {v:?}");
+        assert!((v - target.1.clone()).norm() < 1e-9);
+      }
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,49 @@
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::{Point3, Vector3};
+
+/// Periodic boundary conditions applied along some of the axes of the box `min`, `max`.
+/// The flow is replicated with period `max - min` along the periodic axes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Periodicity {
+    pub min: Point3<f64>,
+    pub max: Point3<f64>,
+    pub periodic: [bool; 3],
+}
+
+impl Periodicity {
+    /// Returns the length of the box along each axis
+    pub fn lengths(&self) -> [f64; 3] {
+        let v = &self.max - &self.min;
+        [v.x, v.y, v.z]
+    }
+
+    /// Returns the position wrapped into the box along the periodic axes
+    pub fn wrap(&self, p: &Point3<f64>) -> Point3<f64> {
+        let l = self.lengths();
+        let wrap = |a: usize, x: f64, min: f64| if self.periodic[a] { min + (x - min).rem_euclid(l[a]) } else { x };
+        Point3::new(wrap(0, p.x, self.min.x), wrap(1, p.y, self.min.y), wrap(2, p.z, self.min.z))
+    }
+
+    /// Returns the shortest of the vectors `r + n L` along the periodic axes, with `L` the
+    /// length of the box
+    pub fn minimum_image(&self, r: &Vector3<f64>) -> Vector3<f64> {
+        let l = self.lengths();
+        let image = |a: usize, x: f64| if self.periodic[a] { x - (x / l[a]).round() * l[a] } else { x };
+        Vector3::new(image(0, r.x), image(1, r.y), image(2, r.z))
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_wraps_periodic_axes() {
+    let periodicity = Periodicity { min: Point3::new(-1.0, 0.0, 0.0), max: Point3::new(1.0, 1.0, 1.0), periodic: [true, false, true] };
+    let p = periodicity.wrap(&Point3::new(1.5, 1.5, -0.25));
+    assert!((p.x + 0.5).abs() < 1e-12 && (p.y - 1.5).abs() < 1e-12 && (p.z - 0.75).abs() < 1e-12);
+    let r = periodicity.minimum_image(&Vector3::new(1.5, 1.5, 0.75));
+    assert!((r.x + 0.5).abs() < 1e-12 && (r.y - 1.5).abs() < 1e-12 && (r.z + 0.25).abs() < 1e-12);
+  }
+}
@@ -0,0 +1,178 @@
+use serde::{Serialize, Deserialize};
+
+use crate::sim::{Vorton, SuperVorton, CellList};
+
+/// Control of the vorton population. Vortons are:
+///  - culled when their vorticity magnitude falls below `cull_vorticity` and their density
+///    perturbation below `cull_density`, weakest first, for as long as the circulation
+///    removed remains within `cull_budget` of the total circulation;
+///  - merged, using the `SuperVorton` aggregation, when their vorticity magnitude is below
+///    `merge_vorticity` and they are within `merge_distance` of each other;
+///  - split in two vortons of half volume aligned with the vorticity when their strength
+///    `|ω|V` exceeds `split_strength`.
+///
+/// The circulation of a set of vortons is measured as `Σ |ω|V`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PopulationControl {
+    pub cull_vorticity: f64,
+    /// Fraction of the total circulation that culling may remove at each step
+    pub cull_budget: f64,
+    pub cull_density: f64,
+    pub merge_vorticity: f64,
+    /// Merging is disabled when the distance is 0
+    pub merge_distance: f64,
+    pub split_strength: Option<f64>,
+}
+
+impl Default for PopulationControl {
+    fn default() -> Self {
+        PopulationControl {
+            cull_vorticity: 1e-5,
+            cull_budget: 1.0,
+            cull_density: 1e-5,
+            merge_vorticity: 0.0,
+            merge_distance: 0.0,
+            split_strength: None,
+        }
+    }
+}
+
+/// Summary of the changes made to the vorton population, with the circulation removed by
+/// each action
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PopulationReport {
+    pub culled: usize,
+    pub culled_circulation: f64,
+    pub merged: usize,
+    pub merged_circulation: f64,
+    /// Splitting conserves the circulation
+    pub split: usize,
+}
+
+impl PopulationControl {
+    /// Returns, for each vorton, whether it is kept by the culling together with the
+    /// circulation removed
+    pub fn cull(&self, vortons: &[Vorton]) -> (Vec<bool>, f64) {
+        let mut keep = vec![true; vortons.len()];
+        let mut candidates = vortons.iter().enumerate()
+            .filter(|(_, v)| v.vorticity().norm() < self.cull_vorticity && v.density().abs() < self.cull_density)
+            .map(|(i, v)| (i, circulation(v)))
+            .collect::<Vec<(usize, f64)>>();
+        candidates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        let budget = self.cull_budget * vortons.iter().map(circulation).sum::<f64>();
+        let mut removed = 0.0;
+        for (i, c) in candidates {
+            if removed + c > budget { break; }
+            removed += c; keep[i] = false;
+        }
+        (keep, removed)
+    }
+
+    /// Returns the vortons after merging the nearby weak vortons, the number of vortons
+    /// removed and the circulation removed by cancellation
+    pub fn merge(&self, vortons: Vec<Vorton>) -> (Vec<Vorton>, usize, f64) {
+        if self.merge_distance <= 0.0 { return (vortons, 0, 0.0); }
+        let is_weak = |v: &Vorton| v.vorticity().norm() < self.merge_vorticity;
+        let cell_list = CellList::new(vortons.iter().map(|v| v.position()), self.merge_distance);
+        let mut merged = vec![false; vortons.len()];
+        let mut result = Vec::with_capacity(vortons.len());
+        let mut removed = 0.0;
+        for (p, vorton) in vortons.iter().enumerate() {
+            if merged[p] { continue; }
+            merged[p] = true;
+            if !is_weak(vorton) { result.push(vorton.clone()); continue; }
+            let mut cluster = cell_list.neighbours(vorton.position())
+                .filter(|q| !merged[*q] && is_weak(&vortons[*q])
+                        && (vortons[*q].position() - vorton.position()).norm() <= self.merge_distance)
+                .collect::<Vec<usize>>();
+            cluster.sort_unstable();
+            let super_vorton = cluster.iter()
+                .fold(SuperVorton::from(vorton), |s, q| s + &vortons[*q]);
+            if cluster.is_empty() || !super_vorton.vorton().position().x.is_finite() {
+                result.push(vorton.clone()); continue;
+            }
+            for q in cluster.iter() { merged[*q] = true; }
+            removed += cluster.iter().map(|q| circulation(&vortons[*q])).sum::<f64>()
+                + circulation(vorton) - circulation(super_vorton.vorton());
+            result.push(super_vorton.vorton().clone());
+        }
+        let n_removed = vortons.len() - result.len();
+        (result, n_removed, removed)
+    }
+
+    /// Returns the vortons after splitting the strong vortons and the number of vortons
+    /// split. The total vorticity and linear impulse are conserved.
+    pub fn split(&self, vortons: Vec<Vorton>) -> (Vec<Vorton>, usize) {
+        let Some(split_strength) = self.split_strength else { return (vortons, 0) };
+        let mut n_split = 0;
+        let result = vortons.into_iter()
+            .flat_map(|vorton| {
+                if circulation(&vorton) <= split_strength {
+                    vec![vorton]
+                } else {
+                    n_split += 1;
+                    let offset = vorton.vorticity().normalize().scale(0.25 * vorton.core_radius());
+                    let volume = 0.5 * vorton.volume();
+                    vec![
+                        Vorton::new(vorton.position() - &offset, vorton.vorticity().clone(), volume).with_density(vorton.density()),
+                        Vorton::new(vorton.position() + &offset, vorton.vorticity().clone(), volume).with_density(vorton.density()),
+                    ]
+                }
+            })
+            .collect();
+        (result, n_split)
+    }
+}
+
+/// Circulation `|ω|V` carried by a vorton
+fn circulation(vorton: &Vorton) -> f64 {
+    vorton.vorticity().norm() * vorton.volume()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{Point3, Vector3};
+
+  fn strength(vortons: &[Vorton]) -> Vector3<f64> {
+    vortons.iter().fold(Vector3::default(), |s, v| s + v.vorticity().scale(v.volume()))
+  }
+
+  #[test]
+  fn it_culls_within_budget() {
+    let vortons = (1..=10)
+      .map(|i| Vorton::new(Point3::new(i as f64, 0.0, 0.0), Vector3::new(0.0, 0.0, i as f64 * 1e-6), 1.0))
+      .chain(std::iter::once(Vorton::new(Point3::origin(), Vector3::new(0.0, 0.0, 1e-4), 1.0)))
+      .collect::<Vec<Vorton>>();
+    // Total circulation 1.55e-4: the budget allows the removal of the 4 weakest vortons
+    let control = PopulationControl { cull_budget: 0.08, ..PopulationControl::default() };
+    let (keep, removed) = control.cull(&vortons);
+    assert_eq!(keep, [false, false, false, false, true, true, true, true, true, true, true]);
+    assert!((removed - 1e-5).abs() < 1e-15);
+    // The default budget removes all vortons with |ω| < 1e-5
+    let (keep, _) = PopulationControl::default().cull(&vortons);
+    assert!(keep[..9].iter().all(|k| !k) && keep[10]);
+  }
+
+  #[test]
+  fn it_merges_and_splits() {
+    let vortons = vec![
+      Vorton::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1e-3, 0.0, 0.0), 1e-3),
+      Vorton::new(Point3::new(0.05, 0.0, 0.0), Vector3::new(2e-3, 0.0, 0.0), 1e-3),
+      Vorton::new(Point3::new(0.1, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 1e-3),
+      Vorton::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1e-3, 0.0), 1e-3),
+    ];
+    let control = PopulationControl { merge_vorticity: 1e-2, merge_distance: 0.1, split_strength: Some(5e-4), ..PopulationControl::default() };
+    let (merged, n_merged, removed) = control.merge(vortons.clone());
+    assert_eq!((merged.len(), n_merged), (3, 1));
+    assert!(removed.abs() < 1e-15);
+    assert!((strength(&merged) - strength(&vortons)).norm() < 1e-15);
+
+    let (split, n_split) = control.split(merged.clone());
+    assert_eq!((split.len(), n_split), (4, 1));
+    assert!((strength(&split) - strength(&merged)).norm() < 1e-15);
+    assert!((split[1].position().x - split[2].position().x).abs() > 0.0);
+    assert!((0.5 * (split[1].position().x + split[2].position().x) - 0.1).abs() < 1e-15);
+  }
+}
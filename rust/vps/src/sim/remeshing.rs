@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::{Point3, Vector3};
+use crate::sim::{Vorton, UniformGrid, CellList};
+
+/// Interpolation kernel used to redistribute the vorton strengths onto the remeshing grid
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+    /// Monaghan M4' kernel, supported over 4 cells and conserving the first three moments
+    /// of the vorticity distribution
+    #[default]
+    M4Prime,
+    /// Λ4,2 (M6') kernel, supported over 6 cells and conserving the first four moments
+    M6Prime,
+}
+
+impl Interpolation {
+    /// Half width of the kernel support in cells
+    pub(crate) fn support(&self) -> i64 {
+        match self { Interpolation::M4Prime => 2, Interpolation::M6Prime => 3 }
+    }
+
+    /// Returns the grid nodes within the support of the kernel centred at `s`, in cells,
+    /// and their weights
+    pub(crate) fn weights(&self, s: f64) -> Vec<(i64, f64)> {
+        let (s0, support) = (s.floor() as i64, self.support());
+        ((s0 - support + 1)..=(s0 + support))
+            .map(|i| (i, self.weight(s - i as f64)))
+            .collect()
+    }
+
+    /// Kernel weight at a distance `x`, in cells, from a grid node
+    fn weight(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            Interpolation::M4Prime => {
+                if x < 1.0 { 1.0 - 2.5 * x * x + 1.5 * x * x * x }
+                else if x < 2.0 { 0.5 * (2.0 - x).powi(2) * (1.0 - x) }
+                else { 0.0 }
+            },
+            Interpolation::M6Prime => {
+                if x < 1.0 { -(x - 1.0) * (25.0 * x.powi(4) - 38.0 * x.powi(3) - 3.0 * x * x + 12.0 * x + 12.0) / 12.0 }
+                else if x < 2.0 { (x - 1.0) * (x - 2.0) * (25.0 * x.powi(3) - 114.0 * x * x + 153.0 * x - 48.0) / 24.0 }
+                else if x < 3.0 { -(x - 2.0) * (x - 3.0).powi(3) * (5.0 * x - 8.0) / 24.0 }
+                else { 0.0 }
+            },
+        }
+    }
+}
+
+/// Criterion used to trigger remeshing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RemeshingTrigger {
+    /// Remesh every `n` iterations
+    Every(usize),
+    /// Remesh when the distortion of the vorton distribution exceeds the threshold
+    Distortion(f64),
+}
+
+/// Redistribution of the vortons onto a regular grid. Vortons advected by the flow cluster in
+/// regions of strain and leave holes elsewhere, degrading the accuracy of the vorticity
+/// representation. Remeshing interpolates the vorton strengths `ωV` onto the cells of a
+/// `UniformGrid` and replaces the vortons by one vorton per cell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Remeshing {
+    pub interpolation: Interpolation,
+    /// Spacing of the remeshing grid. Defaults to the cube root of the median vorton volume.
+    pub spacing: Option<f64>,
+    pub trigger: RemeshingTrigger,
+    /// Vortons with a vorticity magnitude below `threshold` are discarded
+    pub threshold: f64,
+}
+
+impl Default for Remeshing {
+    fn default() -> Self {
+        Remeshing {
+            interpolation: Interpolation::default(),
+            spacing: None,
+            trigger: RemeshingTrigger::Every(10),
+            threshold: 1e-5,
+        }
+    }
+}
+
+impl Remeshing {
+    /// Returns true if the vortons are due to be remeshed at iteration `iteration`
+    pub fn is_due(&self, iteration: usize, vortons: &[Vorton]) -> bool {
+        match self.trigger {
+            RemeshingTrigger::Every(n) => n > 0 && iteration.is_multiple_of(n),
+            RemeshingTrigger::Distortion(threshold) => self.distortion(vortons) > threshold,
+        }
+    }
+
+    /// Spacing of the remeshing grid
+    pub fn spacing(&self, vortons: &[Vorton]) -> f64 {
+        self.spacing.unwrap_or_else(|| median_spacing(vortons))
+    }
+
+    /// Returns the coefficient of variation of the distance between each vorton and its
+    /// nearest neighbour. Distances are capped at twice the remeshing spacing. A regular
+    /// distribution has a distortion of 0.
+    pub fn distortion(&self, vortons: &[Vorton]) -> f64 {
+        let spacing = self.spacing(vortons);
+        if vortons.len() < 2 || spacing <= 0.0 { return 0.0; }
+        let cell_list = CellList::new(vortons.iter().map(|v| v.position()), 2.0 * spacing);
+        let distances = vortons.iter().enumerate()
+            .map(|(p, vorton)| cell_list.neighbours(vorton.position())
+                 .filter(|q| *q != p)
+                 .map(|q| (vorton.position() - vortons[q].position()).norm())
+                 .fold(2.0 * spacing, f64::min))
+            .collect::<Vec<f64>>();
+        let n = distances.len() as f64;
+        let mean = distances.iter().sum::<f64>() / n;
+        let variance = distances.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / n;
+        variance.sqrt() / mean
+    }
+
+    /// Returns the vortons interpolated onto the remeshing grid
+    pub fn remesh(&self, vortons: &[Vorton]) -> Result<Vec<Vorton>, Box<dyn std::error::Error>> {
+        let Some((min, max)) = vortons.iter()
+            .fold(None, |acc: Option<(Point3<f64>, Point3<f64>)>, v|
+                  acc.map(|(min, max)| (min.min(v.position()), max.max(v.position())))
+                  .or(Some((v.position().clone(), v.position().clone())))) else { return Ok(Vec::new()) };
+        let spacing = self.spacing(vortons);
+        let support = self.interpolation.support();
+        let margin = Vector3::new(1.0, 1.0, 1.0).scale((support + 1) as f64 * spacing);
+        let grid = UniformGrid::from_spacing(&(min - margin.clone()), &(max + margin), spacing)?;
+
+        // Strength `ωV` and density perturbation `ρ'V` interpolated onto each node
+        let mut strengths = HashMap::<usize, (Vector3<f64>, f64)>::new();
+        for vorton in vortons {
+            let (x, y, z) = grid.cell_coordinates(vorton.position());
+            let strength = vorton.vorticity().scale(vorton.volume());
+            let density = vorton.density() * vorton.volume();
+            let i = &self.interpolation;
+            let (wx, wy, wz) = (i.weights(x), i.weights(y), i.weights(z));
+            for (k, w_k) in wz.iter() {
+                for (j, w_j) in wy.iter() {
+                    for (i, w_i) in wx.iter() {
+                        let index = grid.cell_index(*i, *j, *k).ok_or("Vorton outside of remeshing grid")?;
+                        let s = strengths.entry(index).or_default();
+                        *s = (s.0.clone() + strength.scale(w_i * w_j * w_k), s.1 + density * w_i * w_j * w_k);
+                    }
+                }
+            }
+        }
+
+        let mut indices = strengths.keys().cloned().collect::<Vec<usize>>();
+        indices.sort_unstable();
+        Ok(indices.into_iter()
+           .map(|index| {
+               let volume = grid.cell_volume(index);
+               let (strength, density) = &strengths[&index];
+               Vorton::new(grid.cell_position(index), strength.scale(1.0 / volume), volume).with_density(density / volume)
+           })
+           .filter(|vorton| vorton.vorticity().norm() > self.threshold || vorton.density().abs() > self.threshold)
+           .collect())
+    }
+}
+
+/// Returns the cube root of the median vorton volume, the spacing of a regular grid of
+/// vortons with the same resolution
+pub(crate) fn median_spacing(vortons: &[Vorton]) -> f64 {
+    let mut volumes = vortons.iter().map(|v| v.volume()).collect::<Vec<f64>>();
+    volumes.sort_by(|a, b| a.total_cmp(b));
+    volumes.get(volumes.len() / 2).cloned().unwrap_or(0.0).cbrt()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_conserves_moments() {
+    for (interpolation, order) in [(Interpolation::M4Prime, 2), (Interpolation::M6Prime, 3)] {
+      for s in [0.0, 0.1, 0.37, 0.5, 0.93] {
+        for m in 0..=order {
+          let moment = (-4..=4).map(|i| interpolation.weight(s - i as f64) * (s - i as f64).powi(m)).sum::<f64>();
+          assert!((moment - if m == 0 { 1.0 } else { 0.0 }).abs() < 1e-12, "{:?} moment {} at {}: {}", interpolation, m, s, moment);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn it_remeshes() -> Result<(), Box<dyn std::error::Error>> {
+    let h = 0.1;
+    let vortons = (0..200)
+      .map(|n| {
+        let t = n as f64 * 0.37;
+        Vorton::new(Point3::new(t.sin(), (1.3 * t).cos(), 0.2 * (0.7 * t).sin()),
+                    Vector3::new(t.cos(), 0.5, (2.0 * t).sin()), 1e-3)
+      })
+      .collect::<Vec<Vorton>>();
+    let remeshing = Remeshing { spacing: Some(h), threshold: 0.0, ..Remeshing::default() };
+    let remeshed = remeshing.remesh(&vortons)?;
+
+    // Total vorticity and linear impulse are conserved
+    let moments = |vortons: &[Vorton]| vortons.iter()
+      .fold((Vector3::default(), Vector3::default()), |(total, impulse), v| {
+        let s = v.vorticity().scale(v.volume());
+        (total + s.clone(), impulse + (v.position() - &Point3::origin()).cross(&s))
+      });
+    let (total, impulse) = moments(&vortons);
+    let (remeshed_total, remeshed_impulse) = moments(&remeshed);
+    assert!((total - remeshed_total).norm() < 1e-12);
+    assert!((impulse - remeshed_impulse).norm() < 1e-12);
+    assert!(remeshed.iter().all(|v| (v.volume() - h.powi(3)).abs() < 1e-15));
+
+    // Remeshing vortons already located on the grid leaves them unchanged
+    let again = Remeshing { threshold: 1e-9, ..remeshing.clone() }.remesh(&remeshed)?;
+    assert_eq!(again.len(), remeshed.len());
+    assert!(again.iter().zip(remeshed.iter())
+            .all(|(a, b)| (a.position() - b.position()).norm() < 1e-12 && (a.vorticity().clone() - b.vorticity().clone()).norm() < 1e-9));
+    assert!(remeshing.distortion(&remeshed) < remeshing.distortion(&vortons));
+    Ok(())
+  }
+}
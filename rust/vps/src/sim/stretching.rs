@@ -0,0 +1,81 @@
+use serde::{Serialize, Deserialize};
+
+use crate::algebra::{Vector3, Matrix3};
+
+/// Formulation used to evaluate the vortex stretching/tilting term `(ω·∇)u`. The
+/// formulations are equivalent for the exact solution but differ once the vorticity
+/// field is discretised with vortons.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Stretching {
+    /// Classical formulation `(ω·∇)u`, ie `J ω` with `J[i][j] = du_i/dx_j`
+    #[default]
+    Classical,
+    /// Transpose formulation `(∇u)^T ω`, ie `J^T ω`, which conserves total vorticity
+    Transpose,
+    /// Mixed formulation `0.5 (J + J^T) ω`
+    Mixed,
+}
+
+impl Stretching {
+    /// Returns the rate of change of vorticity due to stretching/tilting given the vorticity
+    /// and the velocity gradient tensor at the vorton location.
+    pub fn source(&self, vorticity: &Vector3<f64>, gradient: &Matrix3<f64>) -> Vector3<f64> {
+        match self {
+            Stretching::Classical => gradient.dot(vorticity),
+            Stretching::Transpose => gradient.transpose().dot(vorticity),
+            Stretching::Mixed     => (gradient.clone() + gradient.transpose()).scale(0.5).dot(vorticity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::{Point3, Vorton};
+
+  /// A vortex tube along the axis of a vortex ring is stretched by the axisymmetric strain of
+  /// the ring, `du_z/dz = -3ΓR²z / 2(R² + z²)^(5/2)`, and induces no stretching on itself.
+  #[test]
+  fn it_stretches_a_vortex_tube() -> Result<(), Box<dyn std::error::Error>> {
+    let pi = std::f64::consts::PI;
+    let (radius, circulation, n) = (1.0, 1.0, 400);
+    let volume = 1e-6;
+    let mut vortons = (0..n)
+      .map(|i| {
+        let theta = 2.0 * pi * i as f64 / n as f64;
+        let tangent = Vector3::new(-theta.sin(), theta.cos(), 0.0);
+        Vorton::new(Point3::new(radius * theta.cos(), radius * theta.sin(), 0.0),
+                    tangent.scale(circulation * 2.0 * pi * radius / n as f64 / volume), volume)
+      })
+      .collect::<Vec<Vorton>>();
+    let tube = (0..11).map(|i| 0.3 + 0.04 * i as f64).collect::<Vec<f64>>();
+    vortons.extend(tube.iter().map(|z| Vorton::new(Point3::new(0.0, 0.0, *z), Vector3::new(0.0, 0.0, 2.0), volume)));
+
+    let velocity = Vector3::default();
+    let simple = crate::VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+    let tree = crate::VortonToVelocityTreeBuilder::default().vortons(&vortons).velocity(&velocity)
+      .theta(0.25).max_leaf(8).max_depth(10).build()?.initialize()?;
+    for backend in [&simple as &dyn crate::VortonToVelocity, &tree] {
+      for (v, z) in vortons[n..].iter().zip(&tube) {
+        let rate = -1.5 * circulation * radius.powi(2) * z / (radius.powi(2) + z * z).powf(2.5);
+        let gradient = backend.velocity_gradient_at(v.position())?;
+        for stretching in [Stretching::Classical, Stretching::Transpose, Stretching::Mixed] {
+          let source = stretching.source(v.vorticity(), &gradient);
+          let expected = Vector3::new(0.0, 0.0, rate * v.vorticity().z);
+          assert!((source.clone() - expected.clone()).norm() < 1e-2 * expected.norm(), "{:?} at {}: {:?} vs {:?}", stretching, z, source, expected);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Tilting of the vorticity by a shear only appears in the classical formulation
+  #[test]
+  fn it_tilts() {
+    let gradient = Matrix3::new([[0.0, 0.0, 1.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]]);
+    let vorticity = Vector3::new(0.0, 0.0, 1.0);
+    assert!((Stretching::Classical.source(&vorticity, &gradient).x - 1.0).abs() < 1e-12);
+    assert!(Stretching::Transpose.source(&vorticity, &gradient).x.abs() < 1e-12);
+    assert!((Stretching::Mixed.source(&vorticity, &gradient).x - 0.5).abs() < 1e-12);
+  }
+}
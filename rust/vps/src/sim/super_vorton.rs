@@ -1,17 +1,76 @@
-use crate::{Point3, Vorton};
+use crate::{Point3, Vector3, Matrix3, Vorton, Kernel};
 
+/// Aggregate of vortons, represented by an equivalent vorton located at the centre of
+/// vorticity of the aggregate and carrying its total strength `A = Σ ωV`. The dipole
+/// `D_ja = Σ y_j α_a` and quadrupole `Q_jla = Σ y_j y_l α_a` moments of the strengths
+/// `α = ωV` located at `y` from the centre correct the far field of the equivalent vorton.
 #[derive(Debug, Clone)]
 pub struct SuperVorton {
   vorton: Vorton,
   max_vorticity: f64,
   cum_vorticity: f64,
   n_vortons: usize,
+  dipole: [[f64; 3]; 3],
+  quadrupole: [[[f64; 3]; 3]; 3],
 }
 
 impl SuperVorton {
   pub fn vorton(&self) -> &Vorton    { &self.vorton }
   pub fn max_vorticity(&self) -> f64 { self.max_vorticity }
   pub fn n_vortons(&self) -> usize   { self.n_vortons }
+  pub fn dipole(&self) -> &[[f64; 3]; 3] { &self.dipole }
+  pub fn quadrupole(&self) -> &[[[f64; 3]; 3]; 3] { &self.quadrupole }
+
+  /// Returns the velocity induced at `position`, the equivalent vorton being evaluated with
+  /// the smoothing `kernel` and the moments with the singular kernel
+  /// `φ(r) = r / (4π|r|³)`: `u = A × φ - Σ_j D_j × ∂_jφ + ½ Σ_jl Q_jl × ∂_j∂_lφ`.
+  pub fn velocity_contribution(&self, kernel: &Kernel, position: &Point3<f64>) -> Vector3<f64> {
+    // A single vorton has no moment, and the singular kernel is not evaluated at its position
+    if self.n_vortons <= 1 { return self.vorton.velocity_contribution(kernel, position); }
+    let r = to_array(&(position - self.vorton.position()));
+    let (first, second) = (kernel_first(&r), kernel_second(&r));
+    let mut u = [0.0; 3];
+    for j in 0..3 {
+      add_cross(&mut u, &self.dipole[j], &first[j], -1.0);
+      for (q, h) in self.quadrupole[j].iter().zip(second[j].iter()) { add_cross(&mut u, q, h, 0.5); }
+    }
+    self.vorton.velocity_contribution(kernel, position) + Vector3::new(u[0], u[1], u[2])
+  }
+
+  /// Returns the contribution to the velocity gradient tensor at `position`, with entry
+  /// `[i][j]` being `du_i/dx_j`, consistently with `velocity_contribution`.
+  pub fn velocity_gradient_contribution(&self, kernel: &Kernel, position: &Point3<f64>) -> Matrix3<f64> {
+    if self.n_vortons <= 1 { return self.vorton.velocity_gradient_contribution(kernel, position); }
+    let r = to_array(&(position - self.vorton.position()));
+    let (second, third) = (kernel_second(&r), kernel_third(&r));
+    let mut g = [[0.0; 3]; 3];
+    for n in 0..3 {
+      let mut u = [0.0; 3];
+      for j in 0..3 {
+        add_cross(&mut u, &self.dipole[j], &second[n][j], -1.0);
+        for (q, h) in self.quadrupole[j].iter().zip(third[n][j].iter()) { add_cross(&mut u, q, h, 0.5); }
+      }
+      for (i, row) in g.iter_mut().enumerate() { row[n] = u[i]; }
+    }
+    self.vorton.velocity_gradient_contribution(kernel, position) + Matrix3::new(g)
+  }
+
+  /// Returns the dipole and quadrupole moments about a centre located at `-offset` from the
+  /// centre of the super vorton
+  fn shifted_moments(&self, offset: &[f64; 3]) -> ([[f64; 3]; 3], [[[f64; 3]; 3]; 3]) {
+    let strength = to_array(&self.vorton.vorticity().scale(self.vorton.volume()));
+    let (mut dipole, mut quadrupole) = (self.dipole, self.quadrupole);
+    for j in 0..3 {
+      for a in 0..3 {
+        dipole[j][a] += offset[j] * strength[a];
+        for l in 0..3 {
+          quadrupole[j][l][a] += offset[j] * self.dipole[l][a] + offset[l] * self.dipole[j][a]
+                               + offset[j] * offset[l] * strength[a];
+        }
+      }
+    }
+    (dipole, quadrupole)
+  }
 }
 
 impl Default for SuperVorton {
@@ -21,6 +80,8 @@ impl Default for SuperVorton {
       max_vorticity: 0.0,
       cum_vorticity: 0.0,
       n_vortons: 0,
+      dipole: [[0.0; 3]; 3],
+      quadrupole: [[[0.0; 3]; 3]; 3],
     }
   }
 }
@@ -32,6 +93,7 @@ impl From<&Vorton> for SuperVorton {
       max_vorticity: v.vorticity().norm(),
       cum_vorticity: v.vorticity().norm(),
       n_vortons: 1,
+      ..SuperVorton::default()
     }
   }
 }
@@ -59,21 +121,7 @@ impl std::ops::Add<&Vorton> for SuperVorton {
 impl std::ops::Add<&Vorton> for &SuperVorton {
   type Output = SuperVorton;
   fn add(self, other: &Vorton) -> Self::Output {
-    let volume = self.vorton.volume() + other.volume();
-    let cum_vorticity = (self.cum_vorticity*self.vorton.volume() + other.vorticity().norm()*other.volume())/volume;
-    let max_vorticity = self.max_vorticity.max(other.vorticity().norm());
-    let position = Point3::<f64>::origin()
-        + ( (self.vorton.position() - &Point3::<f64>::origin()).scale(self.cum_vorticity * self.vorton.volume())
-          + (other.position()       - &Point3::<f64>::origin()).scale(other.vorticity().norm() * other.volume())
-          ).scale(1.0 / (self.cum_vorticity * self.vorton.volume() + other.vorticity().norm() * other.volume()));
-    let vorticity = (self.vorton.vorticity().scale(self.vorton.volume()) + other.vorticity().scale(other.volume()))
-         .scale(1.0 / volume);
-    SuperVorton {
-      vorton: Vorton::new(position, vorticity, volume),
-      max_vorticity,
-      cum_vorticity,
-      n_vortons: self.n_vortons + 1,
-    }
+    self + &SuperVorton::from(other)
   }
 }
 
@@ -90,25 +138,83 @@ impl std::ops::Add<&SuperVorton> for &SuperVorton {
     let volume = self.vorton.volume() + other.vorton.volume();
     let cum_vorticity = (self.cum_vorticity*self.vorton.volume() + other.cum_vorticity*other.vorton.volume())/volume;
     let max_vorticity = self.max_vorticity.max(other.max_vorticity);
+    let (w_self, w_other) = weights((self.cum_vorticity, self.vorton.volume()), (other.cum_vorticity, other.vorton.volume()));
     let position = Point3::<f64>::origin()
-        + ( (self.vorton.position()  - &Point3::<f64>::origin()).scale(self.cum_vorticity  * self.vorton.volume())
-          + (other.vorton.position() - &Point3::<f64>::origin()).scale(other.cum_vorticity * other.vorton.volume())
-          ).scale(1.0 / (self.cum_vorticity * self.vorton.volume() + other.cum_vorticity * other.vorton.volume()));
+        + ( (self.vorton.position()  - &Point3::<f64>::origin()).scale(w_self)
+          + (other.vorton.position() - &Point3::<f64>::origin()).scale(w_other)
+          ).scale(1.0 / (w_self + w_other));
     let vorticity = (self.vorton.vorticity().scale(self.vorton.volume()) + other.vorton.vorticity().scale(other.vorton.volume()))
          .scale(1.0 / volume);
+    let density = (self.vorton.density() * self.vorton.volume() + other.vorton.density() * other.vorton.volume()) / volume;
+
+    // Moments of both aggregates about the new centre
+    let (dipole_self, quadrupole_self) = self.shifted_moments(&to_array(&(self.vorton.position() - &position)));
+    let (dipole_other, quadrupole_other) = other.shifted_moments(&to_array(&(other.vorton.position() - &position)));
+    let dipole = [0, 1, 2].map(|j| [0, 1, 2].map(|a| dipole_self[j][a] + dipole_other[j][a]));
+    let quadrupole = [0, 1, 2].map(|j| [0, 1, 2].map(|l| [0, 1, 2].map(|a| quadrupole_self[j][l][a] + quadrupole_other[j][l][a])));
     SuperVorton {
-      vorton: Vorton::new(position, vorticity, volume),
+      vorton: Vorton::new(position, vorticity, volume).with_density(density),
       max_vorticity,
       cum_vorticity,
       n_vortons: self.n_vortons + other.n_vortons,
+      dipole,
+      quadrupole,
     }
   }
 }
 
+/// Weights used to position the aggregate of two sets of vortons, given the average
+/// vorticity magnitude and volume of each set. Sets without vorticity, eg carrying only a
+/// density perturbation, are positioned by volume.
+fn weights((vorticity_a, volume_a): (f64, f64), (vorticity_b, volume_b): (f64, f64)) -> (f64, f64) {
+  let (a, b) = (vorticity_a * volume_a, vorticity_b * volume_b);
+  if a + b > 0.0 { (a, b) } else { (volume_a, volume_b) }
+}
+
+fn to_array(v: &Vector3<f64>) -> [f64; 3] { [v.x, v.y, v.z] }
+
+/// Add `scale a × b` to `u`
+fn add_cross(u: &mut [f64; 3], a: &[f64; 3], b: &[f64; 3], scale: f64) {
+  u[0] += scale * (a[1] * b[2] - a[2] * b[1]);
+  u[1] += scale * (a[2] * b[0] - a[0] * b[2]);
+  u[2] += scale * (a[0] * b[1] - a[1] * b[0]);
+}
+
+fn delta(a: usize, b: usize) -> f64 { if a == b { 1.0 } else { 0.0 } }
+
+/// Returns `∂_j φ_m` as `[j][m]`, with `φ(r) = r / (4π|r|³)`
+fn kernel_first(r: &[f64; 3]) -> [[f64; 3]; 3] {
+  let d2 = r.iter().map(|x| x * x).sum::<f64>();
+  let (c3, c5) = (d2.powf(-1.5), d2.powf(-2.5));
+  let c = 1.0 / (4.0 * std::f64::consts::PI);
+  [0, 1, 2].map(|j| [0, 1, 2].map(|m| c * (delta(j, m) * c3 - 3.0 * r[j] * r[m] * c5)))
+}
+
+/// Returns `∂_j ∂_l φ_m` as `[j][l][m]`
+fn kernel_second(r: &[f64; 3]) -> [[[f64; 3]; 3]; 3] {
+  let d2 = r.iter().map(|x| x * x).sum::<f64>();
+  let (c5, c7) = (d2.powf(-2.5), d2.powf(-3.5));
+  let c = 1.0 / (4.0 * std::f64::consts::PI);
+  [0, 1, 2].map(|j| [0, 1, 2].map(|l| [0, 1, 2].map(|m| c * (
+    -3.0 * (delta(j, m) * r[l] + delta(l, m) * r[j] + delta(j, l) * r[m]) * c5
+    + 15.0 * r[j] * r[l] * r[m] * c7))))
+}
+
+/// Returns `∂_n ∂_j ∂_l φ_m` as `[n][j][l][m]`
+fn kernel_third(r: &[f64; 3]) -> [[[[f64; 3]; 3]; 3]; 3] {
+  let d2 = r.iter().map(|x| x * x).sum::<f64>();
+  let (c5, c7, c9) = (d2.powf(-2.5), d2.powf(-3.5), d2.powf(-4.5));
+  let c = 1.0 / (4.0 * std::f64::consts::PI);
+  [0, 1, 2].map(|n| [0, 1, 2].map(|j| [0, 1, 2].map(|l| [0, 1, 2].map(|m| c * (
+    -3.0 * (delta(j, m) * delta(l, n) + delta(l, m) * delta(j, n) + delta(j, l) * delta(m, n)) * c5
+    + 15.0 * (delta(j, m) * r[l] * r[n] + delta(l, m) * r[j] * r[n] + delta(j, l) * r[m] * r[n]
+            + delta(j, n) * r[l] * r[m] + delta(l, n) * r[j] * r[m] + delta(m, n) * r[j] * r[l]) * c7
+    - 105.0 * r[j] * r[l] * r[m] * r[n] * c9)))))
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
-  use crate::{Vector3};
 
   #[test]
   fn it_adds() -> Result<(), Box<dyn std::error::Error>> {
@@ -116,9 +222,13 @@ mod test {
     let v2 = Vorton::new(Point3::new( 3.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 0.1);
     let v = &v1 + &v2;
     println!("#1 v: {v:#?}");
-    assert!((v.vorton().position().x.abs() - 2.0) < 1e-6);
+    assert!((v.vorton().position().x - 2.0).abs() < 1e-6);
+    // Opposite vorticity cancels in the equivalent vorton but not in the dipole moment
+    assert!(v.vorton().vorticity().norm() < 1e-6);
+    assert!((v.dipole()[0][0] - 0.2).abs() < 1e-6);
 
 
+    // The centre of vorticity is weighted by the strength `|ω|V` of the vortons
     let v1 = Vorton::new(Point3::new(-3.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0), 0.05);
     let v2 = Vorton::new(Point3::new( 3.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 0.05);
     let v3 = Vorton::new(Point3::new( 3.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 0.1);
@@ -127,9 +237,39 @@ mod test {
     println!("v3: {v3:#?}");
     let v = &v + &v3;
     println!("v: {v:#?}");
-    assert!((v.vorton().position().x - 1.0).abs() < 1e-6);
+    assert!((v.vorton().position().x - 1.5).abs() < 1e-6);
     assert!((v.vorton().volume() - 0.2).abs() < 1e-6);
+
+    // Moments do not depend on the order of aggregation
+    let w = SuperVorton::from(&v1) + &(&v2 + &v3);
+    assert!((w.quadrupole()[0][0][0] - v.quadrupole()[0][0][0]).abs() < 1e-9);
     Ok(())
   }
-}
 
+  /// Far from a cluster of vortons of random vorticity, the moments reduce the error of the
+  /// equivalent vorton, for both the velocity and its gradient
+  #[test]
+  fn it_expands_far_field() -> Result<(), Box<dyn std::error::Error>> {
+    use crate::{VortonToVelocity, VortonToVelocitySimpleBuilder};
+    let mut seed = 7u64;
+    let mut random = || crate::random::random(&mut seed) - 0.5;
+    let vortons = (0..50)
+      .map(|_| Vorton::new(Point3::new(random(), random(), random()), Vector3::new(random(), random(), random()), 1e-3))
+      .collect::<Vec<Vorton>>();
+    let super_vorton = SuperVorton::from(&vortons.iter().collect::<Vec<&Vorton>>());
+    let velocity = Vector3::default();
+    let simple = VortonToVelocitySimpleBuilder::default().vortons(&vortons).velocity(&velocity).build()?;
+    let kernel = Kernel::default();
+    for p in [Point3::new(3.0, 0.0, 0.0), Point3::new(-1.0, 2.5, 1.5), Point3::new(0.5, -0.5, -4.0)] {
+      let (u, g) = (simple.velocity_at(&p)?, simple.velocity_gradient_at(&p)?);
+      let monopole = (super_vorton.vorton().velocity_contribution(&kernel, &p) - u.clone()).norm();
+      let expansion = (super_vorton.velocity_contribution(&kernel, &p) - u.clone()).norm();
+      let monopole_gradient = (super_vorton.vorton().velocity_gradient_contribution(&kernel, &p) - g.clone()).norm();
+      let expansion_gradient = (super_vorton.velocity_gradient_contribution(&kernel, &p) - g.clone()).norm();
+      println!("{p:?}: monopole {monopole} {monopole_gradient}, expansion {expansion} {expansion_gradient} of {} {}", u.norm(), g.norm());
+      assert!(expansion < 0.1 * monopole && expansion < 0.05 * u.norm());
+      assert!(expansion_gradient < 0.1 * monopole_gradient && expansion_gradient < 0.05 * g.norm());
+    }
+    Ok(())
+  }
+}
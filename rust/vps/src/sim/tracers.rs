@@ -0,0 +1,192 @@
+use serde::{Serialize, Deserialize};
+
+use crate::parallel;
+use crate::algebra::{Point3, Vector3};
+use crate::sim::Periodicity;
+
+/// Passive particle advected by the velocity field, used to visualise the flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tracer {
+    position: Point3<f64>,
+    /// Time elapsed since the tracer was seeded
+    age: f64,
+}
+
+impl Tracer {
+    pub fn new(position: Point3<f64>) -> Tracer {
+        Tracer { position, age: 0.0 }
+    }
+
+    pub fn position(&self) -> &Point3<f64> { &self.position }
+    pub fn age(&self) -> f64               { self.age }
+}
+
+/// Arrangement of the tracers seeded in the flow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Seeding {
+    /// One tracer at each point
+    Points(Vec<Point3<f64>>),
+    /// `n` tracers evenly spaced along the segment from `start` to `end`, ends included
+    Line { start: Point3<f64>, end: Point3<f64>, n: usize },
+    /// Tracers on a lattice of `n` points along each axis of the box `min`, `max`, faces included
+    Box { min: Point3<f64>, max: Point3<f64>, n: [usize; 3] },
+}
+
+impl Seeding {
+    /// Returns the positions of the tracers
+    pub fn positions(&self) -> Vec<Point3<f64>> {
+        // Fraction of the extent at which the i-th of n points is located
+        let fraction = |i: usize, n: usize| if n > 1 { i as f64 / (n - 1) as f64 } else { 0.5 };
+        match self {
+            Seeding::Points(points) => points.clone(),
+            Seeding::Line { start, end, n } => (0..*n)
+                .map(|i| start + &(end - start).scale(fraction(i, *n)))
+                .collect(),
+            Seeding::Box { min, max, n } => (0..n[2])
+                .flat_map(|k| (0..n[1]).flat_map(move |j| (0..n[0]).map(move |i| (i, j, k))))
+                .map(|(i, j, k)| Point3::new(min.x + (max.x - min.x) * fraction(i, n[0]),
+                                             min.y + (max.y - min.y) * fraction(j, n[1]),
+                                             min.z + (max.z - min.z) * fraction(k, n[2])))
+                .collect(),
+        }
+    }
+}
+
+/// Continuous source of tracers, releasing the tracers of `seeding` `rate` times per unit of time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Emitter {
+    pub seeding: Seeding,
+    pub rate: f64,
+    /// Fraction of a release carried over to the next step
+    #[serde(default)]
+    pending: f64,
+}
+
+impl Emitter {
+    pub fn new(seeding: Seeding, rate: f64) -> Emitter {
+        Emitter { seeding, rate, pending: 0.0 }
+    }
+
+    /// Returns the tracers released over `time_step`
+    fn emit(&mut self, time_step: f64) -> Vec<Tracer> {
+        self.pending += self.rate * time_step;
+        let n = self.pending.floor();
+        self.pending -= n;
+        let positions = self.seeding.positions();
+        (0..n as usize)
+            .flat_map(|_| positions.iter().map(|p| Tracer::new(p.clone())))
+            .collect()
+    }
+}
+
+/// Tracers present in the flow and the emitters releasing new ones. Tracers older than
+/// `max_age` are discarded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tracers {
+    tracers: Vec<Tracer>,
+    emitters: Vec<Emitter>,
+    pub max_age: Option<f64>,
+}
+
+impl Tracers {
+    pub fn tracers(&self) -> &Vec<Tracer> { &self.tracers }
+    pub fn emitters(&self) -> &Vec<Emitter> { &self.emitters }
+
+    /// Returns true when there is neither tracers nor emitters
+    pub fn is_empty(&self) -> bool {
+        self.tracers.is_empty() && self.emitters.is_empty()
+    }
+
+    /// Add the tracers of `seeding`
+    pub fn seed(&mut self, seeding: &Seeding) {
+        self.tracers.extend(seeding.positions().into_iter().map(Tracer::new));
+    }
+
+    pub fn push_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    /// Advance the tracers over `time_step` using Heun's scheme, then release the tracers of
+    /// the emitters and discard the tracers older than `max_age`. The velocity fields at the
+    /// start and at the end of the step are evaluated at all positions at once by `start` and
+    /// `end`, one after the other, so that each can build its own evaluation of the field and
+    /// release it before the next. Tracers thus cost two evaluations of the velocity field
+    /// per step on top of those of the time integrator.
+    pub fn step<F, G>(&mut self, time_step: f64, start: F, end: G) -> Result<(), Box<dyn std::error::Error>>
+    where F: FnOnce(&[Point3<f64>]) -> Result<Vec<Vector3<f64>>, Box<dyn std::error::Error>>,
+          G: FnOnce(&[Point3<f64>]) -> Result<Vec<Vector3<f64>>, Box<dyn std::error::Error>>
+    {
+        let positions = self.tracers.iter().map(|t| t.position.clone()).collect::<Vec<_>>();
+        let u = start(&positions)?;
+        let predicted = positions.iter().zip(&u).map(|(p, u)| p + &u.scale(time_step)).collect::<Vec<_>>();
+        let v = end(&predicted)?;
+        if u.len() != self.tracers.len() || v.len() != self.tracers.len() { return Err("A velocity is needed for each tracer".into()); }
+        for ((t, u), v) in self.tracers.iter_mut().zip(u).zip(v) {
+            t.position = &t.position + &(u + v).scale(0.5 * time_step); t.age += time_step;
+        }
+        for emitter in self.emitters.iter_mut() {
+            self.tracers.append(&mut emitter.emit(time_step));
+        }
+        if let Some(max_age) = self.max_age {
+            self.tracers.retain(|t| t.age <= max_age);
+        }
+        Ok(())
+    }
+
+    /// Retain the tracers that satisfy the predicate
+    pub fn retain<P>(&mut self, predicate: P) -> Result<(), Box<dyn std::error::Error>>
+        where P: Fn(&Tracer) -> Result<bool, Box<dyn std::error::Error>> + Sync + Send
+    {
+        let keep = parallel::try_map(&self.tracers, predicate)?;
+        let mut i = 0; self.tracers.retain(|_| { i += 1; keep[i - 1] });
+        Ok(())
+    }
+
+    /// Wrap the tracers into the box along the periodic axes
+    pub fn wrap(&mut self, periodicity: &Periodicity) {
+        for t in self.tracers.iter_mut() { t.position = periodicity.wrap(&t.position); }
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_seeds_and_emits() {
+    let line = Seeding::Line { start: Point3::new(0.0, 0.0, 0.0), end: Point3::new(1.0, 2.0, 0.0), n: 5 };
+    let positions = line.positions();
+    assert_eq!(positions.len(), 5);
+    assert!((positions[4].y - 2.0).abs() < 1e-12 && (positions[2].x - 0.5).abs() < 1e-12);
+    let cube = Seeding::Box { min: Point3::new(0.0, 0.0, 0.0), max: Point3::new(1.0, 1.0, 1.0), n: [2, 3, 1] };
+    assert_eq!(cube.positions().len(), 6);
+
+    // Two releases per unit of time, in steps of 0.25
+    let still = |p: &[Point3<f64>]| Ok(vec![Vector3::default(); p.len()]);
+    let mut tracers = Tracers::default();
+    tracers.push_emitter(Emitter::new(line, 2.0));
+    for _ in 0..6 {
+      tracers.step(0.25, still, still).unwrap();
+    }
+    assert_eq!(tracers.tracers().len(), 15);
+    tracers.max_age = Some(0.5);
+    tracers.step(0.25, still, still).unwrap();
+    assert!(tracers.tracers().iter().all(|t| t.age() <= 0.5));
+  }
+
+  /// In a solid body rotation, Heun's scheme keeps the tracers on their circle to second order
+  #[test]
+  fn it_advects_in_rotation() -> Result<(), Box<dyn std::error::Error>> {
+    let mut tracers = Tracers::default();
+    tracers.seed(&Seeding::Points(vec![Point3::new(1.0, 0.0, 0.0)]));
+    let rotation = |p: &[Point3<f64>]| Ok(p.iter().map(|p| Vector3::new(-p.y, p.x, 0.0)).collect());
+    let n = 100;
+    for _ in 0..n {
+      tracers.step(std::f64::consts::PI / n as f64, rotation, rotation)?;
+    }
+    let p = tracers.tracers()[0].position();
+    assert!((p.x + 1.0).abs() < 1e-3 && p.y.abs() < 1e-3);
+    Ok(())
+  }
+}
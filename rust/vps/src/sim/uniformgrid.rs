@@ -59,6 +59,20 @@ impl UniformGrid {
         UniformGrid::new(min, max, n_points)
     }
 
+    /// Make a grid of cubic cells of size `delta` covering the box `min`, `max`. Cell
+    /// boundaries are aligned on multiples of `delta` so that grids generated for different
+    /// boxes share the same cells.
+    pub fn from_spacing(min: &Point3<f64>, max: &Point3<f64>, delta: f64) -> Result<UniformGrid, Box<dyn Error>> {
+        if delta <= 0.0 { return Err("Grid spacing must be positive".into()); }
+        let start = |v: f64| (v / delta).floor() * delta;
+        let n = |a: f64, b: f64| ((b - start(a)) / delta).floor() as usize + 2;
+        Ok(UniformGrid {
+            min: Point3::new(start(min.x), start(min.y), start(min.z)),
+            n_points: (n(min.x, max.x), n(min.y, max.y), n(min.z, max.z)),
+            delta: (delta, delta, delta),
+        })
+    }
+
     pub fn from_n_cells_target(min: Point3<f64>, max: Point3<f64>, n_cells_target: usize) -> Result<UniformGrid, Box<dyn Error>> {
         let v = &max - &min;
         let cell_volume = (v.x * v.y * v.z) / n_cells_target as f64;
@@ -70,6 +84,8 @@ impl UniformGrid {
     // pub fn max(&self)     -> &Point3<f64> { &self.max }
 
     pub fn n_cells(&self) -> usize { (self.n_points.0 - 1) * (self.n_points.1 - 1) * (self.n_points.2 - 1) }
+    /// Number of cells along each direction
+    pub fn n_cells_ijk(&self) -> (usize, usize, usize) { (self.n_points.0 - 1, self.n_points.1 - 1, self.n_points.2 - 1) }
     // pub fn n_points(&self)-> usize { self.n_points.0 * self.n_points.1 * self.n_points.2 }
     // pub fn n_x(&self)     -> usize { self.n_points.0 }
     // pub fn n_y(&self)     -> usize { self.n_points.1 }
@@ -80,7 +96,7 @@ impl UniformGrid {
     // pub fn delta_z(&self)   -> f64 { self.delta.2 }
     pub fn cell_volume(&self, _index: usize) -> f64 { self.delta.0 * self.delta.1 * self.delta.2 }
 
-    pub fn cell_index_iter(&self) -> CellIndexIterator {
+    pub fn cell_index_iter(&self) -> CellIndexIterator<'_> {
         CellIndexIterator::new(self)
     }
 
@@ -92,6 +108,20 @@ impl UniformGrid {
             + Vector3::z().scale(((k as f64)+0.5)*self.delta.2)
     }
 
+    /// Returns the cell index associated with the cell `i, j, k`, or None if outside the grid
+    pub fn cell_index(&self, i: i64, j: i64, k: i64) -> Option<usize> {
+        let (n_x, n_y, n_z) = (self.n_points.0 as i64 - 1, self.n_points.1 as i64 - 1, self.n_points.2 as i64 - 1);
+        if i < 0 || j < 0 || k < 0 || i >= n_x || j >= n_y || k >= n_z { return None; }
+        Some((i + n_x * (j + n_y * k)) as usize)
+    }
+
+    /// Returns the position of `p` in cell units relative to the center of cell `0, 0, 0`
+    pub fn cell_coordinates(&self, p: &Point3<f64>) -> (f64, f64, f64) {
+        ((p.x - self.min.x) / self.delta.0 - 0.5,
+         (p.y - self.min.y) / self.delta.1 - 0.5,
+         (p.z - self.min.z) / self.delta.2 - 0.5)
+    }
+
     fn index_to_ijk(index: usize, n_x: usize, n_y: usize, _n_z: usize) -> (usize, usize, usize) {
         let k = ((index as f64) / ((n_x*n_y) as f64)).floor() as usize;
         let j = (((index - k * n_x*n_y) as f64)/(n_x as f64)).floor() as usize;
@@ -1,12 +1,16 @@
 use serde::{Serialize, Deserialize};
 
-use crate::algebra::{Point3, Vector3};
+use crate::algebra::{Point3, Vector3, Matrix3};
+use crate::sim::Kernel;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Vorton {
     volume: f64,
     position: Point3<f64>,
     vorticity: Vector3<f64>,
+    /// Density perturbation `ρ'/ρ0` used by the buoyancy
+    #[serde(default)]
+    density: f64,
 }
 
 impl Default for Vorton {
@@ -15,6 +19,7 @@ impl Default for Vorton {
       volume: f64::EPSILON,
       position: Point3::origin(),
       vorticity: Vector3::new(0.0, 0.0, 0.0),
+      density: 0.0,
     }
   }
 }
@@ -25,10 +30,18 @@ impl Vorton {
             volume,
             position,
             vorticity,
+            density: 0.0,
         }
     }
 
-    pub fn make_velocity_at(position: Point3<f64>, volume: f64,
+    /// Returns the vorton with the nominated density perturbation
+    pub fn with_density(self, density: f64) -> Vorton {
+        Vorton { density, ..self }
+    }
+
+    /// Make a vorton located at `position` that induces, using `kernel`, the velocity
+    /// `target_velocity` at `target_point`.
+    pub fn make_velocity_at(kernel: &Kernel, position: Point3<f64>, volume: f64,
                (target_point, target_velocity): (&Point3<f64>, &Vector3<f64>)) -> Result<Vorton, Box<dyn std::error::Error>> {
       let r = target_point - &position;
       let d = r.norm();
@@ -38,12 +51,13 @@ impl Vorton {
       }
       let vorticity_vector = r.cross(target_velocity);
       if vorticity_vector.norm().abs() < 1e-6 { return Err("Unable to make velocity, position and target points are not appropriately positioned".into()); }
-      let vorticity = vorticity_vector.normalize().scale(3.0*Self::ratio(&r, volume).max(8.0) / d * target_velocity.norm());
-      let r = Vorton { volume, position, vorticity } ;
+      let g = volume * kernel.velocity_factor(d, (6.0 * volume / std::f64::consts::PI).cbrt());
+      let vorticity = vorticity_vector.normalize().scale(target_velocity.norm() / (g * d));
+      let r = Vorton::new(position, vorticity, volume);
 
       // check
 /*
-      let v = r.velocity_contribution(target_point);
+      let v = r.velocity_contribution(kernel, target_point);
       if (v.x - target_velocity.x).abs() > 1e-1
       || (v.y - target_velocity.y).abs() > 1e-1
       || (v.z - target_velocity.z).abs() > 1e-1 {
@@ -58,34 +72,57 @@ impl Vorton {
       Self::ratio(&(position - &self.position), self.volume) <= 8.0
     }
 
+    /// Radius of the vorton core, ie the radius within which the velocity induced by the
+    /// vorton is a solid body rotation.
+    pub fn core_radius(&self) -> f64 {
+      (6.0 * self.volume / std::f64::consts::PI).cbrt()
+    }
+
     fn ratio(r: &Vector3<f64>, volume: f64) -> f64 {
       4.0 / 3.0 * std::f64::consts::PI * r.norm().powi(3) / volume
     }
 
-    pub fn velocity_contribution(&self, position: &Point3<f64>) -> Vector3<f64> {
+    /// Returns the velocity induced by the vorton at `position` using the smoothing `kernel`
+    pub fn velocity_contribution(&self, kernel: &Kernel, position: &Point3<f64>) -> Vector3<f64> {
         let r = position - &self.position;
         self.vorticity
             .cross(&r)
-            .scale(1.0/(3.0 * Self::ratio(&r, self.volume).max(8.0)))
+            .scale(self.volume * kernel.velocity_factor(r.norm(), self.core_radius()))
+    }
+
+    /// Returns the contribution of the vorton to the velocity gradient tensor at `position`,
+    /// with entry `[i][j]` being `du_i/dx_j`, using the smoothing `kernel`.
+    pub fn velocity_gradient_contribution(&self, kernel: &Kernel, position: &Point3<f64>) -> Matrix3<f64> {
+        let r = position - &self.position;
+        let (d, core_radius) = (r.norm(), self.core_radius());
+        Matrix3::cross_product(&self.vorticity).scale(self.volume * kernel.velocity_factor(d, core_radius))
+        + Matrix3::outer(&self.vorticity.cross(&r), &r).scale(self.volume * kernel.gradient_factor(d, core_radius))
     }
 
     pub fn advect(&self, velocity: &Vector3<f64>, time_step: f64) -> Vorton {
-        Vorton::new(
-            &self.position + &velocity.scale(time_step),
-            self.vorticity.clone(),
-            self.volume
-        )
+        Vorton {
+            position: &self.position + &velocity.scale(time_step),
+            ..self.clone()
+        }
     }
 
     pub fn step(&self, source: &Vector3<f64>, time_step: f64) -> Vorton {
-        Vorton::new(
-            self.position.clone(),
-            &self.vorticity + source.scale(time_step),
-            self.volume
-        )
+        Vorton {
+            vorticity: &self.vorticity + source.scale(time_step),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the vorton with the density perturbation advanced at the rate `source`
+    pub fn step_density(&self, source: f64, time_step: f64) -> Vorton {
+        Vorton {
+            density: self.density + source * time_step,
+            ..self.clone()
+        }
     }
 
-    pub fn volume(&self)    -> f64           { self.volume.clone() }
+    pub fn volume(&self)    -> f64           { self.volume }
+    pub fn density(&self)   -> f64           { self.density }
     pub fn vorticity(&self) -> &Vector3<f64> { &self.vorticity }
     pub fn position(&self)  -> &Point3<f64>  { &self.position }
 }
@@ -0,0 +1,186 @@
+use std::iter::FromIterator;
+use std::ops::Range;
+
+use crate::{Point3, Vector3, Matrix3, Vorton, Kernel};
+
+/// Number of interactions accumulated side by side, so that the sums can be vectorised
+const LANES: usize = 4;
+
+/// Structure of arrays holding the position, strength `α = ωV` and core radius of a set of
+/// vortons, used to evaluate the direct interaction of the vortons with a position in
+/// batches. The arrays are contiguous and the kernels are written without branches where
+/// possible so that the compiler can vectorise the sums.
+#[derive(Debug, Clone, Default)]
+pub struct VortonArrays {
+  x: Vec<f64>,
+  y: Vec<f64>,
+  z: Vec<f64>,
+  strength_x: Vec<f64>,
+  strength_y: Vec<f64>,
+  strength_z: Vec<f64>,
+  core_radius: Vec<f64>,
+}
+
+impl From<&[Vorton]> for VortonArrays {
+  fn from(vortons: &[Vorton]) -> Self {
+    vortons.iter().collect()
+  }
+}
+
+impl<'a> FromIterator<&'a Vorton> for VortonArrays {
+  fn from_iter<I: IntoIterator<Item = &'a Vorton>>(iter: I) -> Self {
+    let mut arrays = VortonArrays::default();
+    for v in iter {
+      let strength = v.vorticity().scale(v.volume());
+      arrays.x.push(v.position().x); arrays.y.push(v.position().y); arrays.z.push(v.position().z);
+      arrays.strength_x.push(strength.x); arrays.strength_y.push(strength.y); arrays.strength_z.push(strength.z);
+      arrays.core_radius.push(v.core_radius());
+    }
+    arrays
+  }
+}
+
+impl VortonArrays {
+  /// Make the arrays from the vortons at `indices`, in that order
+  pub fn from_indices(vortons: &[Vorton], indices: &[usize]) -> VortonArrays {
+    indices.iter().map(|i| &vortons[*i]).collect()
+  }
+
+  pub fn len(&self) -> usize { self.x.len() }
+
+  pub fn is_empty(&self) -> bool { self.x.is_empty() }
+
+  /// Returns the velocity induced at `position` by the vortons in `range`, consistently with
+  /// `Vorton::velocity_contribution`
+  pub fn velocity_at(&self, kernel: &Kernel, range: Range<usize>, position: &Point3<f64>) -> Vector3<f64> {
+    let u = match kernel {
+      Kernel::SolidSphere => self.sum(range, position, |r, a, s| {
+        let d = (r[0] * r[0] + r[1] * r[1] + r[2] * r[2]).sqrt().max(s);
+        velocity_term(r, a, FRAC_1_4PI / (d * d * d))
+      }),
+      Kernel::RosenheadMoore => self.sum(range, position, |r, a, s| {
+        let e2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2] + s * s;
+        velocity_term(r, a, FRAC_1_4PI / (e2 * e2.sqrt()))
+      }),
+      Kernel::WinckelmansLeonard => self.sum(range, position, |r, a, s| {
+        let d2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+        let e2 = d2 + s * s;
+        velocity_term(r, a, FRAC_1_4PI * (d2 + 2.5 * s * s) / (e2 * e2 * e2.sqrt()))
+      }),
+      Kernel::Gaussian => self.sum(range, position, |r, a, s| {
+        let d = (r[0] * r[0] + r[1] * r[1] + r[2] * r[2]).sqrt();
+        velocity_term(r, a, kernel.velocity_factor(d, s))
+      }),
+    };
+    Vector3::new(u[0], u[1], u[2])
+  }
+
+  /// Returns the velocity gradient tensor induced at `position` by the vortons in `range`,
+  /// with entry `[i][j]` being `du_i/dx_j`, consistently with
+  /// `Vorton::velocity_gradient_contribution`
+  pub fn velocity_gradient_at(&self, kernel: &Kernel, range: Range<usize>, position: &Point3<f64>) -> Matrix3<f64> {
+    let g = match kernel {
+      Kernel::SolidSphere => self.sum(range, position, |r, a, s| {
+        let d2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+        let d = d2.sqrt().max(s);
+        let h = if d2 > s * s { -3.0 * FRAC_1_4PI / (d2 * d2 * d) } else { 0.0 };
+        gradient_term(r, a, FRAC_1_4PI / (d * d * d), h)
+      }),
+      Kernel::RosenheadMoore => self.sum(range, position, |r, a, s| {
+        let e2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2] + s * s;
+        let g = FRAC_1_4PI / (e2 * e2.sqrt());
+        gradient_term(r, a, g, -3.0 * g / e2)
+      }),
+      Kernel::WinckelmansLeonard => self.sum(range, position, |r, a, s| {
+        let d2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+        let e2 = d2 + s * s;
+        let c = FRAC_1_4PI / (e2 * e2 * e2.sqrt());
+        gradient_term(r, a, c * (d2 + 2.5 * s * s), -c * (3.0 * d2 + 10.5 * s * s) / e2)
+      }),
+      Kernel::Gaussian => self.sum(range, position, |r, a, s| {
+        let d = (r[0] * r[0] + r[1] * r[1] + r[2] * r[2]).sqrt();
+        gradient_term(r, a, kernel.velocity_factor(d, s), kernel.gradient_factor(d, s))
+      }),
+    };
+    Matrix3::new([[g[0], g[1], g[2]], [g[3], g[4], g[5]], [g[6], g[7], g[8]]])
+  }
+
+  /// Returns the sum of `term(r, α, σ)` over the vortons in `range`, `r` being the offset of
+  /// `position` from the vorton. The terms are accumulated in `LANES` independent sums.
+  #[inline(always)]
+  fn sum<const N: usize, F>(&self, range: Range<usize>, position: &Point3<f64>, term: F) -> [f64; N]
+  where F: Fn([f64; 3], [f64; 3], f64) -> [f64; N]
+  {
+    let x = &self.x[range.clone()];
+    let n = x.len();
+    // Slices of the same length, so that the bounds checks are elided
+    let (y, z) = (&self.y[range.clone()][..n], &self.z[range.clone()][..n]);
+    let (ax, ay, az) = (&self.strength_x[range.clone()][..n], &self.strength_y[range.clone()][..n], &self.strength_z[range.clone()][..n]);
+    let s = &self.core_radius[range][..n];
+    let (px, py, pz) = (position.x, position.y, position.z);
+    let at = |i: usize| term([px - x[i], py - y[i], pz - z[i]], [ax[i], ay[i], az[i]], s[i]);
+
+    let mut lanes = [[0.0; N]; LANES];
+    let chunks = n - n % LANES;
+    for c in (0..chunks).step_by(LANES) {
+      for (l, lane) in lanes.iter_mut().enumerate() {
+        for (a, t) in lane.iter_mut().zip(at(c + l)) { *a += t; }
+      }
+    }
+    for i in chunks..n {
+      for (a, t) in lanes[0].iter_mut().zip(at(i)) { *a += t; }
+    }
+    lanes.iter().fold([0.0; N], |mut r, lane| {
+      for (a, t) in r.iter_mut().zip(lane) { *a += t; }
+      r
+    })
+  }
+}
+
+const FRAC_1_4PI: f64 = 0.25 * std::f64::consts::FRAC_1_PI;
+
+/// Returns `g α × r`
+#[inline(always)]
+fn velocity_term(r: [f64; 3], a: [f64; 3], g: f64) -> [f64; 3] {
+  [g * (a[1] * r[2] - a[2] * r[1]),
+   g * (a[2] * r[0] - a[0] * r[2]),
+   g * (a[0] * r[1] - a[1] * r[0])]
+}
+
+/// Returns `g [α×] + h (α × r) ⊗ r` by rows
+#[inline(always)]
+fn gradient_term(r: [f64; 3], a: [f64; 3], g: f64, h: f64) -> [f64; 9] {
+  let c = velocity_term(r, a, h);
+  [c[0] * r[0],          c[0] * r[1] - g * a[2], c[0] * r[2] + g * a[1],
+   c[1] * r[0] + g * a[2], c[1] * r[1],          c[1] * r[2] - g * a[0],
+   c[2] * r[0] - g * a[1], c[2] * r[1] + g * a[0], c[2] * r[2]]
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_matches_vorton_contributions() {
+    let vortons = (0..11)
+      .map(|i| {
+        let t = i as f64;
+        Vorton::new(Point3::new(0.1 * t.cos(), 0.05 * t, 0.1 * t.sin()), Vector3::new(t.sin(), 1.0, 0.5 * t), 1e-3 * (1.0 + 0.1 * t))
+      })
+      .collect::<Vec<Vorton>>();
+    let arrays = VortonArrays::from(vortons.as_slice());
+    assert_eq!(arrays.len(), vortons.len());
+    for kernel in [Kernel::SolidSphere, Kernel::Gaussian, Kernel::RosenheadMoore, Kernel::WinckelmansLeonard] {
+      // Positions both outside of and inside the vorton cores
+      for p in [Point3::new(0.5, 0.5, 0.5), Point3::new(0.1, 0.0, 0.01), Point3::new(0.0, 0.25, 0.0)] {
+        let range = 1..10;
+        let u = vortons[range.clone()].iter().fold(Vector3::default(), |r, v| r + v.velocity_contribution(&kernel, &p));
+        let g = vortons[range.clone()].iter().fold(Matrix3::default(), |r, v| r + v.velocity_gradient_contribution(&kernel, &p));
+        let (u_arrays, g_arrays) = (arrays.velocity_at(&kernel, range.clone(), &p), arrays.velocity_gradient_at(&kernel, range, &p));
+        println!("{kernel:?} at {p:?}: {u:?} vs {u_arrays:?}");
+        assert!((u_arrays - u.clone()).norm() < 1e-12 * u.norm());
+        assert!((g_arrays - g.clone()).norm() < 1e-12 * g.norm());
+      }
+    }
+  }
+}
@@ -1,6 +1,6 @@
-use crate::{sim, Profiler, Vector3, Vorton,
-  VortonToVelocity, VortonToVelocitySimpleBuilder, VortonToVelocityTreeBuilder, 
-  Geometry, 
+use crate::{sim, parallel, Profiler, Point3, Vector3, Matrix3, Vorton, Stretching, Diffusion, Kernel, Buoyancy, Remeshing, Periodicity, PopulationControl, PopulationReport, Tracers, Seeding, Emitter, TimeIntegrator, Derivative, TimeStepController,
+  VortonToVelocity, VortonToVelocitySimpleBuilder, VortonToVelocityTree, VortonToVelocityTreeBuilder, AccuracyReport, VortonToVelocityFmmBuilder, VortonToVelocityVic, VortonToVelocityVicBuilder, 
+  Geometry, Octree, GreenFunction, Panels,
 };
 
 /// Vortex simulation root object
@@ -13,57 +13,330 @@ pub struct Simulation {
     vortons: Vec<sim::Vorton>,
     #[serde(default="default_vorton_to_velocity")]
     vorton_to_velocity_algorithm: VortonToVelocityAlgorithm,
+    #[serde(default="default_time_integrator")]
+    time_integrator: TimeIntegrator,
+    /// Derivatives from the previous step, used by multi-step time integrators
+    #[serde(default)]
+    history: Vec<Option<Derivative>>,
+    /// Time step of the previous step, for which the history was evaluated
+    #[serde(default)]
+    previous_time_step: Option<f64>,
+    #[serde(default)]
+    time_step_controller: TimeStepController,
+    #[serde(default="default_stretching")]
+    stretching: Stretching,
+    #[serde(default="default_diffusion")]
+    diffusion: Diffusion,
+    #[serde(default)]
+    kernel: Kernel,
+    /// Remeshing of the vortons onto a regular grid, disabled when `None`
+    #[serde(default)]
+    remeshing: Option<Remeshing>,
+    /// Iteration at which the vortons were last remeshed, if any
+    #[serde(default)]
+    last_remesh: Option<usize>,
+    /// Boussinesq buoyancy of the density perturbation carried by the vortons, if any
+    #[serde(default)]
+    buoyancy: Option<Buoyancy>,
+    /// Periodic boundary conditions of the domain, if any
+    #[serde(default)]
+    periodicity: Option<Periodicity>,
+    #[serde(default)]
+    population_control: PopulationControl,
+    /// Changes made to the vorton population during the last step
+    #[serde(skip)]
+    population_report: PopulationReport,
+    /// Passive tracers advected by the flow
+    #[serde(default)]
+    tracers: Tracers,
     geometries: Vec<Geometry>,
+    /// Solve a panel method cancelling the flow through the geometries at each step
+    #[serde(default)]
+    panel_method: bool,
+    /// Panels of the geometries solved at the last step, added to the velocity of the vortons
+    #[serde(skip)]
+    panels: Option<Panels>,
+    /// Octree of the tree algorithm, kept across the evaluations while the vortons are
+    /// neither added nor removed
+    #[serde(skip)]
+    octree: std::sync::Mutex<Option<std::sync::Arc<Octree>>>,
+    /// Green's function of the last evaluation of the VIC algorithm, reused while the grid
+    /// keeps its size
+    #[serde(skip)]
+    green: std::sync::Mutex<Option<std::sync::Arc<GreenFunction>>>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "VortonToVelocityAlgorithmDefinition")]
 pub enum VortonToVelocityAlgorithm {
   Simple,
-  Tree(usize),
+  /// Barnes–Hut tree with the opening angle `theta`, subdividing cells with more than
+  /// `max_leaf` vortons up to `max_depth` levels. The tree is refitted to the advected
+  /// vortons until its cells grow by more than `rebuild_threshold`.
+  Tree {
+    theta: f64,
+    max_leaf: usize,
+    max_depth: usize,
+    rebuild_threshold: f64,
+  },
+  /// Fast multipole method with the nominated expansion order
+  Fmm(usize),
+  /// Vortex-In-Cell method on a grid matching the vorton volume
+  Vic,
+}
+
+/// Definition of the algorithm read from a file, which accepts the legacy form of the
+/// tree, `"Tree": max_depth`, as well as its parameters with defaults
+#[derive(serde::Deserialize)]
+enum VortonToVelocityAlgorithmDefinition {
+  Simple,
+  Tree(TreeDefinition),
+  Fmm(usize),
+  Vic,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum TreeDefinition {
+  MaxDepth(usize),
+  Parameters {
+    #[serde(default="default_theta")]
+    theta: f64,
+    #[serde(default)]
+    max_leaf: usize,
+    #[serde(default="default_max_depth")]
+    max_depth: usize,
+    #[serde(default="default_rebuild_threshold")]
+    rebuild_threshold: f64,
+  },
+}
+
+impl From<VortonToVelocityAlgorithmDefinition> for VortonToVelocityAlgorithm {
+  fn from(definition: VortonToVelocityAlgorithmDefinition) -> Self {
+    match definition {
+      VortonToVelocityAlgorithmDefinition::Simple => VortonToVelocityAlgorithm::Simple,
+      VortonToVelocityAlgorithmDefinition::Tree(TreeDefinition::MaxDepth(max_depth)) =>
+        VortonToVelocityAlgorithm::Tree { theta: default_theta(), max_leaf: 0, max_depth, rebuild_threshold: default_rebuild_threshold() },
+      VortonToVelocityAlgorithmDefinition::Tree(TreeDefinition::Parameters { theta, max_leaf, max_depth, rebuild_threshold }) =>
+        VortonToVelocityAlgorithm::Tree { theta, max_leaf, max_depth, rebuild_threshold },
+      VortonToVelocityAlgorithmDefinition::Fmm(order) => VortonToVelocityAlgorithm::Fmm(order),
+      VortonToVelocityAlgorithmDefinition::Vic => VortonToVelocityAlgorithm::Vic,
+    }
+  }
 }
 
 fn default_vorton_to_velocity() -> VortonToVelocityAlgorithm {
   VortonToVelocityAlgorithm::Simple
 }
 
+fn default_theta() -> f64 {
+  crate::algorithms::DEFAULT_THETA
+}
+
+fn default_max_depth() -> usize {
+  6
+}
+
+fn default_rebuild_threshold() -> f64 {
+  crate::algorithms::DEFAULT_REBUILD_THRESHOLD
+}
+
+impl VortonToVelocityAlgorithm {
+  /// Tree algorithm with the default parameters
+  pub fn tree() -> VortonToVelocityAlgorithm {
+    VortonToVelocityAlgorithm::Tree { theta: default_theta(), max_leaf: 0, max_depth: default_max_depth(), rebuild_threshold: default_rebuild_threshold() }
+  }
+}
+
+fn default_time_integrator() -> TimeIntegrator {
+  TimeIntegrator::Euler
+}
+
+fn default_stretching() -> Stretching {
+  Stretching::Classical
+}
+
+fn default_diffusion() -> Diffusion {
+  Diffusion::Decay
+}
+
 impl std::convert::TryFrom<&crate::configuration::Configuration> for Simulation {
   type Error = Box<dyn std::error::Error>;
   /// Make a new simulation from a configuration
   fn try_from(c: &crate::configuration::Configuration) -> Result<Self, Self::Error> {
+    let mut tracers = Tracers::default();
+    for seeding in c.tracers.iter() { tracers.seed(seeding); }
+    for emitter in c.emitters.iter() { tracers.push_emitter(emitter.clone()); }
     Ok(Simulation {
       time: 0.0,
       iteration: 0,
       viscosity: c.viscosity,
       free_stream_velocity: c.get_initial_conditions().free_stream_velocity(),
-      vortons: crate::sim::functions::make_vortons(&c)?,
+      vortons: crate::sim::functions::make_vortons(c)?,
       vorton_to_velocity_algorithm: VortonToVelocityAlgorithm::Simple,
+      time_integrator: default_time_integrator(),
+      history: Vec::new(),
+      previous_time_step: None,
+      time_step_controller: TimeStepController::default(),
+      stretching: default_stretching(),
+      diffusion: c.diffusion.clone(),
+      kernel: c.kernel,
+      remeshing: None,
+      last_remesh: None,
+      buoyancy: (c.gravity.norm() > 0.0 || c.diffusivity > 0.0)
+        .then(|| Buoyancy { gravity: c.gravity.clone(), diffusivity: c.diffusivity, ..Buoyancy::default() }),
+      periodicity: c.domain.periodicity(),
+      population_control: PopulationControl::default(),
+      population_report: PopulationReport::default(),
+      tracers,
       geometries: Vec::new(),
+      panel_method: false,
+      panels: None,
+      octree: Default::default(),
+      green: Default::default(),
     })
   }
 }
 
+/// Velocity of the vortons with the velocity of the panels of the geometries
+struct WithPanels<'a> {
+    vorton_to_velocity: Box<dyn VortonToVelocity + 'a>,
+    panels: &'a Panels,
+}
+
+impl VortonToVelocity for WithPanels<'_> {
+    fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
+        Ok(self.vorton_to_velocity.velocity_at(position)? + self.panels.velocity_at(position))
+    }
+
+    fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+        Ok(self.vorton_to_velocity.velocity_gradient_at(position)? + self.panels.velocity_gradient_at(position))
+    }
+}
+
 impl Simulation {
     pub fn push_geometry(&mut self, geometry: Geometry) -> Result<(), Box<dyn std::error::Error>> {
       self.geometries.push(geometry); Ok(())
     }
 
+    /// Seed tracers in the flow
+    pub fn seed_tracers(&mut self, seeding: &Seeding) {
+      self.tracers.seed(seeding);
+    }
+
+    /// Add an emitter releasing tracers at each step
+    pub fn push_emitter(&mut self, emitter: Emitter) {
+      self.tracers.push_emitter(emitter);
+    }
+
     /*
      * Accessor functions
      */
     pub fn iteration(&self) -> usize        { self.iteration }
     pub fn time(&self) -> f64               { self.time }
     pub fn vortons(&self) -> &Vec<Vorton>   { &self.vortons }
+    pub fn free_stream_velocity(&self) -> &Vector3<f64> { &self.free_stream_velocity }
+    pub fn population_report(&self) -> &PopulationReport { &self.population_report }
+    pub fn last_remesh(&self) -> Option<usize> { self.last_remesh }
+    pub fn tracers(&self) -> &Tracers       { &self.tracers }
+    pub fn panels(&self) -> Option<&Panels> { self.panels.as_ref() }
 
     pub fn use_vorton_to_velocity(&mut self, vorton_to_velocity_algorithm: VortonToVelocityAlgorithm) {
       self.vorton_to_velocity_algorithm = vorton_to_velocity_algorithm;
+      self.reset_octree();
+    }
+    /// Nominate the formulation used for the vortex stretching/tilting term
+    pub fn use_stretching(&mut self, stretching: Stretching) {
+      self.stretching = stretching;
+    }
+    /// Nominate the scheme used to advance vortons in time
+    pub fn use_time_integrator(&mut self, time_integrator: TimeIntegrator) {
+      self.time_integrator = time_integrator; self.history.clear();
+    }
+    /// Nominate the controller used to select time steps in `step_adaptive`
+    pub fn use_time_step_controller(&mut self, time_step_controller: TimeStepController) {
+      self.time_step_controller = time_step_controller;
+    }
+    /// Nominate the remeshing of the vortons onto a regular grid, or disable it with `None`
+    pub fn use_remeshing(&mut self, remeshing: Option<Remeshing>) {
+      self.remeshing = remeshing;
+    }
+    /// Nominate the culling, merging and splitting applied to the vortons after each step
+    pub fn use_population_control(&mut self, population_control: PopulationControl) {
+      self.population_control = population_control;
+    }
+    /// Nominate the buoyancy of the density perturbation, or disable it with `None`
+    pub fn use_buoyancy(&mut self, buoyancy: Option<Buoyancy>) {
+      self.buoyancy = buoyancy;
+    }
+    /// Nominate the periodic boundary conditions, or disable them with `None`
+    pub fn use_periodicity(&mut self, periodicity: Option<Periodicity>) {
+      self.periodicity = periodicity;
+    }
+    /// Nominate whether a panel method cancels the flow through the geometries, in addition
+    /// to the vortons generated on their surface
+    pub fn use_panel_method(&mut self, panel_method: bool) {
+      self.panel_method = panel_method;
+      if !panel_method { self.panels = None; }
     }
     pub fn get_vorton_to_velocity(&self) -> Result<Box<dyn VortonToVelocity + '_>, Box<dyn std::error::Error>> {
-      match &self.vorton_to_velocity_algorithm {
+      self.make_vorton_to_velocity(&self.vortons)
+    }
+
+    /// Discard the octree kept by the tree algorithm, once vortons are added or removed
+    fn reset_octree(&mut self) {
+      *self.octree.get_mut().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Make the velocity algorithm associated with an arbitrary set of vortons. The tree
+    /// algorithm refits the octree of the previous evaluation, which requires `vortons` to
+    /// be the vortons of the simulation, possibly displaced.
+    /// The velocity of the panels solved at the last step, if any, is added to the velocity
+    /// of the vortons.
+    fn make_vorton_to_velocity<'a>(&'a self, vortons: &'a [Vorton]) -> Result<Box<dyn VortonToVelocity + 'a>, Box<dyn std::error::Error>> {
+      let vorton_to_velocity: Box<dyn VortonToVelocity + 'a> = match &self.vorton_to_velocity_algorithm {
+        VortonToVelocityAlgorithm::Fmm(_) | VortonToVelocityAlgorithm::Vic if self.periodicity.is_some()
+        => return Err("Periodic domains are only supported by the simple and tree algorithms".into()),
         VortonToVelocityAlgorithm::Simple 
-        => Ok(Box::new(VortonToVelocitySimpleBuilder::default().vortons(&self.vortons).velocity(&self.free_stream_velocity).build()?)),
-        VortonToVelocityAlgorithm::Tree(n_grids) 
-        => Ok(Box::new(VortonToVelocityTreeBuilder::default().vortons(&self.vortons).velocity(&self.free_stream_velocity).n_grids(*n_grids).build()?.initialize()?)),
-      }
+        => Box::new(VortonToVelocitySimpleBuilder::default().vortons(vortons).velocity(&self.free_stream_velocity).kernel(self.kernel).periodicity(self.periodicity.clone()).build()?),
+        VortonToVelocityAlgorithm::Tree { .. }
+        => Box::new(self.make_tree(vortons)?),
+        VortonToVelocityAlgorithm::Fmm(order)
+        => Box::new(VortonToVelocityFmmBuilder::default().vortons(vortons).velocity(&self.free_stream_velocity).kernel(self.kernel).order(*order).build()?.initialize()?),
+        VortonToVelocityAlgorithm::Vic
+        => Box::new(self.make_vic(vortons)?),
+      };
+      Ok(match &self.panels {
+        Some(panels) => Box::new(WithPanels { vorton_to_velocity, panels }),
+        None => vorton_to_velocity,
+      })
+    }
+
+    /// Make the tree algorithm, refitting the octree of the previous evaluation
+    fn make_tree<'a>(&'a self, vortons: &'a [Vorton]) -> Result<VortonToVelocityTree<'a>, Box<dyn std::error::Error>> {
+      let VortonToVelocityAlgorithm::Tree { theta, max_leaf, max_depth, rebuild_threshold } = &self.vorton_to_velocity_algorithm
+        else { return Err("The simulation does not use the tree algorithm".into()) };
+      let mut octree = self.octree.lock().map_err(|e| e.to_string())?;
+      let tree = VortonToVelocityTreeBuilder::default().vortons(vortons).velocity(&self.free_stream_velocity).kernel(self.kernel)
+                   .theta(*theta).max_leaf(*max_leaf).max_depth(*max_depth).rebuild_threshold(*rebuild_threshold)
+                   .octree(octree.take()).periodicity(self.periodicity.clone()).build()?.initialize()?;
+      *octree = tree.octree();
+      Ok(tree)
+    }
+
+    /// Make the VIC algorithm, reusing the Green's function of the previous evaluation
+    fn make_vic<'a>(&'a self, vortons: &'a [Vorton]) -> Result<VortonToVelocityVic<'a>, Box<dyn std::error::Error>> {
+      let mut green = self.green.lock().map_err(|e| e.to_string())?;
+      let vic = VortonToVelocityVicBuilder::default().vortons(vortons).velocity(&self.free_stream_velocity).kernel(self.kernel)
+                  .green(green.take()).build()?.initialize()?;
+      *green = vic.green();
+      Ok(vic)
+    }
+
+    /// Compare the tree algorithm to the direct sum at `n_samples` vortons selected at
+    /// random, which differ at each iteration
+    pub fn accuracy_report(&self, n_samples: usize) -> Result<AccuracyReport, Box<dyn std::error::Error>> {
+      self.make_tree(&self.vortons)?.accuracy_report(n_samples, self.iteration as u64)
     }
 
     /*
@@ -90,53 +363,142 @@ impl Simulation {
     {
         self.iteration += 1; self.time += time_step;
         println!("start: {} vortons", self.vortons.len());
-        self.step_vorticity(time_step, profiler)?; // Simulation::make_timer(profiler, "step_vorticity"))?;
         self.advect_vortons(time_step, profiler)?; // Simulation::make_timer(profiler, "advect_vortons"))?;
+        self.control_population(profiler)?;
+        self.remesh_vortons(profiler)?;
         for g in self.geometries.iter_mut() { g.step(time_step)?; }
         
-        self.vortons = self.vortons.iter()
-             .filter(|v| 
-                self.geometries.iter()
-                .fold(Ok(true), |r: Result<bool, Box<dyn std::error::Error>>, g| 
-                                   r.and_then(|r| Ok(r && !g.is_inside(v.position())?))
-                     ).unwrap_or(false)
-             )
-             .cloned()
-             .collect();
+        let geometries = std::mem::take(&mut self.geometries);
+        self.retain_vortons(|v| 
+                geometries.iter()
+                .try_fold(true, |r, g| 
+                                   Ok::<bool, Box<dyn std::error::Error>>(r && !g.is_inside(v.position())?)
+                     ).or(Ok(false))
+             )?;
+        self.tracers.retain(|t|
+                geometries.iter()
+                .try_fold(true, |r, g| Ok::<bool, Box<dyn std::error::Error>>(r && !g.is_inside(t.position())?))
+             )?;
+        self.geometries = geometries;
         println!("Geometry: {} vortons", self.vortons.len());
         
+        self.solve_panels()?;
         self.enforce_geometry()?;
         println!("Enforce: {} vortons", self.vortons.len());
         
         Ok(())
     }
 
+    /// Advance the simulation up to `target_time` using time steps selected by the time
+    /// step controller. Returns the time steps of the sub-steps taken.
+    pub fn step_adaptive<F>(&mut self, target_time: f64, profiler: &mut Profiler<F>) -> Result<Vec<f64>, Box<dyn std::error::Error>> 
+        where F: Fn() -> f64
+    {
+        let mut time_steps = Vec::new();
+        while self.time < target_time - 1e-12 {
+            let time_step = self.adaptive_time_step()?;
+            // Avoid leaving a sliver of time to be covered by an extra sub-step: split what
+            // remains past one time step in two equal sub-steps
+            let remaining = target_time - self.time;
+            let time_step = if remaining <= time_step { remaining }
+                            else if remaining < 2.0*time_step { 0.5*remaining }
+                            else { time_step };
+            self.step(time_step, profiler)?;
+            time_steps.push(time_step);
+        }
+        Ok(time_steps)
+    }
+
+    /// Time step selected by the time step controller for the current vortons
+    fn adaptive_time_step(&self) -> Result<f64, Box<dyn std::error::Error>> {
+        let vorton_to_velocity = self.get_vorton_to_velocity()?;
+        let values = parallel::try_map(&self.vortons, |vorton|
+            Ok((vorton_to_velocity.velocity_at(vorton.position())?,
+                vorton_to_velocity.velocity_gradient_at(vorton.position())?)))?;
+        Ok(self.time_step_controller.time_step(self.vortons.iter().zip(values).map(|(vorton, (u, g))| (vorton, u, g))))
+    }
+
     /*
     pub fn print_profiling(&self) {
         self.profiling.print();
     }
     */
 
-/*
-    /// Report the velocity at a given point
+    /// Returns the velocity at `position`, including the free stream velocity, evaluated
+    /// using the active velocity algorithm. Use `velocities_at` to sample many positions, as
+    /// the algorithm is set up at each call.
     pub fn velocity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
-      Ok(
-      sim::functions::velocity_at(position, 
-                                  self.free_stream_velocity.clone(),
-                                  self.vortons.iter())
-      )
+      self.get_vorton_to_velocity()?.velocity_at(position)
+    }
+
+    /// Returns the velocity gradient tensor at `position`, with entry `[i][j]` being `du_i/dx_j`
+    pub fn velocity_gradient_at(&self, position: &Point3<f64>) -> Result<Matrix3<f64>, Box<dyn std::error::Error>> {
+      self.get_vorton_to_velocity()?.velocity_gradient_at(position)
+    }
+
+    /// Returns the vorticity at `position`, ie the curl of the velocity induced by the vortons
+    pub fn vorticity_at(&self, position: &Point3<f64>) -> Result<Vector3<f64>, Box<dyn std::error::Error>> {
+      Ok(self.velocity_gradient_at(position)?.curl())
+    }
+
+    /// Returns the velocity at each of `positions`
+    pub fn velocities_at(&self, positions: &[Point3<f64>]) -> Result<Vec<Vector3<f64>>, Box<dyn std::error::Error>> {
+      let vorton_to_velocity = self.get_vorton_to_velocity()?;
+      parallel::try_map(positions, |p| vorton_to_velocity.velocity_at(p))
+    }
+
+    /// Returns the velocity gradient tensor at each of `positions`
+    pub fn velocity_gradients_at(&self, positions: &[Point3<f64>]) -> Result<Vec<Matrix3<f64>>, Box<dyn std::error::Error>> {
+      let vorton_to_velocity = self.get_vorton_to_velocity()?;
+      parallel::try_map(positions, |p| vorton_to_velocity.velocity_gradient_at(p))
+    }
+
+    /// Returns the vorticity at each of `positions`
+    pub fn vorticities_at(&self, positions: &[Point3<f64>]) -> Result<Vec<Vector3<f64>>, Box<dyn std::error::Error>> {
+      Ok(self.velocity_gradients_at(positions)?.iter().map(|g| g.curl()).collect())
+    }
+
+    /// Retain the vortons, and their associated history, that satisfy the predicate
+    fn retain_vortons<P>(&mut self, predicate: P) -> Result<(), Box<dyn std::error::Error>>
+        where P: Fn(&Vorton) -> Result<bool, Box<dyn std::error::Error>> + Sync + Send
+    {
+        let keep = parallel::try_map(&self.vortons, predicate)?;
+        self.retain_vortons_by_mask(&keep);
+        Ok(())
+    }
+
+    /// Retain the vortons, and their associated history, flagged in `keep`
+    fn retain_vortons_by_mask(&mut self, keep: &[bool]) {
+        if keep.iter().any(|k| !k) { self.reset_octree(); }
+        let mut i = 0; self.vortons.retain(|_| { i += 1; keep[i - 1] });
+        if ! self.history.is_empty() {
+          let mut i = 0; self.history.retain(|_| { i += 1; keep[i - 1] });
+        }
+    }
+
+    /// Solve the panels of all the geometries at once in the flow of the vortons, reusing the
+    /// factorized system of the previous step while the panels move rigidly
+    fn solve_panels(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+      let previous = self.panels.take();
+      if !self.panel_method || self.geometries.is_empty() { return Ok(()); }
+      let panels = {
+        let vorton_to_velocity = self.get_vorton_to_velocity()?;
+        Panels::solve(self.geometries.iter().flat_map(|g| g.panels()).collect(), previous.as_ref(), |p| vorton_to_velocity.velocity_at(p))?
+      };
+      self.panels = Some(panels);
+      Ok(())
     }
-*/
 
     fn enforce_geometry(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-      self.vortons.append(&mut {
+      let mut vortons = {
         let vorton_to_velocity = self.get_vorton_to_velocity()?;
-        self.geometries.iter()
-            .map(|g| g.enforce(|p| vorton_to_velocity.velocity_at(p)))
-            .collect::<Result<Vec<Vec<Vorton>>, Box<dyn std::error::Error>>>()?
+        parallel::try_map(&self.geometries, |g| g.enforce(&self.kernel, |p| vorton_to_velocity.velocity_at(p)))?
             .iter_mut()
             .fold(Vec::new(), |mut r, i| {r.append(i); r})
-      });
+      };
+      if ! vortons.is_empty() { self.reset_octree(); }
+      self.vortons.append(&mut vortons);
+      if ! self.history.is_empty() { self.history.resize(self.vortons.len(), None); }
       Ok(())
     }
     /*
@@ -146,39 +508,91 @@ impl Simulation {
         where F: Fn() -> f64
     {
         profiler.start("advect_vortons".to_string());
-        self.vortons = {
-          let vorton_to_velocity = self.get_vorton_to_velocity()?;
-          self.vortons.iter()
-            .filter(|vorton| vorton.vorticity().norm() > 1e-5)
-            .map(|vorton|  {
-                let v = vorton_to_velocity.velocity_at(vorton.position())?;
-                Ok(vorton.advect(&v, time_step))
-            })
-            .collect::<Result<Vec<Vorton>, Box<dyn std::error::Error>>>()?
-        };
+        let (mut history, mut previous_time_step) = (std::mem::take(&mut self.history), self.previous_time_step);
+        let vortons = self.time_integrator
+            .step(&self.vortons, &mut history, &mut previous_time_step, time_step, |vortons| self.derivatives(vortons))?;
+        self.history = history; self.previous_time_step = previous_time_step;
+        self.advect_tracers(&vortons, time_step)?;
+        self.vortons = vortons;
+        if let Some(periodicity) = &self.periodicity {
+            self.vortons = self.vortons.iter()
+                .map(|v| Vorton::new(periodicity.wrap(v.position()), v.vorticity().clone(), v.volume()).with_density(v.density()))
+                .collect();
+            self.tracers.wrap(periodicity);
+        }
         profiler.finish("advect_vortons".to_string());
         Ok(())
     }
 
-    fn step_vorticity<F>(&mut self, time_step: f64, profiler: &mut Profiler<F>) -> Result<(), Box<dyn std::error::Error>> 
+    /// Advect the tracers using the velocity induced by the vortons at the start of the
+    /// step and by the advected `vortons` at the end of the step. Each evaluation is dropped
+    /// before the next is made, so that the octree of the tree algorithm is refitted in place.
+    fn advect_tracers(&mut self, vortons: &[Vorton], time_step: f64) -> Result<(), Box<dyn std::error::Error>> {
+        if self.tracers.is_empty() { return Ok(()); }
+        let mut tracers = std::mem::take(&mut self.tracers);
+        let velocities = |vortons: &[Vorton], positions: &[Point3<f64>]| {
+            let vorton_to_velocity = self.make_vorton_to_velocity(vortons)?;
+            parallel::try_map(positions, |p| vorton_to_velocity.velocity_at(p))
+        };
+        let result = tracers.step(time_step, |p| velocities(&self.vortons, p), |p| velocities(vortons, p));
+        self.tracers = tracers;
+        result
+    }
+
+    /// Cull, merge and split the vortons as nominated by the population control. The
+    /// history of multi-step time integrators is discarded when vortons are merged or split.
+    fn control_population<F>(&mut self, profiler: &mut Profiler<F>) -> Result<(), Box<dyn std::error::Error>> 
         where F: Fn() -> f64
     {
-        profiler.start("step_vorticity".to_string());
-        self.vortons = self.vortons.iter()
-            .map(|vorton| 
-                 {
-                     let rhs = Vector3::<f64>::new(0.0, 0.0, 0.0)
-                         // .add(&self.grid.point_interpolate(&velocity_gradient, vorton.position())
-                              // .mult(vorton.vorticity())
-                              // ) 
-                         + vorton.vorticity().scale(-0.1*self.viscosity); 
-                     vorton.step(&rhs, time_step)
-                 })
-            .collect::<Vec<Vorton>>();
-        profiler.finish("step_vorticity".to_string());
+        profiler.start("control_population".to_string());
+        let (keep, culled_circulation) = self.population_control.cull(&self.vortons);
+        let culled = keep.iter().filter(|k| !**k).count();
+        self.retain_vortons_by_mask(&keep);
+        let (vortons, merged, merged_circulation) = self.population_control.merge(std::mem::take(&mut self.vortons));
+        let (vortons, split) = self.population_control.split(vortons);
+        self.vortons = vortons;
+        if merged > 0 || split > 0 { self.history.clear(); self.reset_octree(); }
+        self.population_report = PopulationReport { culled, culled_circulation, merged, merged_circulation, split };
+        profiler.finish("control_population".to_string());
         Ok(())
     }
 
+    /// Remesh the vortons when due. The history of multi-step time integrators does not
+    /// apply to the new vortons and is discarded.
+    fn remesh_vortons<F>(&mut self, profiler: &mut Profiler<F>) -> Result<(), Box<dyn std::error::Error>> 
+        where F: Fn() -> f64
+    {
+        let Some(remeshing) = &self.remeshing else { return Ok(()) };
+        if remeshing.is_due(self.iteration, &self.vortons) {
+            profiler.start("remesh_vortons".to_string());
+            self.vortons = remeshing.remesh(&self.vortons)?;
+            self.history.clear();
+            self.reset_octree();
+            self.last_remesh = Some(self.iteration);
+            profiler.finish("remesh_vortons".to_string());
+        }
+        Ok(())
+    }
+
+    /// Evaluate the time derivative of the nominated vortons: the velocity at each vorton,
+    /// the rate of change of vorticity due to stretching, diffusion and buoyancy, and the
+    /// rate of change of the density perturbation.
+    fn derivatives(&self, vortons: &[Vorton]) -> Result<Vec<Derivative>, Box<dyn std::error::Error>> {
+        let vorton_to_velocity = self.make_vorton_to_velocity(vortons)?;
+        let diffusion = self.diffusion.rates(vortons, self.viscosity);
+        let buoyancy = match &self.buoyancy {
+            Some(buoyancy) => buoyancy.rates(vortons),
+            None => vec![(Vector3::default(), 0.0); vortons.len()],
+        };
+        let items = vortons.iter().zip(diffusion).zip(buoyancy).collect::<Vec<_>>();
+        parallel::try_map(&items, |((vorton, diffusion), (baroclinic, density))| {
+            let velocity = vorton_to_velocity.velocity_at(vorton.position())?;
+            let gradient = vorton_to_velocity.velocity_gradient_at(vorton.position())?;
+            let vorticity = self.stretching.source(vorton.vorticity(), &gradient) + diffusion.clone() + baroclinic.clone();
+            Ok(Derivative { velocity, vorticity, density: *density })
+        })
+    }
+
     /*
     fn calculate_vorton_diffusion(&self) -> Result<Vec<Vector>, Box<dyn std::error::Error>> {
         let average_vorticity = self.vortons.iter()
@@ -206,3 +620,158 @@ mod tests {
     }
 }
 */
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Configuration;
+
+    /// The legacy form of the tree, its maximum depth, is read with the default parameters
+    /// and written back with all its parameters
+    #[test]
+    fn it_reads_legacy_tree_algorithm() -> Result<(), Box<dyn std::error::Error>> {
+        let legacy: VortonToVelocityAlgorithm = serde_json::from_str(r#"{"Tree": 4}"#)?;
+        assert_eq!(legacy, VortonToVelocityAlgorithm::Tree { theta: default_theta(), max_leaf: 0, max_depth: 4,
+                                                             rebuild_threshold: default_rebuild_threshold() });
+        let json = serde_json::to_string(&legacy)?;
+        assert_eq!(serde_json::from_str::<VortonToVelocityAlgorithm>(&json)?, legacy);
+        let partial: VortonToVelocityAlgorithm = serde_json::from_str(r#"{"Tree": {"theta": 0.5, "max_leaf": 8}}"#)?;
+        assert_eq!(partial, VortonToVelocityAlgorithm::Tree { theta: 0.5, max_leaf: 8, max_depth: default_max_depth(),
+                                                              rebuild_threshold: default_rebuild_threshold() });
+        for algorithm in [VortonToVelocityAlgorithm::Simple, VortonToVelocityAlgorithm::Fmm(6), VortonToVelocityAlgorithm::Vic] {
+            assert_eq!(serde_json::from_str::<VortonToVelocityAlgorithm>(&serde_json::to_string(&algorithm)?)?, algorithm);
+        }
+        assert_eq!(serde_json::from_str::<VortonToVelocityAlgorithm>(r#""Simple""#)?, VortonToVelocityAlgorithm::Simple);
+        Ok(())
+    }
+
+    #[test]
+    fn it_steps_adaptively_to_target_time() -> Result<(), Box<dyn std::error::Error>> {
+        use std::convert::TryFrom;
+        let mut configuration = Configuration::new_vortex_ring();
+        configuration.n_vortons = 100;
+        let mut simulation = Simulation::try_from(&configuration)?;
+        simulation.use_time_step_controller(TimeStepController { safety_factor: 0.5, min_time_step: 1e-4, max_time_step: 0.02 });
+        let mut profiler = Profiler::new(|| 0.0)?;
+        let time_steps = simulation.step_adaptive(0.1, &mut profiler)?;
+        assert!(time_steps.len() >= 5);
+        assert!(time_steps.iter().all(|dt| *dt <= 0.02 + 1e-12));
+        assert!((simulation.time() - 0.1).abs() < 1e-12);
+        assert_eq!(simulation.iteration(), time_steps.len());
+
+        // A target which is not a multiple of the time step is reached without exceeding it
+        let max_time_step = 0.02;
+        simulation.use_time_step_controller(TimeStepController { safety_factor: 10.0, min_time_step: 1e-4, max_time_step });
+        let time_steps = simulation.step_adaptive(0.125, &mut profiler)?;
+        println!("Time steps: {time_steps:?}");
+        assert!(time_steps.iter().all(|dt| *dt <= max_time_step + 1e-12));
+        assert!((simulation.time() - 0.125).abs() < 1e-12);
+        Ok(())
+    }
+
+    /// Stepping in parallel gives the same vortons, bit for bit, as stepping serially
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn it_steps_deterministically_in_parallel() -> Result<(), Box<dyn std::error::Error>> {
+        use std::convert::TryFrom;
+        let mut configuration = Configuration::new_vortex_ring();
+        configuration.n_vortons = 500;
+        let algorithms = [VortonToVelocityAlgorithm::Simple,
+                          VortonToVelocityAlgorithm::Tree { theta: 0.5, max_leaf: 8, max_depth: 6, rebuild_threshold: 0.2 },
+                          VortonToVelocityAlgorithm::Fmm(4)];
+        for algorithm in algorithms {
+            let run = |n_threads: usize| -> Result<Vec<Vorton>, Box<dyn std::error::Error>> {
+                let mut simulation = Simulation::try_from(&configuration)?;
+                simulation.use_vorton_to_velocity(algorithm.clone());
+                simulation.use_time_integrator(TimeIntegrator::Midpoint);
+                simulation.push_geometry(Geometry::sphere(Point3::new(0.0, 0.0, 1.5), 0.3))?;
+                let mut profiler = Profiler::new(|| 0.0)?;
+                rayon::ThreadPoolBuilder::new().num_threads(n_threads).build()?
+                    .install(|| (0..3).try_for_each(|_| simulation.step(0.05, &mut profiler).map_err(|e| e.to_string())))?;
+                Ok(simulation.vortons().clone())
+            };
+            let (serial, parallel) = (run(1)?, run(4)?);
+            assert_eq!(serial.len(), parallel.len());
+            for (a, b) in serial.iter().zip(parallel.iter()) {
+                let (p, q) = (a.position(), b.position());
+                let (u, v) = (a.vorticity(), b.vorticity());
+                assert!([p.x, p.y, p.z, u.x, u.y, u.z, a.volume()] == [q.x, q.y, q.z, v.x, v.y, v.z, b.volume()],
+                        "{:?}: {:?} vs {:?}", algorithm, a, b);
+            }
+        }
+        Ok(())
+    }
+
+    /// The baroclinic term generates a linear impulse at the rate `g Σ ρ'V`, ie the buoyancy
+    /// force, pointing upwards for a light blob. The smoothing of the density gradient leads
+    /// to an error of a few percent at this resolution.
+    #[test]
+    fn it_generates_impulse_from_buoyancy() -> Result<(), Box<dyn std::error::Error>> {
+        use std::convert::TryFrom;
+        use crate::configuration::{InitialConditionData, BuoyantBlob};
+        let mut configuration = Configuration::new();
+        configuration.n_vortons = 2000;
+        configuration.initial_conditions = InitialConditionData::InitialConditionBuoyantBlob(
+            BuoyantBlob { center: crate::Point3::new(0.0, 0.0, 0.0), radius: 1.0, density: -0.1 });
+        configuration.gravity = Vector3::new(0.0, 0.0, -9.81);
+        let mut simulation = Simulation::try_from(&configuration)?;
+        let buoyancy = simulation.vortons().iter().map(|v| v.density() * v.volume()).sum::<f64>();
+        let time_step = 0.01;
+        let mut profiler = Profiler::new(|| 0.0)?;
+        simulation.step(time_step, &mut profiler)?;
+        let impulse = crate::Diagnostics::new(&simulation)?.linear_impulse;
+        let expected = configuration.gravity.scale(buoyancy * time_step);
+        println!("impulse {impulse:?} vs {expected:?}");
+        assert!(impulse.z > 0.0);
+        assert!((impulse - expected.clone()).norm() < 0.1 * expected.norm());
+        Ok(())
+    }
+
+    /// Probes sample the field of the active algorithm: the vorticity is aligned with the
+    /// vorticity of the vortons in the ring core and vanishes far from the ring
+    #[test]
+    fn it_probes_the_field() -> Result<(), Box<dyn std::error::Error>> {
+        use std::convert::TryFrom;
+        let mut configuration = Configuration::new_vortex_ring();
+        configuration.n_vortons = 500;
+        let mut simulation = Simulation::try_from(&configuration)?;
+        simulation.use_vorton_to_velocity(VortonToVelocityAlgorithm::tree());
+        let vorton = simulation.vortons().iter().max_by(|a, b| a.vorticity().norm().total_cmp(&b.vorticity().norm())).ok_or("No vortons")?.clone();
+        let far = Point3::new(50.0, 50.0, 50.0);
+        let positions = vec![vorton.position().clone(), far.clone()];
+
+        let (velocities, vorticities) = (simulation.velocities_at(&positions)?, simulation.vorticities_at(&positions)?);
+        assert!((velocities[0].clone() - simulation.velocity_at(&positions[0])?).norm() < 1e-12);
+        assert!((vorticities[0].clone() - simulation.vorticity_at(&positions[0])?).norm() < 1e-12);
+        assert!((velocities[1].clone() - simulation.free_stream_velocity().clone()).norm() < 1e-4);
+        let core = &vorticities[0];
+        println!("core vorticity {core:?} vs vorton {:?}, far {:?}", vorton.vorticity(), vorticities[1]);
+        assert!(core.dot(vorton.vorticity()) > 0.9 * core.norm() * vorton.vorticity().norm());
+        assert!(vorticities[1].norm() < 1e-6 * core.norm());
+        Ok(())
+    }
+
+    /// The panels of a sphere ahead of a vortex ring cancel the flow of the ring through the
+    /// sphere, and their velocity is added to the velocity of the vortons
+    #[test]
+    fn it_solves_panels_on_geometries() -> Result<(), Box<dyn std::error::Error>> {
+        use std::convert::TryFrom;
+        let mut configuration = Configuration::new_vortex_ring();
+        configuration.n_vortons = 200;
+        let mut simulation = Simulation::try_from(&configuration)?;
+        simulation.push_geometry(Geometry::sphere(Point3::new(2.0, 0.0, 0.0), 0.5))?;
+        simulation.use_panel_method(true);
+        let far = Point3::new(2.0, 2.0, 0.0);
+        let before = simulation.velocity_at(&far)?;
+        let centers = simulation.geometries[0].panels().into_iter().map(|p| p.center).collect::<Vec<_>>();
+        let velocities = simulation.velocities_at(&centers)?;
+        simulation.solve_panels()?;
+
+        let panels = simulation.panels().ok_or("No panels")?;
+        for (i, u) in velocities.iter().enumerate() {
+            assert!((panels.surface_velocity(i) + u.clone()).dot(&panels.panels()[i].normal).abs() < 1e-6);
+        }
+        assert!((simulation.velocity_at(&far)? - (before + panels.velocity_at(&far))).norm() < 1e-12);
+        Ok(())
+    }
+}
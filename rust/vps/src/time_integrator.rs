@@ -0,0 +1,124 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{Vector3, Vorton};
+
+/// Time derivative of a vorton state: the velocity advecting its position and the rate of
+/// change of its vorticity and density perturbation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Derivative {
+    pub velocity: Vector3<f64>,
+    pub vorticity: Vector3<f64>,
+    #[serde(default)]
+    pub density: f64,
+}
+
+/// Scheme used to advance vortons in time
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum TimeIntegrator {
+    /// Forward Euler, first order
+    #[default]
+    Euler,
+    /// Explicit midpoint (second order Runge-Kutta)
+    Midpoint,
+    /// Classical fourth order Runge-Kutta
+    RungeKutta4,
+    /// Second order Adams-Bashforth. Uses the derivative from the previous step and falls back
+    /// to forward Euler for vortons where it is not available. The weights account for a
+    /// change of the time step since the previous step.
+    AdamsBashforth2,
+}
+
+impl TimeIntegrator {
+    /// Advance `vortons` by `time_step`. The function `f` evaluates the derivative of a set of
+    /// vortons. `history` holds the derivatives of each vorton from the previous step for
+    /// multi-step schemes and is updated on return, along with `previous_time_step`, the time
+    /// step it was evaluated for.
+    pub fn step<F>(&self, vortons: &[Vorton], history: &mut Vec<Option<Derivative>>, previous_time_step: &mut Option<f64>,
+                   time_step: f64, f: F)
+        -> Result<Vec<Vorton>, Box<dyn std::error::Error>>
+        where F: Fn(&[Vorton]) -> Result<Vec<Derivative>, Box<dyn std::error::Error>>
+    {
+        let k1 = f(vortons)?;
+        let r = match self {
+            TimeIntegrator::Euler => {
+                TimeIntegrator::update(vortons, &[(1.0, &k1)], time_step)
+            },
+            TimeIntegrator::Midpoint => {
+                let k2 = f(&TimeIntegrator::update(vortons, &[(1.0, &k1)], 0.5*time_step))?;
+                TimeIntegrator::update(vortons, &[(1.0, &k2)], time_step)
+            },
+            TimeIntegrator::RungeKutta4 => {
+                let k2 = f(&TimeIntegrator::update(vortons, &[(1.0, &k1)], 0.5*time_step))?;
+                let k3 = f(&TimeIntegrator::update(vortons, &[(1.0, &k2)], 0.5*time_step))?;
+                let k4 = f(&TimeIntegrator::update(vortons, &[(1.0, &k3)], time_step))?;
+                TimeIntegrator::update(vortons, &[(1.0/6.0, &k1), (2.0/6.0, &k2), (2.0/6.0, &k3), (1.0/6.0, &k4)], time_step)
+            },
+            TimeIntegrator::AdamsBashforth2 => {
+                let previous = k1.iter().enumerate()
+                    .map(|(i, k)| history.get(i).cloned().flatten().unwrap_or_else(|| k.clone()))
+                    .collect::<Vec<Derivative>>();
+                // Ratio of the time steps, assumed to be one when the previous time step is unknown
+                let ratio = previous_time_step.map_or(1.0, |dt| time_step / dt);
+                TimeIntegrator::update(vortons, &[(1.0 + 0.5*ratio, &k1), (-0.5*ratio, &previous)], time_step)
+            },
+        };
+        if *self == TimeIntegrator::AdamsBashforth2 {
+            *history = k1.into_iter().map(Some).collect(); *previous_time_step = Some(time_step);
+        } else {
+            *history = Vec::new(); *previous_time_step = None;
+        }
+        Ok(r)
+    }
+
+    /// Returns the vortons advanced by `time_step` using the weighted sum of derivatives
+    fn update(vortons: &[Vorton], derivatives: &[(f64, &Vec<Derivative>)], time_step: f64) -> Vec<Vorton> {
+        vortons.iter().enumerate()
+            .map(|(i, vorton)| {
+                let d = derivatives.iter()
+                    .fold(Derivative::default(), |r, (w, d)| Derivative {
+                        velocity: r.velocity + d[i].velocity.scale(*w),
+                        vorticity: r.vorticity + d[i].vorticity.scale(*w),
+                        density: r.density + d[i].density * w,
+                    });
+                vorton.advect(&d.velocity, time_step).step(&d.vorticity, time_step).step_density(d.density, time_step)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::Point3;
+
+  /// Advect a vorton in a solid body rotation `u = Ω × x` over a full revolution and
+  /// check the convergence order of each scheme. With `variable`, the time steps alternate
+  /// between two thirds and four thirds of the mean time step.
+  fn error(integrator: TimeIntegrator, n: usize, variable: bool) -> Result<f64, Box<dyn std::error::Error>> {
+    let vortons = vec![Vorton::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0), 0.1)];
+    let f = |vortons: &[Vorton]| Ok(vortons.iter()
+            .map(|v| Derivative { velocity: Vector3::z().cross(&(v.position() - &Point3::origin())), vorticity: Vector3::default(), density: 0.0 })
+            .collect());
+    let time_step = 2.0 * std::f64::consts::PI / n as f64;
+    let (mut history, mut previous_time_step) = (Vec::new(), None);
+    let r = (0..n).try_fold(vortons, |vortons, i| {
+      let factor = if !variable { 1.0 } else if i % 2 == 0 { 2.0 / 3.0 } else { 4.0 / 3.0 };
+      integrator.step(&vortons, &mut history, &mut previous_time_step, factor * time_step, f)
+    })?;
+    Ok((r[0].position() - &Point3::new(1.0, 0.0, 0.0)).norm())
+  }
+
+  #[test]
+  fn it_converges_at_expected_order() -> Result<(), Box<dyn std::error::Error>> {
+    for (integrator, order) in [(TimeIntegrator::Euler, 1.0), (TimeIntegrator::Midpoint, 2.0),
+                                (TimeIntegrator::RungeKutta4, 4.0), (TimeIntegrator::AdamsBashforth2, 2.0)] {
+      for variable in [false, true] {
+        let e1 = error(integrator, 200, variable)?; let e2 = error(integrator, 400, variable)?;
+        let measured = (e1 / e2).log2();
+        println!("{integrator:?}, variable {variable}: {e1} / {e2} -> order {measured}");
+        assert!((measured - order).abs() < 0.3);
+      }
+    }
+    Ok(())
+  }
+}
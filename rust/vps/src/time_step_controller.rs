@@ -0,0 +1,65 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{Vorton, Vector3, Matrix3};
+
+/// Adaptive time step controller. The time step is selected so that no vorton travels
+/// further than its core radius, and so that the flow strain rate is resolved:
+/// `dt = safety_factor * min( min(core_radius / |u|), 1 / max(|∇u|) )`
+/// bounded by `[min_time_step, max_time_step]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeStepController {
+    pub safety_factor: f64,
+    pub min_time_step: f64,
+    pub max_time_step: f64,
+}
+
+impl Default for TimeStepController {
+    fn default() -> Self {
+        TimeStepController {
+            safety_factor: 0.5,
+            min_time_step: 1e-5,
+            max_time_step: 0.1,
+        }
+    }
+}
+
+impl TimeStepController {
+    /// Returns the time step associated with the velocity and velocity gradient at each vorton
+    pub fn time_step<'a, I>(&self, values: I) -> f64
+    where I: Iterator<Item = (&'a Vorton, Vector3<f64>, Matrix3<f64>)>
+    {
+        let time_step = values
+            .map(|(vorton, velocity, gradient)| {
+                let advection = vorton.core_radius() / velocity.norm();
+                let strain = 1.0 / gradient.norm();
+                advection.min(strain)
+            })
+            .fold(f64::INFINITY, f64::min);
+        (self.safety_factor * time_step).max(self.min_time_step).min(self.max_time_step)
+    }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::Point3;
+
+  #[test]
+  fn it_bounds_time_step() {
+    let controller = TimeStepController { safety_factor: 0.5, min_time_step: 1e-3, max_time_step: 1.0 };
+    let vorton = Vorton::new(Point3::origin(), Vector3::new(0.0, 0.0, 1.0), 1e-3);
+    let radius = vorton.core_radius();
+    let still = Matrix3::default();
+
+    // Limited by advection over the core radius
+    let dt = controller.time_step(vec![(&vorton, Vector3::new(2.0, 0.0, 0.0), still.clone())].into_iter());
+    assert!((dt - 0.5 * radius / 2.0).abs() < 1e-12);
+    // Limited by the strain rate
+    let strain = Matrix3::new([[10.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]]);
+    let dt = controller.time_step(vec![(&vorton, Vector3::new(1e-6, 0.0, 0.0), strain)].into_iter());
+    assert!((dt - 0.05).abs() < 1e-12);
+    // Bounded
+    assert_eq!(controller.time_step(vec![(&vorton, Vector3::default(), still.clone())].into_iter()), 1.0);
+    assert_eq!(controller.time_step(vec![(&vorton, Vector3::new(1e6, 0.0, 0.0), still)].into_iter()), 1e-3);
+  }
+}
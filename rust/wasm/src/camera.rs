@@ -194,7 +194,7 @@ impl Camera {
     }
 
     fn touch_mid(touches: &HashMap<i32, Touch>) -> (f32, f32) {
-        let alpha: f32 = 1f32 / if touches.len() > 0 { touches.len() as f32 } else { 1f32 };
+        let alpha: f32 = 1f32 / if !touches.is_empty() { touches.len() as f32 } else { 1f32 };
         touches.iter().fold((0f32, 0f32), |r, (_, t)| (r.0 + alpha*t.client_x() as f32, r.1 + alpha*t.client_y() as f32))
     }
 
@@ -209,7 +209,7 @@ impl Camera {
             if b.client_y() > a.client_y() {
                 1f32 * ((b.client_x() as f32 - a.client_x() as f32)/l).acos()
             } else {
-                -1f32 * ((b.client_x() as f32 - a.client_x() as f32)/l).acos()
+                -((b.client_x() as f32 - a.client_x() as f32)/l).acos()
             }
         } else {
             0f32
@@ -221,11 +221,11 @@ impl Camera {
         let mut alpha = 0f32; let mut count = 0;
         for (k, v_from) in touches_from.iter() {
             if let Some(v_to) = touches_to.get(k) {
-                if reference.is_none() { 
-                    reference = Some((v_from, v_to)); 
-                } else {
+                if let Some((r_from, r_to)) = reference {
                     count += 1;
-                    alpha += Camera::alpha(reference.unwrap().1, v_to) - Camera::alpha(reference.unwrap().0, v_from);
+                    alpha += Camera::alpha(r_to, v_to) - Camera::alpha(r_from, v_from);
+                } else {
+                    reference = Some((v_from, v_to)); 
                 }
             }
         }
@@ -1,6 +1,7 @@
 use std::{
     default::Default,
-    sync::{Arc, Mutex,},
+    cell::RefCell,
+    rc::Rc,
 };
 use wasm_bindgen::{JsCast, JsValue, prelude::wasm_bindgen};
 use js_sys::{ArrayBuffer};
@@ -22,12 +23,13 @@ use camera::Camera;
 pub struct Simulation {
     parameters: Parameters,
     solution: Option<Solution>,
-    viewer: Option<Arc<Mutex<Viewer>>>,
+    viewer: Option<Rc<RefCell<Viewer>>>,
     camera: Option<Camera>,
 }
 
 #[wasm_bindgen]
 impl Simulation {
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Self {
         Simulation { 
             parameters: Parameters::default(),
@@ -69,12 +71,21 @@ impl Simulation {
 
     pub fn step(&mut self, time_step: f64) -> Result<(), JsValue> {
         self.solution.as_mut()
-            .ok_or_else(|| JsValue::from_str(format!("Simulation::step - solution is not available").as_str()))?
+            .ok_or_else(|| JsValue::from_str("Simulation::step - solution is not available"))?
             .step(time_step)
             .map_err(|e| JsValue::from_str(format!("Simulation::step - Error: {}", e).as_str()))?;
         Ok(())
     }
 
+    /// Advance the simulation by `duration` using adaptive time steps. Returns the number of sub-steps taken.
+    pub fn step_adaptive(&mut self, duration: f64) -> Result<JsValue, JsValue> {
+        let n = self.solution.as_mut()
+            .ok_or_else(|| JsValue::from_str("Simulation::step_adaptive - solution is not available"))?
+            .step_adaptive(duration)
+            .map_err(|e| JsValue::from_str(format!("Simulation::step_adaptive - Error: {}", e).as_str()))?;
+        Ok(JsValue::from_f64(n as f64))
+    }
+
     pub fn iteration(&self) -> JsValue {
         if let Some(solution) = self.solution.as_ref() {
             JsValue::from_f64(solution.iteration() as f64)
@@ -85,15 +96,24 @@ impl Simulation {
 
     pub fn time(&self) -> JsValue {
         if let Some(solution) = self.solution.as_ref() {
-            JsValue::from_f64(solution.time() as f64)
+            JsValue::from_f64(solution.time())
         } else {
           JsValue::from_f64(0f64)
         }
     }
 
+    /// Conservation diagnostics of the current solution as a JS object
+    pub fn diagnostics(&self) -> Result<JsValue, JsValue> {
+        let diagnostics = self.solution.as_ref()
+            .ok_or_else(|| JsValue::from_str("Simulation::diagnostics - solution is not available"))?
+            .diagnostics()
+            .map_err(|e| JsValue::from_str(format!("Simulation::diagnostics - Error: {}", e).as_str()))?;
+        Ok(serde_wasm_bindgen::to_value(&diagnostics)?)
+    }
+
     pub fn solution_to_arraybuffer(&self) -> Result<ArrayBuffer, JsValue> {
         match &self.solution {
-            None    => Err(JsValue::from_str(format!("Simulation::solution_to_arraybuffer: No solution is available to be converted.").as_str())),
+            None    => Err(JsValue::from_str("Simulation::solution_to_arraybuffer: No solution is available to be converted.")),
             Some(s) => {
                 Ok(s.to_arraybuffer()
                    .map_err(|e| JsValue::from_str(format!("Simulation::solution_to_arraybuffer - Error converting simulation to array buffer: {}", e).as_str()))?)
@@ -113,7 +133,7 @@ impl Simulation {
     pub fn initialize_viewer(&mut self, element_id: &str) -> Result<(), JsValue> {
         let viewer = Viewer::from_element_id(element_id)
             .map_err(|e| JsValue::from_str(format!("Simulation::initialize_viewer - Error making viewer: {}", e).as_str()))?;
-        self.viewer = Some(Arc::new(Mutex::new(viewer)));
+        self.viewer = Some(Rc::new(RefCell::new(viewer)));
         Ok(())
     }
 
@@ -121,7 +141,7 @@ impl Simulation {
         if self.viewer.is_none() {
             return js_sys::Promise::reject(&JsValue::from_str("Simulation::draw - Error: view is not initialised"));
         }
-        let viewer = Arc::clone(self.viewer.as_ref().unwrap());
+        let viewer = Rc::clone(self.viewer.as_ref().unwrap());
 
         let data = data.as_string();
         if data.is_none() {
@@ -131,11 +151,12 @@ impl Simulation {
 
         wasm_bindgen_futures::future_to_promise(
           async move {
-              viewer.lock().unwrap()
-                  .create_view(data.as_str())
+              // The view is made before borrowing the viewer, which is not held across the await
+              let view = Viewer::to_view(data.as_str())
                   .await
-                  .map(|uuid| JsValue::from_str(uuid.to_hyphenated().to_string().as_str()))
-                  .map_err(|e| JsValue::from_str(format!("{}", e).as_str()))
+                  .map_err(|e| JsValue::from_str(format!("{}", e).as_str()))?;
+              let uuid = viewer.borrow_mut().add_view(view);
+              Ok(JsValue::from_str(uuid.to_hyphenated().to_string().as_str()))
           }
         )
     }
@@ -149,7 +170,7 @@ impl Simulation {
         let camera = self.camera.as_mut().unwrap();
         let mut viewer = self.viewer.as_ref()
             .ok_or_else(|| JsValue::from_str("Simulation::draw - Error: viewer is not initialised"))?
-            .lock().unwrap();
+            .borrow_mut();
 
         if camera.width().is_none() 
             || camera.height().is_none() 
@@ -160,7 +181,7 @@ impl Simulation {
             }
 
         if let Some(solution) = self.solution.as_ref() {
-            viewer.draw(solution, &camera)
+            viewer.draw(solution, camera)
                 .map_err(|e| JsValue::from_str(format!("Simulation::draw - Error: {}", e).as_str()))?;
         }
 
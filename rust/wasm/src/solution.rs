@@ -4,9 +4,8 @@ use std::{
 use wasm_bindgen::{JsValue, prelude::wasm_bindgen};
 use web_sys::{console};
 use js_sys::{ArrayBuffer, Uint8Array};
-use bincode;
 
-use vortex_particle_simulation::{Simulation, Profiler};
+use vortex_particle_simulation::{Simulation, Profiler, Diagnostics};
 
 #[wasm_bindgen(module = "/functions.js")]
 extern "C" {
@@ -46,6 +45,23 @@ impl Solution {
         self.simulation.time()
     }
 
+    pub fn diagnostics(&self) -> Result<Diagnostics, Box<dyn Error>> {
+        Diagnostics::new(&self.simulation)
+    }
+
+    /// Advance the solution by `duration` using adaptive time steps. Returns the number of sub-steps taken.
+    pub fn step_adaptive(&mut self, duration: f64) -> Result<usize, Box<dyn Error>> {
+        let mut profiler = Profiler::new(|| {time_now_ms()}).unwrap();
+        let target_time = self.simulation.time() + duration;
+        let time_steps = self.simulation.step_adaptive(target_time, &mut profiler)?;
+        console::log_1(&JsValue::from_str(
+                format!("Iteration {} - {:.2}s ({} sub-steps) [{}]", self.simulation.iteration(), self.simulation.time(), time_steps.len(),
+                        profiler.as_magnitude()
+                        .iter().fold("".to_string(), |r, v| format!("{}{}{}: {:.1}ms", r, if r.is_empty() {""} else {"; "}, v.0, v.1))
+                        ).as_str()));
+        Ok(time_steps.len())
+    }
+
     pub fn step(&mut self, time_step: f64) -> Result<(), Box<dyn Error>> {
         let mut profiler = Profiler::new(|| {time_now_ms()}).unwrap();
 
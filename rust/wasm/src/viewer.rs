@@ -25,6 +25,7 @@ mod webgl;
 use webgl::{webgl_link_program, webgl_compile_vertex_shader, webgl_compile_fragment_shader};
 
 pub trait View {
+    #[allow(dead_code)]
     fn reset(&mut self) -> Result<(), Box<dyn Error>>;
     fn draw(&mut self, context: &WebGl2RenderingContext, camera: &Camera, simulation: &Simulation) -> Result<(), Box<dyn Error>>;
     fn redraw(&mut self, context: &WebGl2RenderingContext, camera: &Camera) -> Result<(), Box<dyn Error>>;
@@ -34,6 +35,7 @@ pub trait View {
 #[serde(tag = "type")]
 enum ViewType {
     VortonRender,
+    TracerRender,
     SkyBox,
 }
 
@@ -43,11 +45,10 @@ pub struct Viewer {
 }
 
 impl Viewer {
-    pub async fn create_view(&mut self, data: &str) -> Result<Uuid, Box<dyn Error>> {
+    pub fn add_view(&mut self, view: Box<dyn View>) -> Uuid {
         let uuid = Uuid::new_v4();
-        let view = Viewer::to_view(data).await?;
-        self.views.insert(uuid.clone(), view);
-        Ok(uuid)
+        self.views.insert(uuid, view);
+        uuid
     }
 
     /*
@@ -61,9 +62,10 @@ impl Viewer {
     }
     */
 
-    async fn to_view(data: &str) -> Result<Box<dyn View>, Box<dyn Error>> {
+    pub async fn to_view(data: &str) -> Result<Box<dyn View>, Box<dyn Error>> {
         let view: Box<dyn View> = match serde_json::from_str(data)? {
             ViewType::VortonRender => Box::new(ProgramVortonRender::new()?),
+            ViewType::TracerRender => Box::new(ProgramVortonRender::new_tracers()?),
             ViewType::SkyBox => Box::new(ProgramSkyBox::new().await?),
         };
         Ok(view)
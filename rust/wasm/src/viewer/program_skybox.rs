@@ -109,6 +109,7 @@ pub struct ProgramSkyBox {
 }
 
 impl ProgramSkyBox {
+    #[allow(clippy::identity_op, clippy::erasing_op)]
     pub async fn new() -> Result<ProgramSkyBox, Box<dyn Error>> {
         let url = "/vpm/assets/skybox/miramar_large.jpg";
         let image_params = ImageParams {
@@ -190,7 +191,7 @@ impl ProgramSkyBox {
                  "##,
                  )?;
 
-             self.program = Some(webgl_link_program(&context, &vert_shared, &frag_shader)?);
+             self.program = Some(webgl_link_program(context, &vert_shared, &frag_shader)?);
         }
         self.program
             .as_ref()
@@ -255,7 +256,7 @@ impl View for ProgramSkyBox {
             // Textures
             let texture = context.create_texture();
             context.bind_texture(WebGl2RenderingContext::TEXTURE_CUBE_MAP, texture.as_ref());
-            self.image_params.assign_textures(&context, &self.image)?;
+            self.image_params.assign_textures(context, &self.image)?;
             context.generate_mipmap(WebGl2RenderingContext::TEXTURE_CUBE_MAP);
             context.tex_parameteri(WebGl2RenderingContext::TEXTURE_CUBE_MAP,
                                    WebGl2RenderingContext::TEXTURE_MIN_FILTER,
@@ -10,9 +10,16 @@ use super::{
 };
 use super::{webgl_link_program, webgl_compile_vertex_shader, webgl_compile_fragment_shader};
 
+/// Particles drawn by the view
+enum Particles {
+    Vortons,
+    Tracers,
+}
+
 pub struct ProgramVortonRender
 {
     program: Option<WebGlProgram>,
+    particles: Particles,
     vertices: Vec<f32>,
     n_vertices: usize,
 }
@@ -21,11 +28,17 @@ impl ProgramVortonRender {
     pub fn new() -> Result<ProgramVortonRender, Box<dyn Error>> {
         Ok(ProgramVortonRender {
             program: None,
+            particles: Particles::Vortons,
             vertices: Vec::new(),
             n_vertices: 0,
         })
     }
 
+    /// Render the tracers instead of the vortons
+    pub fn new_tracers() -> Result<ProgramVortonRender, Box<dyn Error>> {
+        Ok(ProgramVortonRender { particles: Particles::Tracers, ..ProgramVortonRender::new()? })
+    }
+
     fn program(&mut self, context: &WebGl2RenderingContext) -> Result<&WebGlProgram, Box<dyn Error>> {
         if self.program.is_none() {
           let vert_shader = webgl_compile_vertex_shader(
@@ -44,15 +57,24 @@ impl ProgramVortonRender {
 
           let frag_shader = webgl_compile_fragment_shader(
               context,
-            r##"
+              match self.particles {
+                  Particles::Vortons => r##"
      precision mediump float;
      void main()
      {
        gl_FragColor = vec4(0.9, 0.9, 0.9, 1);
      }
             "##,
+                  Particles::Tracers => r##"
+     precision mediump float;
+     void main()
+     {
+       gl_FragColor = vec4(0.95, 0.6, 0.2, 1);
+     }
+            "##,
+              },
             )?;
-          self.program = Some(webgl_link_program(&context, &vert_shader, &frag_shader)?);
+          self.program = Some(webgl_link_program(context, &vert_shader, &frag_shader)?);
         }
         self.program
             .as_ref()
@@ -77,15 +99,17 @@ impl View for ProgramVortonRender {
         };
         context.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
 
-        self.n_vertices = simulation.vortons().len();
+        let positions = match self.particles {
+            Particles::Vortons => simulation.vortons().iter().map(|v| v.position()).collect::<Vec<_>>(),
+            Particles::Tracers => simulation.tracers().tracers().iter().map(|t| t.position()).collect::<Vec<_>>(),
+        };
+        self.n_vertices = positions.len();
         self.vertices 
-            = simulation
-            .vortons()
+            = positions
             .iter()
             .fold(
                 Vec::new(),
-                |mut r, v| {
-                    let p = v.position();
+                |mut r, p| {
                     r.push(p.x as f32);
                     r.push(p.y as f32);
                     r.push(p.z as f32);